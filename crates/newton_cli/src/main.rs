@@ -0,0 +1,370 @@
+mod output;
+mod render;
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{bail, Context, Result};
+use clap::{Parser, ValueEnum};
+use newton_core::{
+    compare_subdivided, png_scene, render_frame_records, render_frame_subdivided,
+    render_frame_wide, ChaosColor, ColorScheme, GradientPalette, IterMethod, LightnessCurve,
+    PixelData32, RawData, RawFormat, Scene, SceneColoring, SceneView, SchemeOptions, SubdivideSpec,
+    CANVAS_SIZE, SCENE_VERSION,
+};
+
+use crate::{
+    output::{write_exr, write_pfm, write_png},
+    render::color_frame,
+};
+
+///////////////////////////////////////////////////////////////////
+
+/// Renders a Newton fractal to a PNG, without needing a browser. The PNG carries the scene it was
+/// rendered from, so it can be passed back to `--scene` to render it again. For grading, it can
+/// also write linear light as floats to a PFM or OpenEXR file.
+#[derive(Parser)]
+#[command(version)]
+struct Args {
+    /// Polynomial to render, eg. "z^3 - 1"
+    #[arg(required_unless_present = "scene")]
+    formula: Option<String>,
+
+    /// Render a scene file, in JSON or TOML, or the scene of a PNG rendered earlier, instead of the
+    /// formula, view and coloring options
+    #[arg(long, conflicts_with_all = SCENE_ARGS)]
+    scene: Option<PathBuf>,
+
+    /// Also write the scene to a file, as TOML if it ends in .toml and as JSON otherwise
+    #[arg(long)]
+    save_scene: Option<PathBuf>,
+
+    /// Root finding method id, where 0 is Newton's method
+    #[arg(short, long, default_value_t = 0)]
+    method: u32,
+
+    /// Method parameter, in order. Repeat for methods taking several; omit for the defaults
+    #[arg(short, long = "param")]
+    params: Vec<f32>,
+
+    /// Zoom factor, where smaller values zoom in
+    #[arg(long, default_value_t = 1.)]
+    scale: f32,
+
+    /// Rotation of the view, in degrees
+    #[arg(long, default_value_t = 0., allow_negative_numbers = true)]
+    rotation: f32,
+
+    /// Horizontal shear of the view, applied before rotation
+    #[arg(long, default_value_t = 0., allow_negative_numbers = true)]
+    shear: f32,
+
+    /// Real part of the view's center
+    #[arg(long, default_value_t = 0., allow_negative_numbers = true)]
+    x: f32,
+
+    /// Imaginary part of the view's center
+    #[arg(long, default_value_t = 0., allow_negative_numbers = true)]
+    y: f32,
+
+    /// Width of the image, in pixels
+    #[arg(long, default_value_t = CANVAS_SIZE)]
+    width: usize,
+
+    /// Height of the image, in pixels
+    #[arg(long, default_value_t = CANVAS_SIZE)]
+    height: usize,
+
+    /// How lightness follows convergence speed: normal, inverted, parabola, inverted-parabola,
+    /// or a curve such as "gamma 2.2", "points 0,0 0.5,0.8 1,1", "bezier 0.4 0 0.2 1" or
+    /// "expr 1 - (1 - v)^3"
+    #[arg(long, default_value = "normal", value_parser = LightnessCurve::parse)]
+    lightness: LightnessCurve,
+
+    /// How quickly colors fade with iteration count, on the same 0 to 1 scale as the web app
+    #[arg(long, default_value_t = 0.5)]
+    dropoff: f32,
+
+    /// Color for points that never converge
+    #[arg(long, value_enum, default_value_t = Chaos::Black)]
+    chaos: Chaos,
+
+    /// Color the roots with one of the web app's color schemes, instead of by their own arguments
+    /// and distances from the origin. The colors go into the scene.
+    #[arg(long, value_enum)]
+    scheme: Option<Scheme>,
+
+    /// Shift the scheme's hues around the color wheel, in degrees
+    #[arg(
+        long,
+        default_value_t = 0.,
+        allow_negative_numbers = true,
+        requires = "scheme"
+    )]
+    hue_offset: f32,
+
+    /// Saturation of the scheme's colors, from 0 to 1
+    #[arg(long, default_value_t = 0.25, requires = "scheme")]
+    chromaticity: f32,
+
+    /// Start the scheme's hues at 0°, instead of the first root's argument
+    #[arg(long, requires = "scheme")]
+    static_hues: bool,
+
+    /// Color each root with a gradient from a palette file, instead of its own hue getting darker.
+    /// Saved with the scene, and replaces the palette of one passed to --scene
+    #[arg(long, conflicts_with = "lightness")]
+    palette: Option<PathBuf>,
+
+    /// Skip iterating the insides of solid basins, at the risk of missing small details
+    #[arg(long)]
+    subdivide: bool,
+
+    /// Also render every pixel, and report how far the subdivided render is from it
+    #[arg(long, requires = "subdivide")]
+    check_subdivide: bool,
+
+    /// Image to write. Ending in .pfm or .exr, it holds linear floating point RGB; otherwise
+    /// it's an sRGB PNG
+    #[arg(short, long, default_value = "newton.png")]
+    output: PathBuf,
+
+    /// Bits per channel of PNG output. 16 avoids banding in smooth shading
+    #[arg(long, value_enum, default_value_t = BitDepth::Eight)]
+    bit_depth: BitDepth,
+
+    /// Also write the root index and smooth iteration count of every pixel, as a NumPy .npz
+    /// archive, plain .npy files or a .csv file. All record how pixels map onto the complex
+    /// plane. For .npy, each array gets its own file, named like `data_root_index.npy` for
+    /// `data.npy`.
+    #[arg(long)]
+    raw: Option<PathBuf>,
+
+    /// Include where z ended up in the raw data. Renders the frame a second time, keeping more per
+    /// pixel, and ignores `--subdivide`
+    #[arg(long, requires = "raw")]
+    raw_z: bool,
+}
+
+/// Options a scene file sets, which can't be given alongside one
+const SCENE_ARGS: [&str; 15] = [
+    "formula",
+    "method",
+    "params",
+    "scale",
+    "rotation",
+    "shear",
+    "x",
+    "y",
+    "lightness",
+    "dropoff",
+    "chaos",
+    "scheme",
+    "hue_offset",
+    "chromaticity",
+    "static_hues",
+];
+
+#[derive(Clone, Copy, ValueEnum)]
+enum Chaos {
+    Black,
+    White,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum Scheme {
+    ContrastingHues,
+    LinearHues,
+    Monochromatic,
+    GoldenAngle,
+    EquidistantHues,
+}
+
+#[derive(Clone, Copy, PartialEq, ValueEnum)]
+enum BitDepth {
+    #[value(name = "8")]
+    Eight,
+    #[value(name = "16")]
+    Sixteen,
+}
+
+///////////////////////////////////////////////////////////////////
+
+fn main() -> Result<()> {
+    let args = Args::parse();
+
+    let mut scene = match &args.scene {
+        Some(path) => {
+            read_scene(path).with_context(|| format!("Failed to read {}", path.display()))?
+        }
+        None => scene_from_args(&args)?,
+    };
+    if let Some(path) = &args.palette {
+        let palette =
+            read_palette(path).with_context(|| format!("Failed to read {}", path.display()))?;
+        scene.coloring.palette = Some(palette);
+    }
+    if let Some(path) = &args.save_scene {
+        write_scene(path, &scene).with_context(|| format!("Failed to write {}", path.display()))?;
+    }
+
+    let fz = scene.fz()?;
+    let roots = scene.roots()?;
+    let method = IterMethod::by_id(scene.method)?;
+    let params = method.resolve_params(&scene.params)?;
+
+    if args.width == 0 || args.height == 0 {
+        bail!("Image must be at least 1x1 pixels");
+    }
+    let viewport = scene.viewport(args.width, args.height);
+
+    // Checked up front, rather than after a long render
+    let raw_output = match &args.raw {
+        Some(path) => {
+            let extension = path.extension().unwrap_or_default().to_string_lossy();
+            Some((path, RawFormat::from_extension(&extension)?))
+        }
+        None => None,
+    };
+
+    let spec = SubdivideSpec::default();
+    if args.check_subdivide {
+        let report =
+            compare_subdivided::<PixelData32>(&fz, &roots.roots, method, &params, &viewport, &spec);
+        println!(
+            "Subdivision mismatched {:.3}% of pixels, and iterated {:.1}% of them",
+            report.mismatch_percent, report.computed_percent
+        );
+    }
+
+    // 32 bits per pixel, so every polynomial that has roots can be drawn
+    let pixel_data: Vec<PixelData32> = match args.subdivide {
+        true => render_frame_subdivided(&fz, &roots.roots, method, &params, &viewport, &spec),
+        false => render_frame_wide(&fz, &roots.roots, method, &params, &viewport),
+    };
+    if let Some((path, format)) = raw_output {
+        let raw_data = match args.raw_z {
+            true => {
+                let records = render_frame_records(&fz, &roots.roots, method, &params, &viewport);
+                RawData::from_records(&records, &viewport)
+            }
+            false => RawData::from_pixels(&pixel_data, &viewport),
+        };
+        let files = match format {
+            RawFormat::Npy => {
+                let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+                raw_data
+                    .to_npy_files()
+                    .into_iter()
+                    .map(|(name, bytes)| (path.with_file_name(format!("{stem}_{name}")), bytes))
+                    .collect()
+            }
+            _ => vec![(path.clone(), raw_data.encode(format)?)],
+        };
+        for (path, bytes) in files {
+            fs::write(&path, bytes)
+                .with_context(|| format!("Failed to write {}", path.display()))?;
+        }
+    }
+
+    let colors = color_frame(&pixel_data, &roots, &scene.coloring);
+
+    let (path, width, height) = (&args.output, viewport.width, viewport.height);
+    let written = if has_extension(path, "pfm") {
+        write_pfm(path, width, height, &colors)
+    } else if has_extension(path, "exr") {
+        write_exr(path, width, height, &colors)
+    } else {
+        let deep = args.bit_depth == BitDepth::Sixteen;
+        write_png(path, width, height, &colors, deep, &scene)
+    };
+    written.with_context(|| format!("Failed to write {}", path.display()))
+}
+
+fn scene_from_args(args: &Args) -> Result<Scene> {
+    let mut scene = Scene {
+        version: SCENE_VERSION,
+        formula: args.formula.clone().unwrap_or_default(),
+        method: args.method,
+        params: args.params.clone(),
+        view: SceneView {
+            center: [args.x, args.y],
+            scale: args.scale,
+            rotation: args.rotation,
+            shear: args.shear,
+        },
+        coloring: SceneColoring {
+            lightness: args.lightness.clone(),
+            dropoff: args.dropoff,
+            chaos: args.chaos.into(),
+            palette: None,
+        },
+        root_colors: None,
+    };
+
+    if let Some(scheme) = args.scheme {
+        let options = SchemeOptions {
+            hue_offset: args.hue_offset,
+            chromaticity: args.chromaticity,
+            static_hues: args.static_hues,
+        };
+        let colors = ColorScheme::from(scheme).colors(&scene.roots()?.roots, &options);
+        scene.root_colors = Some(colors);
+    }
+    scene.validate()?;
+    Ok(scene)
+}
+
+fn has_extension(path: &Path, extension: &str) -> bool {
+    path.extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case(extension))
+}
+
+fn read_scene(path: &Path) -> Result<Scene> {
+    if has_extension(path, "png") {
+        return png_scene(&fs::read(path)?);
+    }
+
+    let text = fs::read_to_string(path)?;
+    match has_extension(path, "toml") {
+        true => Scene::from_toml(&text),
+        false => Scene::from_json(&text),
+    }
+}
+
+fn read_palette(path: &Path) -> Result<GradientPalette> {
+    GradientPalette::parse(&fs::read_to_string(path)?)
+}
+
+fn write_scene(path: &Path, scene: &Scene) -> Result<()> {
+    let text = match has_extension(path, "toml") {
+        true => scene.to_toml()?,
+        false => scene.to_json()?,
+    };
+    Ok(fs::write(path, text)?)
+}
+
+///////////////////////////////////////////////////////////////////
+
+impl From<Scheme> for ColorScheme {
+    fn from(value: Scheme) -> Self {
+        match value {
+            Scheme::ContrastingHues => ColorScheme::ContrastingHues,
+            Scheme::LinearHues => ColorScheme::LinearHues,
+            Scheme::Monochromatic => ColorScheme::Monochromatic,
+            Scheme::GoldenAngle => ColorScheme::GoldenAngle,
+            Scheme::EquidistantHues => ColorScheme::EquidistantHues,
+        }
+    }
+}
+
+impl From<Chaos> for ChaosColor {
+    fn from(value: Chaos) -> Self {
+        match value {
+            Chaos::Black => ChaosColor::Black,
+            Chaos::White => ChaosColor::White,
+        }
+    }
+}
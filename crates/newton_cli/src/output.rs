@@ -0,0 +1,121 @@
+use std::{fs, path::Path};
+
+use anyhow::Result;
+use newton_core::{
+    encode_png, encode_png16, linear_to_srgb16, linear_to_srgb8, LinearColor, Scene,
+};
+
+///////////////////////////////////////////////////////////////////
+
+/// Writes a PNG with the scene embedded, at 8 or 16 bits a channel
+pub fn write_png(
+    path: &Path,
+    width: usize,
+    height: usize,
+    colors: &[LinearColor],
+    deep: bool,
+    scene: &Scene,
+) -> Result<()> {
+    let png = match deep {
+        true => {
+            let rgba: Vec<u16> = colors.iter().flat_map(|&c| linear_to_srgb16(c)).collect();
+            encode_png16(width, height, &rgba, scene)?
+        }
+        false => {
+            let rgba: Vec<u8> = colors.iter().flat_map(|&c| linear_to_srgb8(c)).collect();
+            encode_png(width, height, &rgba, scene)?
+        }
+    };
+    Ok(fs::write(path, png)?)
+}
+
+/// Writes linear RGB as a little endian Portable Float Map. Rows run bottom to top, as the format
+/// has them, and alpha is dropped.
+pub fn write_pfm(path: &Path, width: usize, height: usize, colors: &[LinearColor]) -> Result<()> {
+    let mut bytes = format!("PF\n{width} {height}\n-1.0\n").into_bytes();
+    for row in colors.chunks_exact(width).rev() {
+        for &[r, g, b, _] in row {
+            [r, g, b].iter().for_each(|v| bytes.extend(v.to_le_bytes()));
+        }
+    }
+    Ok(fs::write(path, bytes)?)
+}
+
+/// Writes linear RGBA as a 32-bit float OpenEXR file
+pub fn write_exr(path: &Path, width: usize, height: usize, colors: &[LinearColor]) -> Result<()> {
+    exr::prelude::write_rgba_file(path, width, height, |x, y| {
+        let [r, g, b, a] = colors[y * width + x];
+        (r, g, b, a)
+    })?;
+    Ok(())
+}
+
+///////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    // Distinct in every channel, so a flipped or transposed image shows up
+    const WIDTH: usize = 3;
+    const HEIGHT: usize = 2;
+
+    fn colors() -> Vec<LinearColor> {
+        (0..WIDTH * HEIGHT)
+            .map(|i| {
+                let v = i as f32;
+                [v * 0.1, v * 0.2 + 0.05, 1.5 - v * 0.25, 1. - v * 0.125]
+            })
+            .collect()
+    }
+
+    fn temp_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("newton-cli-{}-{name}", std::process::id()))
+    }
+
+    #[test]
+    fn test_write_pfm() -> Result<()> {
+        let path = temp_path("test.pfm");
+        let colors = colors();
+        write_pfm(&path, WIDTH, HEIGHT, &colors)?;
+        let bytes = fs::read(&path)?;
+        fs::remove_file(&path)?;
+
+        let header = b"PF\n3 2\n-1.0\n";
+        assert_eq!(&bytes[..header.len()], header);
+        let floats: Vec<f32> = bytes[header.len()..]
+            .chunks_exact(4)
+            .map(|chunk| f32::from_le_bytes(chunk.try_into().unwrap()))
+            .collect();
+        assert_eq!(floats.len(), WIDTH * HEIGHT * 3);
+
+        // The bottom row comes first, without alpha
+        for (i, rgb) in floats.chunks_exact(3).enumerate() {
+            let (x, y) = (i % WIDTH, HEIGHT - 1 - i / WIDTH);
+            assert_eq!(rgb, &colors[y * WIDTH + x][..3]);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_write_exr() -> Result<()> {
+        let path = temp_path("test.exr");
+        let colors = colors();
+        write_exr(&path, WIDTH, HEIGHT, &colors)?;
+        let image = exr::prelude::read_first_rgba_layer_from_file(
+            &path,
+            |resolution, _| vec![[0.; 4]; resolution.width() * resolution.height()],
+            |pixels: &mut Vec<LinearColor>, position, (r, g, b, a): (f32, f32, f32, f32)| {
+                pixels[position.y() * WIDTH + position.x()] = [r, g, b, a];
+            },
+        );
+        fs::remove_file(&path)?;
+
+        let image = image?;
+        assert_eq!(image.layer_data.size.width(), WIDTH);
+        assert_eq!(image.layer_data.size.height(), HEIGHT);
+        assert_eq!(image.layer_data.channel_data.pixels, colors);
+        Ok(())
+    }
+}
@@ -0,0 +1,39 @@
+use newton_core::{
+    calc_luminance_max, gradient_color_linear, pixel_color_linear, LinearColor, PixelDataDetail,
+    Roots, SceneColoring,
+};
+
+///////////////////////////////////////////////////////////////////
+
+/// Colors a frame from `newton_core::render_frame_wide` in linear light, one color per pixel in row
+/// order, to be encoded however it's written out. The coloring's palette, if it has one, replaces
+/// the root colors and the lightness curve.
+pub fn color_frame<P: Copy + Into<PixelDataDetail>>(
+    pixel_data: &[P],
+    roots: &Roots,
+    coloring: &SceneColoring,
+) -> Vec<LinearColor> {
+    let dropoff = coloring.pixel_dropoff();
+    let luminance_max = calc_luminance_max(dropoff);
+
+    pixel_data
+        .iter()
+        .map(|&pixel| match &coloring.palette {
+            Some(palette) => gradient_color_linear(
+                pixel,
+                palette,
+                luminance_max,
+                dropoff,
+                coloring.is_dark_non_convergence(),
+            ),
+            None => pixel_color_linear(
+                pixel,
+                &roots.colors,
+                luminance_max,
+                dropoff,
+                &coloring.lightness,
+                coloring.is_dark_non_convergence(),
+            ),
+        })
+        .collect()
+}
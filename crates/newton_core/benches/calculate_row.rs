@@ -0,0 +1,50 @@
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+use newton_core::{
+    calculate::{HalleysMethod, NewtonsMethod},
+    calculate_row, calculate_row_simd, PixelData, Polynomial, Roots, SimdIterRoot, Viewport,
+};
+
+///////////////////////////////////////////////////////////////////
+
+// Run with `cargo bench --features simd`
+
+const FORMULAS: [&str; 2] = ["z^3 - 1", "z^8 + 15z^4 - 16"];
+
+fn bench_method<I: SimdIterRoot>(c: &mut Criterion, name: &str, params: &[f32]) {
+    let viewport = Viewport::default();
+    let mut group = c.benchmark_group(name);
+
+    for formula in FORMULAS {
+        let fz = Polynomial::parse(formula).unwrap();
+        let roots = Roots::new(&fz).unwrap().roots;
+        let (z, step) = (
+            viewport.row_start(viewport.height / 3),
+            viewport.pixel_step(),
+        );
+        let mut pixel_data = vec![PixelData::default(); viewport.width];
+
+        group.bench_with_input(BenchmarkId::new("scalar", formula), &fz, |b, fz| {
+            b.iter(|| {
+                calculate_row::<f32, I>(fz, &roots, black_box(z), step, params, &mut pixel_data)
+            })
+        });
+        group.bench_with_input(BenchmarkId::new("simd", formula), &fz, |b, fz| {
+            b.iter(|| {
+                calculate_row_simd::<I>(fz, &roots, black_box(z), step, params, &mut pixel_data)
+            })
+        });
+    }
+
+    group.finish();
+}
+
+fn bench_newton(c: &mut Criterion) {
+    bench_method::<NewtonsMethod>(c, "newton", &[]);
+}
+
+fn bench_halley(c: &mut Criterion) {
+    bench_method::<HalleysMethod>(c, "halley", &[]);
+}
+
+criterion_group!(benches, bench_newton, bench_halley);
+criterion_main!(benches);
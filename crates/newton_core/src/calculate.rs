@@ -1,7 +1,9 @@
 use num_complex::Complex32;
 
 use crate::{
+    critical::Coefficients,
     pixel_data::PixelData,
+    pixel_record::PixelRecord,
     polynomial::{Polynomial, TPolynomial},
     Lerp, LOG_EPSILON, MAX_NEWTON_COUNT,
 };
@@ -15,10 +17,21 @@ pub struct HalleysMethod;
 pub struct SteffensensMethod;
 
 pub trait IterRoot {
-    fn iter_root<T: TPolynomial>(fz: &Polynomial<T>, f0: Complex32, z: &mut Complex32);
+    fn iter_root<T: TPolynomial>(
+        fz: &Polynomial<T>,
+        f0: Complex32,
+        z: &mut Complex32,
+        params: &[f32],
+    );
     fn max_count() -> f32 {
         MAX_NEWTON_COUNT
     }
+
+    /// The polynomial whose zeros are the critical points of the iteration map, other than the
+    /// roots of f(z) themselves. Methods that can't express this return `None`.
+    fn free_critical_polynomial(_f: &Coefficients, _params: &[f32]) -> Option<Coefficients> {
+        None
+    }
 }
 
 ///////////////////////////////////////////////////////////////////
@@ -27,23 +40,76 @@ pub fn calculate_row<T: TPolynomial, I: IterRoot>(
     fz: &Polynomial<T>,
     roots: &[Complex32],
     mut z: Complex32,
-    units_per_pixel: f32,
+    step: Complex32,
+    params: &[f32],
     pixel_data: &mut [PixelData],
 ) {
     pixel_data.iter_mut().for_each(|pixel| {
-        let (z_final, frac) = newtons_method::<_, I>(fz, z);
+        let (z_final, frac) = newtons_method::<_, I>(fz, z, params);
         let root_index = nearest_root(z_final, roots);
         *pixel = (root_index, frac).into();
-        z.re += units_per_pixel;
+        z += step;
+    });
+}
+
+/// Same as `calculate_row`, keeping everything known about each pixel rather than packing it
+pub fn calculate_row_records<T: TPolynomial, I: IterRoot>(
+    fz: &Polynomial<T>,
+    roots: &[Complex32],
+    mut z: Complex32,
+    step: Complex32,
+    params: &[f32],
+    records: &mut [PixelRecord],
+) {
+    records.iter_mut().for_each(|record| {
+        // The starting point is visited too
+        let (mut visits, mut z_prev, mut step_size) = (0, z, 0.);
+        let (z_final, frac) = iterate::<_, I>(fz, z, params, |z| {
+            visits += 1;
+            step_size = (z - z_prev).norm();
+            z_prev = z;
+        });
+        *record = PixelRecord {
+            root_index: nearest_root(z_final, roots),
+            frac,
+            z: z_final,
+            iterations: visits - 1,
+            residual: fz.f0(z_final).norm(),
+            derivative: fz.f1(z_final).norm(),
+            step_size,
+        };
+        z += step;
     });
 }
 
 pub fn newtons_method<T: TPolynomial, I: IterRoot>(
+    fz: &Polynomial<T>,
+    z: Complex32,
+    params: &[f32],
+) -> (Complex32, f32) {
+    iterate::<_, I>(fz, z, params, |_| ())
+}
+
+/// Every iterate z takes on its way to a root, starting with z itself
+pub fn orbit<T: TPolynomial, I: IterRoot>(
+    fz: &Polynomial<T>,
+    z: Complex32,
+    params: &[f32],
+) -> Vec<Complex32> {
+    let mut zs = Vec::new();
+    iterate::<_, I>(fz, z, params, |z| zs.push(z));
+    zs
+}
+
+fn iterate<T: TPolynomial, I: IterRoot>(
     fz: &Polynomial<T>,
     mut z: Complex32,
+    params: &[f32],
+    mut visit: impl FnMut(Complex32),
 ) -> (Complex32, f32) {
     let mut count = 0.;
     let mut prev_log_norm = LOG_EPSILON;
+    visit(z);
     while count < I::max_count() {
         let f0 = fz.f0(z);
         let log_norm = f0.norm_sqr().log10();
@@ -52,7 +118,8 @@ pub fn newtons_method<T: TPolynomial, I: IterRoot>(
             break;
         }
 
-        I::iter_root(fz, f0, &mut z);
+        I::iter_root(fz, f0, &mut z, params);
+        visit(z);
 
         count += 1.;
         prev_log_norm = log_norm;
@@ -61,9 +128,15 @@ pub fn newtons_method<T: TPolynomial, I: IterRoot>(
     (z, count / I::max_count())
 }
 
+/// A single iteration of the method, starting from z
+pub fn step<T: TPolynomial, I: IterRoot>(fz: &Polynomial<T>, z: &mut Complex32, params: &[f32]) {
+    let f0 = fz.f0(*z);
+    I::iter_root(fz, f0, z, params);
+}
+
 ///////////////////////////////////////////////////////////////////
 
-fn nearest_root(z: Complex32, roots: &[Complex32]) -> usize {
+pub(crate) fn nearest_root(z: Complex32, roots: &[Complex32]) -> usize {
     let dist = |i: usize| (z - roots[i]).norm_sqr();
     (0..roots.len())
         .min_by_key(|&i| (100000. * dist(i)) as u32)
@@ -74,39 +147,93 @@ fn nearest_root(z: Complex32, roots: &[Complex32]) -> usize {
 
 // Newton's method: z := z - f(z) / f'(z)
 impl IterRoot for NewtonsMethod {
-    fn iter_root<T: TPolynomial>(fz: &Polynomial<T>, f0: Complex32, z: &mut Complex32) {
+    fn iter_root<T: TPolynomial>(
+        fz: &Polynomial<T>,
+        f0: Complex32,
+        z: &mut Complex32,
+        _params: &[f32],
+    ) {
         let f1 = fz.f1(*z);
         *z -= f0 / f1;
     }
+
+    // N'(z) = f(z) * f''(z) / f'(z)^2
+    fn free_critical_polynomial(f: &Coefficients, _params: &[f32]) -> Option<Coefficients> {
+        Some(f.derivative().derivative())
+    }
 }
 
 impl IterRoot for SchroedersMethod {
-    fn iter_root<T: TPolynomial>(fz: &Polynomial<T>, f0: Complex32, z: &mut Complex32) {
+    fn iter_root<T: TPolynomial>(
+        fz: &Polynomial<T>,
+        f0: Complex32,
+        z: &mut Complex32,
+        _params: &[f32],
+    ) {
         let f1 = fz.f1(*z);
         let f2 = fz.f2(*z);
         *z -= f0 * f1 / (f1 * f1 - f0 * f2);
     }
+
+    // S'(z) = f(z) * (2*f(z)*f''(z)^2 - f'(z)^2*f''(z) - f(z)*f'(z)*f'''(z)) / (...)^2
+    fn free_critical_polynomial(f: &Coefficients, _params: &[f32]) -> Option<Coefficients> {
+        let f1 = f.derivative();
+        let f2 = f1.derivative();
+        let f3 = f2.derivative();
+        Some(&(f * &f2) * &f2 * 2. - &(&f1 * &f1) * &f2 - &(f * &f1) * &f3)
+    }
 }
 
 impl IterRoot for SchroedersMethod2 {
-    fn iter_root<T: TPolynomial>(fz: &Polynomial<T>, f0: Complex32, z: &mut Complex32) {
+    fn iter_root<T: TPolynomial>(
+        fz: &Polynomial<T>,
+        f0: Complex32,
+        z: &mut Complex32,
+        _params: &[f32],
+    ) {
         let f1 = fz.f1(*z);
         let f2 = fz.f2(*z);
         *z -= f0 / f1;
         *z -= f2 * f0 * f0 / (2. * f1 * f1 * f1);
     }
+
+    // S'(z) = f(z)^2 * (3*f''(z)^2 - f'(z)*f'''(z)) / (2*f'(z)^4)
+    fn free_critical_polynomial(f: &Coefficients, _params: &[f32]) -> Option<Coefficients> {
+        let f1 = f.derivative();
+        let f2 = f1.derivative();
+        let f3 = f2.derivative();
+        Some(&f2 * &f2 * 3. - &f1 * &f3)
+    }
 }
 
 impl IterRoot for HalleysMethod {
-    fn iter_root<T: TPolynomial>(fz: &Polynomial<T>, f0: Complex32, z: &mut Complex32) {
+    fn iter_root<T: TPolynomial>(
+        fz: &Polynomial<T>,
+        f0: Complex32,
+        z: &mut Complex32,
+        _params: &[f32],
+    ) {
         let f1 = fz.f1(*z);
         let f2 = fz.f2(*z);
         *z -= 2. * f0 * f1 / (2. * f1 * f1 - f0 * f2);
     }
+
+    // H'(z) = f(z)^2 * (3*f''(z)^2 - 2*f'(z)*f'''(z)) / (2*f'(z)^2 - f(z)*f''(z))^2
+    fn free_critical_polynomial(f: &Coefficients, _params: &[f32]) -> Option<Coefficients> {
+        let f1 = f.derivative();
+        let f2 = f1.derivative();
+        let f3 = f2.derivative();
+        Some(&f2 * &f2 * 3. - &f1 * &f3 * 2.)
+    }
 }
 
 impl IterRoot for SteffensensMethod {
-    fn iter_root<T: TPolynomial>(fz: &Polynomial<T>, f0: Complex32, z: &mut Complex32) {
+    fn iter_root<T: TPolynomial>(
+        fz: &Polynomial<T>,
+        f0: Complex32,
+        z: &mut Complex32,
+        _params: &[f32],
+    ) {
         let f0h = fz.f0(*z + f0);
         *z -= f0 * f0 / (f0h - f0);
     }
@@ -0,0 +1,314 @@
+use std::cmp::Ordering;
+
+use anyhow::{bail, Result};
+use num_complex::Complex32;
+use serde::{Deserialize, Serialize};
+
+use crate::OklchColor;
+
+///////////////////////////////////////////////////////////////////
+
+/// Ways of picking root colors. Every scheme starts from each root's argument for its hue and
+/// its distance from the origin for its chroma, then spreads the hues out its own way.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ColorScheme {
+    /// Evenly spaced hues, shuffled so roots next to each other get hues far apart
+    #[default]
+    ContrastingHues,
+    /// Evenly spaced hues, in order around the origin
+    LinearHues,
+    /// Every root gets the same hue
+    Monochromatic,
+    /// Hues a golden angle apart, in order around the origin, which never quite repeat
+    GoldenAngle,
+    /// Evenly spaced hues all at the same chroma, so neighboring colors are the same distance
+    /// apart in Oklab
+    EquidistantHues,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SchemeOptions {
+    /// Added to every hue, in degrees
+    pub hue_offset: f32,
+    /// Scales chroma, from 0 for gray up to 1, which allows a chroma of 0.4
+    pub chromaticity: f32,
+    /// Start the hues at 0° instead of at the first root's argument
+    pub static_hues: bool,
+}
+
+// Roots this close to the origin have no meaningful argument, so they're colored last
+const SHORT_RADIUS: f32 = 0.1;
+
+// Hues closer than this, in degrees, are treated as the same, and ordered by radius instead
+const MIN_ANGLE_DELTA: f32 = 5.;
+
+const GOLDEN_ANGLE: f32 = 137.50776;
+
+///////////////////////////////////////////////////////////////////
+
+struct RootInfo {
+    index: usize,
+    radius: f32,
+    color: OklchColor,
+}
+
+impl Default for SchemeOptions {
+    fn default() -> Self {
+        Self {
+            hue_offset: 0.,
+            chromaticity: 0.25,
+            static_hues: false,
+        }
+    }
+}
+
+impl TryFrom<u32> for ColorScheme {
+    type Error = anyhow::Error;
+
+    fn try_from(value: u32) -> Result<Self> {
+        use ColorScheme as CS;
+        Ok(match value {
+            0 => CS::ContrastingHues,
+            1 => CS::LinearHues,
+            2 => CS::Monochromatic,
+            3 => CS::GoldenAngle,
+            4 => CS::EquidistantHues,
+            _ => bail!("Invalid color scheme: {value}"),
+        })
+    }
+}
+
+impl ColorScheme {
+    /// A color for each root, in the same order as the roots
+    pub fn colors(self, roots: &[Complex32], options: &SchemeOptions) -> Vec<OklchColor> {
+        use ColorScheme as CS;
+
+        if roots.is_empty() {
+            return Vec::new();
+        }
+
+        let mut info: Vec<RootInfo> = roots
+            .iter()
+            .enumerate()
+            .map(|(index, z)| RootInfo {
+                index,
+                radius: z.norm(),
+                color: OklchColor {
+                    h: z.arg().to_degrees(),
+                    c: root_chroma(*z, options.chromaticity),
+                },
+            })
+            .collect();
+        sort_by_hue(&mut info);
+
+        let n = info.len();
+        let initial_hue = match options.static_hues {
+            true => 0.,
+            false => info[0].color.h,
+        };
+        let spacing = 360. / n as f32;
+        let hue_steps: Vec<f32> = match self {
+            CS::ContrastingHues => contrasting_order(n)
+                .into_iter()
+                .map(|i| i as f32 * spacing)
+                .collect(),
+            CS::LinearHues | CS::EquidistantHues => (0..n).map(|i| i as f32 * spacing).collect(),
+            CS::Monochromatic => vec![0.; n],
+            CS::GoldenAngle => (0..n).map(|i| i as f32 * GOLDEN_ANGLE).collect(),
+        };
+        for (root, step) in info.iter_mut().zip(hue_steps) {
+            root.color.h = initial_hue + step + options.hue_offset;
+        }
+
+        if self == CS::EquidistantHues {
+            let c = info.iter().map(|root| root.color.c).sum::<f32>() / n as f32;
+            info.iter_mut().for_each(|root| root.color.c = c);
+        }
+
+        info.sort_by_key(|root| root.index);
+        info.into_iter().map(|root| root.color).collect()
+    }
+}
+
+/// Further roots are more saturated, up to the chromaticity's share of the maximum. This is the
+/// web app's `lerpClamped(radius / 1.5, 0.01, 0.4 * chromaticity)` step for step, in doubles like
+/// JavaScript numbers, so colors don't shift when a scene moves between the two. The maximum is
+/// applied last, so below a chromaticity of 0.025 every root gets exactly that maximum, and none
+/// comes out gray.
+fn root_chroma(z: Complex32, chromaticity: f32) -> f32 {
+    let radius = (z.re as f64).hypot(z.im as f64);
+    let (a, b) = (0.01, 0.4 * chromaticity as f64);
+    (a + radius / 1.5 * (b - a)).max(a).min(b) as f32
+}
+
+/// Orders roots by hue, with roots too close to the origin to have a hue at the end. Hues within
+/// a few degrees of each other are ordered by radius instead, which doesn't make a total order,
+/// so this is an insertion sort rather than `sort_by`, which may panic on one.
+fn sort_by_hue(info: &mut Vec<RootInfo>) {
+    let normalized = |h: f32| (h + 360. + MIN_ANGLE_DELTA) % 360. - MIN_ANGLE_DELTA;
+    let is_after = |a: &RootInfo, b: &RootInfo| {
+        let angle_delta = normalized(a.color.h) - normalized(b.color.h);
+        let ordering = match angle_delta.abs() > MIN_ANGLE_DELTA {
+            true => angle_delta.partial_cmp(&0.),
+            false => a.radius.partial_cmp(&b.radius),
+        };
+        ordering == Some(Ordering::Greater)
+    };
+    let insertion_sort = |roots: &mut [RootInfo]| {
+        for i in 1..roots.len() {
+            let mut j = i;
+            while j > 0 && is_after(&roots[j - 1], &roots[j]) {
+                roots.swap(j - 1, j);
+                j -= 1;
+            }
+        }
+    };
+
+    let (mut long, mut short): (Vec<_>, Vec<_>) = std::mem::take(info)
+        .into_iter()
+        .partition(|root| root.radius >= SHORT_RADIUS);
+    insertion_sort(&mut long);
+    insertion_sort(&mut short);
+    long.extend(short);
+    *info = long;
+}
+
+/// A permutation of 0..n that maximizes the smallest difference, modulo n, between neighbors,
+/// so hues next to each other in the order are as far apart on the color wheel as they can be
+fn contrasting_order(n: usize) -> Vec<usize> {
+    let order: Vec<usize> = match n % 2 {
+        0 => {
+            let half: Vec<usize> = (0..n / 2).map(|i| i * (n / 2 + 1)).collect();
+            let last = (n * n + 2 * n - 4) / 4;
+            half.iter()
+                .copied()
+                .chain(half.iter().map(|v| last - v))
+                .collect()
+        }
+        _ => (0..n).map(|i| i * (n - 1) / 2).collect(),
+    };
+    order.into_iter().map(|v| v % n).collect()
+}
+
+///////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Polynomial, Roots};
+
+    const SCHEMES: [ColorScheme; 5] = [
+        ColorScheme::ContrastingHues,
+        ColorScheme::LinearHues,
+        ColorScheme::Monochromatic,
+        ColorScheme::GoldenAngle,
+        ColorScheme::EquidistantHues,
+    ];
+
+    fn roots_of(formula: &str) -> Vec<Complex32> {
+        Roots::new(&Polynomial::parse(formula).unwrap())
+            .unwrap()
+            .roots
+    }
+
+    fn hues(colors: &[OklchColor]) -> Vec<f32> {
+        colors.iter().map(|color| (color.h + 360.) % 360.).collect()
+    }
+
+    #[test]
+    fn test_contrasting_order() {
+        for n in 1..20 {
+            let mut order = contrasting_order(n);
+            let closest = (0..n)
+                .map(|i| {
+                    let d = (order[i] + n - order[(i + 1) % n]) % n;
+                    d.min(n - d)
+                })
+                .min()
+                .unwrap();
+            assert!(n < 4 || closest >= n / 2 - 1, "{n}: {order:?}");
+
+            order.sort();
+            assert_eq!(order, (0..n).collect::<Vec<_>>());
+        }
+    }
+
+    #[test]
+    fn test_schemes() {
+        let roots = roots_of("z^4 - 1");
+        let options = SchemeOptions {
+            static_hues: true,
+            ..Default::default()
+        };
+        let colors = |scheme: ColorScheme| hues(&scheme.colors(&roots, &options));
+
+        // The roots come as 1, i, -1, -i, which is also the order schemes assign hues in
+        assert_eq!(colors(ColorScheme::LinearHues), [0., 90., 180., 270.]);
+        assert_eq!(colors(ColorScheme::ContrastingHues), [0., 270., 90., 180.]);
+        assert_eq!(colors(ColorScheme::Monochromatic), [0.; 4]);
+        let golden = colors(ColorScheme::GoldenAngle);
+        assert!((golden[1] - GOLDEN_ANGLE).abs() < 1e-3);
+    }
+
+    #[test]
+    fn test_options() {
+        let roots = roots_of("z^5 + 3z^3 + z + 3");
+        for scheme in SCHEMES {
+            let plain = scheme.colors(&roots, &SchemeOptions::default());
+            let shifted = scheme.colors(
+                &roots,
+                &SchemeOptions {
+                    hue_offset: 30.,
+                    chromaticity: 0.,
+                    ..Default::default()
+                },
+            );
+            assert_eq!(plain.len(), roots.len());
+            for (a, b) in plain.iter().zip(&shifted) {
+                assert!((b.h - a.h - 30.).abs() < 1e-3, "{scheme:?}");
+                assert_eq!(b.c, 0.);
+            }
+        }
+
+        let equidistant = ColorScheme::EquidistantHues.colors(&roots, &SchemeOptions::default());
+        assert!(equidistant.iter().all(|color| color.c == equidistant[0].c));
+        assert_eq!(ColorScheme::try_from(3).unwrap(), ColorScheme::GoldenAngle);
+        assert!(ColorScheme::try_from(5).is_err());
+    }
+
+    #[test]
+    fn test_chroma() {
+        let roots = roots_of("z^5 + 3z^3 + z + 3");
+        let chromas = |chromaticity: f32| -> Vec<f32> {
+            let options = SchemeOptions {
+                chromaticity,
+                ..Default::default()
+            };
+            let colors = ColorScheme::LinearHues.colors(&roots, &options);
+            colors.iter().map(|color| color.c).collect()
+        };
+
+        // Too little chromaticity for the 0.01 floor, so the maximum wins
+        assert!(chromas(0.02)
+            .iter()
+            .all(|&c| c == (0.4 * 0.02f32 as f64) as f32));
+        assert!(chromas(0.).iter().all(|&c| c == 0.));
+        for c in chromas(1.) {
+            assert!((0.01..=0.4).contains(&c));
+        }
+    }
+
+    #[test]
+    fn test_roots_near_origin() {
+        // z^3 - z has a root at 0, which is colored after the others
+        let roots = roots_of("z^3 - z");
+        let options = SchemeOptions {
+            static_hues: true,
+            ..Default::default()
+        };
+        let colors = ColorScheme::LinearHues.colors(&roots, &options);
+        let center = roots.iter().position(|z| z.norm() < SHORT_RADIUS).unwrap();
+        assert_eq!(hues(&colors)[center], 240.);
+    }
+}
@@ -1,62 +1,200 @@
-use palette::{IntoColor, Oklch, Srgba};
+use palette::{convert::IntoColorUnclamped, LinSrgb, Oklch, Srgb};
 
-use crate::{pixel_data::PixelDataDetail, Lerp, OklchColor};
+use anyhow::{bail, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    pixel_data::PixelDataDetail, Lerp, LightnessCurve, OklchColor, PixelRecord, LOG_EPSILON,
+};
 
 ///////////////////////////////////////////////////////////////////
 
+/// The built-in lightness curves. Any other shape is a `LightnessCurve`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
 pub enum LightnessMode {
+    #[default]
     Normal,
     Inverted,
     Parabola,
     InvertedParabola,
 }
 
-impl From<u32> for LightnessMode {
-    fn from(value: u32) -> Self {
+impl TryFrom<u32> for LightnessMode {
+    type Error = anyhow::Error;
+
+    fn try_from(value: u32) -> Result<Self> {
         use LightnessMode as LM;
-        match value {
+        Ok(match value {
             0 => LM::Normal,
             1 => LM::Inverted,
             2 => LM::Parabola,
             3 => LM::InvertedParabola,
-            _ => panic!("Invalid lighness mode"),
-        }
+            _ => bail!("Invalid lightness mode: {value}"),
+        })
+    }
+}
+
+/// What sets a converged pixel's lightness
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum ShadingMode {
+    /// How many iterations it took. The only mode plain `PixelData` can do.
+    #[default]
+    Iterations,
+    /// How far below the convergence threshold |f(z)| ended up
+    Residual,
+    /// How small the last step the method took was
+    StepSize,
+}
+
+impl TryFrom<u32> for ShadingMode {
+    type Error = anyhow::Error;
+
+    fn try_from(value: u32) -> Result<Self> {
+        use ShadingMode as SM;
+        Ok(match value {
+            0 => SM::Iterations,
+            1 => SM::Residual,
+            2 => SM::StepSize,
+            _ => bail!("Invalid shading mode: {value}"),
+        })
+    }
+}
+
+impl ShadingMode {
+    pub fn needs_records(self) -> bool {
+        self != ShadingMode::Iterations
     }
 }
 
 ///////////////////////////////////////////////////////////////////
 
+/// Linear light RGBA, which is what the 8 and 16-bit colors are encoded from. Colors outside
+/// sRGB's gamut can come out a little below 0 or above 1, and are left that way for grading.
+pub type LinearColor = [f32; 4];
+
 pub fn pixel_color<P: Into<PixelDataDetail>>(
     pixel_data: P,
     roots: &[OklchColor],
     luminance_max: f32,
     dropoff: f32,
-    lightness_mode: LightnessMode,
+    lightness: &LightnessCurve,
     is_dark_non_convergence: bool,
 ) -> [u8; 4] {
-    use LightnessMode as LM;
+    linear_to_srgb8(pixel_color_linear(
+        pixel_data,
+        roots,
+        luminance_max,
+        dropoff,
+        lightness,
+        is_dark_non_convergence,
+    ))
+}
 
+pub fn pixel_color_linear<P: Into<PixelDataDetail>>(
+    pixel_data: P,
+    roots: &[OklchColor],
+    luminance_max: f32,
+    dropoff: f32,
+    lightness: &LightnessCurve,
+    is_dark_non_convergence: bool,
+) -> LinearColor {
     let PixelDataDetail { root_index, frac } = pixel_data.into();
     if frac == 1.0 {
-        return match is_dark_non_convergence {
-            true => [0, 0, 0, 255],
-            false => [255, 255, 255, 255],
-        };
+        return non_convergence_color(is_dark_non_convergence);
     }
 
-    let v = frac;
-    let v = 1. - brightness_transform(v, dropoff);
-    let v = v.ilerp_clamped(0., luminance_max);
-    let v = match lightness_mode {
-        LM::Normal => v,
-        LM::Inverted => 1. - v,
-        LM::Parabola => 4. * (v - 0.5) * (v - 0.5),
-        LM::InvertedParabola => 1. - 4. * (v - 0.5) * (v - 0.5),
+    root_color(&roots[root_index], frac, luminance_max, dropoff, lightness)
+}
+
+/// Same as `pixel_color`, with the lightness taken from whatever `shading` picks out of the record
+pub fn record_color(
+    record: &PixelRecord,
+    roots: &[OklchColor],
+    luminance_max: f32,
+    dropoff: f32,
+    lightness: &LightnessCurve,
+    shading: ShadingMode,
+    is_dark_non_convergence: bool,
+) -> [u8; 4] {
+    linear_to_srgb8(record_color_linear(
+        record,
+        roots,
+        luminance_max,
+        dropoff,
+        lightness,
+        shading,
+        is_dark_non_convergence,
+    ))
+}
+
+pub fn record_color_linear(
+    record: &PixelRecord,
+    roots: &[OklchColor],
+    luminance_max: f32,
+    dropoff: f32,
+    lightness: &LightnessCurve,
+    shading: ShadingMode,
+    is_dark_non_convergence: bool,
+) -> LinearColor {
+    if record.frac == 1.0 {
+        return non_convergence_color(is_dark_non_convergence);
+    }
+
+    // Converged pixels end up with |f(z)|^2 between 1e-10 and 1e-5, give or take
+    let shade = |value: f32| (2. * value.log10()).ilerp_clamped(2. * LOG_EPSILON, LOG_EPSILON);
+    let frac = match shading {
+        ShadingMode::Iterations => record.frac,
+        ShadingMode::Residual => shade(record.residual),
+        ShadingMode::StepSize => shade(record.step_size),
     };
 
-    let OklchColor { h, c } = roots[root_index];
-    let rgba: Srgba = Oklch::new(v, c, h).into_color();
-    rgba.into_format().into()
+    let root = &roots[record.root_index];
+    root_color(root, frac, luminance_max, dropoff, lightness)
+}
+
+/// Encodes with the sRGB transfer curve, clamping to the gamut
+pub fn linear_to_srgb8(color: LinearColor) -> [u8; 4] {
+    let [r, g, b, a] = color;
+    let (r, g, b) = Srgb::<f32>::from_linear(LinSrgb::new(r, g, b))
+        .into_format::<u8>()
+        .into_components();
+    [r, g, b, (a.clamp(0., 1.) * 255.).round() as u8]
+}
+
+/// The same as `linear_to_srgb8`, with 256 times the levels, so smooth shading doesn't band
+pub fn linear_to_srgb16(color: LinearColor) -> [u16; 4] {
+    let [r, g, b, a] = color;
+    let (r, g, b) = Srgb::<f32>::from_linear(LinSrgb::new(r, g, b))
+        .into_format::<u16>()
+        .into_components();
+    [r, g, b, (a.clamp(0., 1.) * 65535.).round() as u16]
+}
+
+fn non_convergence_color(is_dark_non_convergence: bool) -> LinearColor {
+    match is_dark_non_convergence {
+        true => [0., 0., 0., 1.],
+        false => [1., 1., 1., 1.],
+    }
+}
+
+fn root_color(
+    color: &OklchColor,
+    frac: f32,
+    luminance_max: f32,
+    dropoff: f32,
+    lightness: &LightnessCurve,
+) -> LinearColor {
+    let v = lightness.evaluate(convergence_speed(frac, luminance_max, dropoff));
+
+    let &OklchColor { h, c } = color;
+    let rgb: LinSrgb = Oklch::new(v, c, h).into_color_unclamped();
+    [rgb.red, rgb.green, rgb.blue, 1.]
+}
+
+/// How quickly a pixel converged, from 1 for straight away down to 0, with the dropoff applied
+pub(crate) fn convergence_speed(frac: f32, luminance_max: f32, dropoff: f32) -> f32 {
+    (1. - brightness_transform(frac, dropoff)).ilerp_clamped(0., luminance_max)
 }
 
 /// Lower input values yield brighter colors
@@ -67,3 +205,48 @@ pub fn brightness_transform(v: f32, dropoff: f32) -> f32 {
 pub fn calc_luminance_max(dropoff: f32) -> f32 {
     (2.5 * dropoff.powf(0.333)).ln()
 }
+
+///////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use palette::{IntoColor, Srgba};
+
+    use super::*;
+
+    #[test]
+    fn test_linear_matches_srgb() {
+        let roots = [
+            OklchColor { h: 40., c: 0.15 },
+            OklchColor { h: 250., c: 0.3 },
+        ];
+        let dropoff = 0.8;
+        let luminance_max = calc_luminance_max(dropoff);
+
+        for i in 0..=100 {
+            let frac = i as f32 / 101.;
+            let color = pixel_color_linear(
+                PixelDataDetail {
+                    root_index: 1,
+                    frac,
+                },
+                &roots,
+                luminance_max,
+                dropoff,
+                &LightnessCurve::default(),
+                true,
+            );
+
+            // Straight from Oklch to 8-bit sRGB, without stopping at linear light
+            let v = (1. - brightness_transform(frac, dropoff)).ilerp_clamped(0., luminance_max);
+            let srgba: Srgba = Oklch::new(v, roots[1].c, roots[1].h).into_color();
+            let expected: [u8; 4] = srgba.into_format().into();
+            assert_eq!(linear_to_srgb8(color), expected);
+
+            let deep = linear_to_srgb16(color);
+            for (&c8, &c16) in expected.iter().zip(&deep) {
+                assert!((c16 as f32 / 257. - c8 as f32).abs() <= 0.5);
+            }
+        }
+    }
+}
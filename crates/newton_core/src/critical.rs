@@ -0,0 +1,215 @@
+use std::ops::{Add, Mul, Sub};
+
+use anyhow::{bail, Result};
+use num_complex::Complex32;
+
+use crate::{
+    calculate::nearest_root,
+    polynomial::{CPolynomial, Polynomial as P, TPolynomial},
+    roots::roots_of,
+    IterMethod, Polynomial,
+};
+
+///////////////////////////////////////////////////////////////////
+
+// How long a critical point is iterated before looking for a cycle
+const MAX_CRITICAL_ITERATIONS: usize = 500;
+
+// Longest cycle searched for, once the orbit has settled
+const MAX_CYCLE_PERIOD: usize = 64;
+
+// Distance at which an iterate counts as having landed on a root, or returned to itself
+const CRITICAL_EPSILON: f32 = 1e-3;
+
+///////////////////////////////////////////////////////////////////
+
+/// Dense complex polynomial coefficients, lowest power first. This is only used for the
+/// algebra behind critical point analysis, so it favors simplicity over speed.
+#[derive(Clone, Debug)]
+pub struct Coefficients(pub Vec<Complex32>);
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CriticalFate {
+    /// The critical point is pulled into the basin of the root at this index
+    Root(usize),
+    /// The critical point falls into an attracting cycle, which isn't any of the roots
+    Cycle { period: usize },
+    /// The orbit neither converged nor settled into a short cycle
+    Undetermined,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct CriticalOrbit {
+    pub point: Complex32,
+    pub fate: CriticalFate,
+}
+
+///////////////////////////////////////////////////////////////////
+
+/// Finds the free critical points of `method` over `fz` (those that aren't roots of f), and
+/// follows each of them to see where it ends up. Any critical point that doesn't end up at
+/// a root marks an attracting cycle, which will show up as a region of chaos in the render.
+pub fn critical_orbits(
+    fz: &Polynomial,
+    roots: &[Complex32],
+    method: &IterMethod,
+    params: &[f32],
+) -> Result<Vec<CriticalOrbit>> {
+    if roots.is_empty() {
+        bail!("Critical orbits require the roots of the polynomial");
+    }
+
+    let Some(critical) = method.free_critical_polynomial(&Coefficients::of(fz), params) else {
+        bail!(
+            "Critical point analysis is not supported for {}",
+            method.name
+        );
+    };
+
+    let critical = critical.into_polynomial();
+    if critical.is_constant() {
+        return Ok(Vec::new());
+    }
+
+    Ok(roots_of(&critical)
+        .into_iter()
+        .map(|point| CriticalOrbit {
+            point,
+            fate: critical_fate(fz, roots, method, params, point),
+        })
+        .collect())
+}
+
+fn critical_fate(
+    fz: &Polynomial,
+    roots: &[Complex32],
+    method: &IterMethod,
+    params: &[f32],
+    mut z: Complex32,
+) -> CriticalFate {
+    for _ in 0..MAX_CRITICAL_ITERATIONS {
+        let root_index = nearest_root(z, roots);
+        if (z - roots[root_index]).norm() <= CRITICAL_EPSILON {
+            return CriticalFate::Root(root_index);
+        }
+        if !z.is_finite() {
+            return CriticalFate::Undetermined;
+        }
+        method.step(fz, &mut z, params);
+    }
+
+    // The orbit has had plenty of time to settle, so any cycle should be tight by now
+    let start = z;
+    for period in 1..=MAX_CYCLE_PERIOD {
+        method.step(fz, &mut z, params);
+        if (z - start).norm() <= CRITICAL_EPSILON {
+            return CriticalFate::Cycle { period };
+        }
+    }
+
+    CriticalFate::Undetermined
+}
+
+///////////////////////////////////////////////////////////////////
+
+impl Coefficients {
+    pub fn of<T: TPolynomial>(fz: &P<T>) -> Self {
+        let fz: CPolynomial = fz.into();
+        Self(fz.coefficients())
+    }
+
+    pub fn derivative(&self) -> Self {
+        Self(
+            self.0
+                .iter()
+                .enumerate()
+                .skip(1)
+                .map(|(power, &c)| c * power as f32)
+                .collect(),
+        )
+    }
+
+    pub fn into_polynomial(self) -> CPolynomial {
+        self.0.into()
+    }
+}
+
+impl Add for Coefficients {
+    type Output = Self;
+
+    fn add(self, rhs: Self) -> Self {
+        let (mut long, short) = match self.0.len() >= rhs.0.len() {
+            true => (self.0, rhs.0),
+            false => (rhs.0, self.0),
+        };
+        long.iter_mut().zip(short).for_each(|(a, b)| *a += b);
+        Self(long)
+    }
+}
+
+impl Sub for Coefficients {
+    type Output = Self;
+
+    fn sub(self, rhs: Self) -> Self {
+        self + rhs * -1.
+    }
+}
+
+impl Mul for &Coefficients {
+    type Output = Coefficients;
+
+    fn mul(self, rhs: Self) -> Coefficients {
+        if self.0.is_empty() || rhs.0.is_empty() {
+            return Coefficients(Vec::new());
+        }
+
+        let mut product = vec![Complex32::default(); self.0.len() + rhs.0.len() - 1];
+        for (i, &a) in self.0.iter().enumerate() {
+            for (j, &b) in rhs.0.iter().enumerate() {
+                product[i + j] += a * b;
+            }
+        }
+        Coefficients(product)
+    }
+}
+
+impl Mul<f32> for Coefficients {
+    type Output = Self;
+
+    fn mul(mut self, rhs: f32) -> Self {
+        self.0.iter_mut().for_each(|c| *c *= rhs);
+        self
+    }
+}
+
+///////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Roots;
+
+    #[test]
+    fn test_cubic_critical_point() -> Result<()> {
+        // The lone free critical point of z^3 - 1 sits at the origin, where f'(z) = 0
+        let fz = Polynomial::parse("z^3 - 1")?;
+        let roots = Roots::new(&fz).unwrap().roots;
+        let method = IterMethod::by_id(0)?;
+        let orbits = critical_orbits(&fz, &roots, method, &method.default_params())?;
+        assert_eq!(orbits.len(), 1);
+        assert!(orbits[0].point.norm() < CRITICAL_EPSILON);
+        Ok(())
+    }
+
+    #[test]
+    fn test_superattracting_cycle() -> Result<()> {
+        // Newton's map for z^3 - 2z + 2 swaps 0 and 1, and 0 is critical, since f''(0) = 0
+        let fz = Polynomial::parse("z^3 - 2z + 2")?;
+        let roots = Roots::new(&fz).unwrap().roots;
+        let method = IterMethod::by_id(0)?;
+        let orbits = critical_orbits(&fz, &roots, method, &method.default_params())?;
+        assert_eq!(orbits.len(), 1);
+        assert_eq!(orbits[0].fate, CriticalFate::Cycle { period: 2 });
+        Ok(())
+    }
+}
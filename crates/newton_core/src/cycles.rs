@@ -0,0 +1,200 @@
+use anyhow::{bail, Result};
+
+use crate::{critical_orbits, CriticalFate, IterMethod, Polynomial, Roots};
+
+///////////////////////////////////////////////////////////////////
+
+// Pivots smaller than this mean the chosen points don't pin down a unique polynomial
+const SINGULAR_EPSILON: f64 = 1e-9;
+
+///////////////////////////////////////////////////////////////////
+
+/// Describes a polynomial whose Newton map carries `points[0] -> points[1] -> ... -> points[0]`,
+/// with `points[0]` also a critical point of the map, so the cycle is superattracting.
+pub struct CycleSpec {
+    /// Points on the cycle, in the order Newton's method visits them. The period is their count.
+    pub points: Vec<f32>,
+    pub degree: usize,
+    /// Free parameters: the `degree - period` highest coefficients, highest power first.
+    /// The remaining `period + 1` coefficients are solved for.
+    pub leading: Vec<f32>,
+}
+
+///////////////////////////////////////////////////////////////////
+
+/// Solves for the polynomial described by `spec`, then checks with the critical orbit
+/// analysis that the cycle is really there (rather than, say, one of the points being a root).
+pub fn superattracting_cycle(spec: &CycleSpec) -> Result<Polynomial> {
+    let period = spec.points.len();
+    if period < 2 {
+        bail!("A cycle needs at least 2 points, but {period} were given");
+    }
+    if spec.degree <= period {
+        bail!(
+            "A {period}-cycle needs a polynomial of degree at least {}",
+            period + 1
+        );
+    }
+    if spec.leading.len() != spec.degree - period {
+        bail!(
+            "A degree {} polynomial with a {period}-cycle takes {} leading coefficients, but {} were given",
+            spec.degree,
+            spec.degree - period,
+            spec.leading.len()
+        );
+    }
+    if spec.leading[0] == 0. {
+        bail!("The highest coefficient must not be zero");
+    }
+
+    let fz: Polynomial = cycle_coefficients(spec)?.into();
+    let roots = match Roots::new(&fz) {
+        Ok(roots) => roots.roots,
+        Err(err) => bail!("Generated polynomial {fz}: {err}"),
+    };
+
+    let method = IterMethod::by_id(0)?;
+    let orbits = critical_orbits(&fz, &roots, method, &method.default_params())?;
+    if !orbits
+        .iter()
+        .any(|orbit| orbit.fate == CriticalFate::Cycle { period })
+    {
+        bail!("Generated polynomial {fz} does not have an attracting {period}-cycle");
+    }
+
+    Ok(fz)
+}
+
+/// Each point k_i mapping to k_i+1 under Newton's method means f(k_i) - (k_i - k_i+1) f'(k_i) = 0,
+/// and k_0 being critical means f''(k_0) = 0. Both are linear in the coefficients of f, so with
+/// the leading coefficients fixed, the lowest `period + 1` come out of a square linear system.
+fn cycle_coefficients(spec: &CycleSpec) -> Result<Vec<f32>> {
+    let period = spec.points.len();
+    let unknowns = period + 1;
+    let points = spec.points.iter().map(|&k| k as f64).collect::<Vec<_>>();
+
+    // Each row holds the constraint's factor for every power of z, lowest first
+    let mut rows = (0..period)
+        .map(|i| {
+            let (k, next) = (points[i], points[(i + 1) % period]);
+            (0..=spec.degree)
+                .map(|n| {
+                    let derivative = match n {
+                        0 => 0.,
+                        _ => n as f64 * k.powi(n as i32 - 1),
+                    };
+                    k.powi(n as i32) - (k - next) * derivative
+                })
+                .collect::<Vec<_>>()
+        })
+        .collect::<Vec<_>>();
+    rows.push(
+        (0..=spec.degree)
+            .map(|n| match n {
+                0 | 1 => 0.,
+                _ => (n * (n - 1)) as f64 * points[0].powi(n as i32 - 2),
+            })
+            .collect(),
+    );
+
+    // Move the known, leading terms over to the right hand side
+    let leading = spec.leading.iter().rev().map(|&c| c as f64);
+    let mut system = rows
+        .into_iter()
+        .map(|row| {
+            let rhs: f64 = row[unknowns..]
+                .iter()
+                .zip(leading.clone())
+                .map(|(a, c)| a * c)
+                .sum();
+            let mut equation = row[..unknowns].to_vec();
+            equation.push(-rhs);
+            equation
+        })
+        .collect::<Vec<_>>();
+
+    let solution = solve(&mut system)?;
+    Ok(solution
+        .into_iter()
+        .chain(leading)
+        .map(|c| c as f32)
+        .collect())
+}
+
+/// Gaussian elimination with partial pivoting over an augmented matrix
+fn solve(system: &mut [Vec<f64>]) -> Result<Vec<f64>> {
+    let n = system.len();
+    for col in 0..n {
+        let pivot = (col..n)
+            .max_by(|&a, &b| system[a][col].abs().total_cmp(&system[b][col].abs()))
+            .unwrap();
+        if system[pivot][col].abs() < SINGULAR_EPSILON {
+            bail!("The chosen cycle points don't determine a unique polynomial");
+        }
+        system.swap(col, pivot);
+
+        let (pivot_rows, rows) = system.split_at_mut(col + 1);
+        let pivot_row = &pivot_rows[col];
+        for row in rows {
+            let factor = row[col] / pivot_row[col];
+            row.iter_mut()
+                .zip(pivot_row)
+                .skip(col)
+                .for_each(|(a, &b)| *a -= factor * b);
+        }
+    }
+
+    let mut solution = vec![0.; n];
+    for row in (0..n).rev() {
+        let sum: f64 = (row + 1..n).map(|k| system[row][k] * solution[k]).sum();
+        solution[row] = (system[row][n] - sum) / system[row][row];
+    }
+    Ok(solution)
+}
+
+///////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_recovers_known_cycle() -> Result<()> {
+        // z^3 - 2z + 2 is the classic polynomial whose Newton map swaps 0 and 1
+        let spec = CycleSpec {
+            points: vec![0., 1.],
+            degree: 3,
+            leading: vec![1.],
+        };
+        assert_eq!(cycle_coefficients(&spec)?, vec![2., -2., 0., 1.]);
+        superattracting_cycle(&spec)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_longer_cycles() -> Result<()> {
+        for (points, leading) in [
+            (vec![0., 1., -1.], vec![1., 2.]),
+            (vec![-1., 1.], vec![3., -2., 5., 1.]),
+        ] {
+            let spec = CycleSpec {
+                degree: points.len() + leading.len(),
+                points,
+                leading,
+            };
+            superattracting_cycle(&spec)?;
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_degenerate_cycle() {
+        // Repeating a point forces it to be a root, so there's no cycle to find
+        let spec = CycleSpec {
+            points: vec![0.5, 0.5],
+            degree: 4,
+            leading: vec![1., 1.],
+        };
+        assert!(superattracting_cycle(&spec).is_err());
+    }
+}
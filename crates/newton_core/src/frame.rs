@@ -0,0 +1,111 @@
+use num_complex::Complex32;
+
+use crate::{
+    CalculatedPixel, IterMethod, PixelData, PixelData32, PixelRecord, Polynomial, Viewport,
+};
+
+///////////////////////////////////////////////////////////////////
+
+/// Calculates every pixel of the viewport, in row order. With the `rayon` feature, rows are
+/// spread across threads. Each row only depends on its own starting point, so the result is
+/// bit-identical either way. Params must already have been resolved with `resolve_params`.
+pub fn render_frame(
+    fz: &Polynomial,
+    roots: &[Complex32],
+    method: &IterMethod,
+    params: &[f32],
+    viewport: &Viewport,
+) -> Vec<PixelData> {
+    render(fz, roots, method, params, viewport)
+}
+
+/// The same as `render_frame`, in the 32 bit layout, for more than `PixelData::MAX_ROOTS` roots
+pub fn render_frame_wide(
+    fz: &Polynomial,
+    roots: &[Complex32],
+    method: &IterMethod,
+    params: &[f32],
+    viewport: &Viewport,
+) -> Vec<PixelData32> {
+    render(fz, roots, method, params, viewport)
+}
+
+/// The same as `render_frame`, keeping a full `PixelRecord` for every pixel
+pub fn render_frame_records(
+    fz: &Polynomial,
+    roots: &[Complex32],
+    method: &IterMethod,
+    params: &[f32],
+    viewport: &Viewport,
+) -> Vec<PixelRecord> {
+    render(fz, roots, method, params, viewport)
+}
+
+pub(crate) fn render<P: CalculatedPixel>(
+    fz: &Polynomial,
+    roots: &[Complex32],
+    method: &IterMethod,
+    params: &[f32],
+    viewport: &Viewport,
+) -> Vec<P> {
+    let mut pixel_data = vec![P::default(); viewport.width * viewport.height];
+    if pixel_data.is_empty() {
+        return pixel_data;
+    }
+
+    let step = viewport.pixel_step();
+    let calculate_row = |(row, pixel_row): (usize, &mut [P])| {
+        let z = viewport.row_start(row);
+        P::calculate_row(method, fz, roots, z, step, params, pixel_row);
+    };
+
+    #[cfg(feature = "rayon")]
+    {
+        use rayon::prelude::*;
+        pixel_data
+            .par_chunks_mut(viewport.width)
+            .enumerate()
+            .for_each(calculate_row);
+    }
+
+    #[cfg(not(feature = "rayon"))]
+    pixel_data
+        .chunks_mut(viewport.width)
+        .enumerate()
+        .for_each(calculate_row);
+
+    pixel_data
+}
+
+///////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Roots, ITER_METHODS};
+
+    #[test]
+    fn test_matches_row_by_row() -> anyhow::Result<()> {
+        let fz = Polynomial::parse("z^5 + 3z^3 + z + 3")?;
+        let roots = Roots::new(&fz).unwrap().roots;
+        let viewport = Viewport {
+            rotation: 0.4,
+            ..Viewport::new(67, 41)
+        };
+
+        for method in &ITER_METHODS {
+            let params = method.default_params();
+            let frame = render_frame(&fz, &roots, method, &params, &viewport);
+
+            let mut expected = vec![PixelData::default(); viewport.width];
+            for (row, pixel_row) in frame.chunks(viewport.width).enumerate() {
+                let z = viewport.row_start(row);
+                let step = viewport.pixel_step();
+                method.calculate_row(&fz, &roots, z, step, &params, &mut expected);
+                assert_eq!(pixel_row, expected);
+            }
+        }
+
+        Ok(())
+    }
+}
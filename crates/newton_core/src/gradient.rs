@@ -0,0 +1,467 @@
+use std::fmt::Write;
+
+use anyhow::{anyhow, bail, Context, Result};
+use palette::{
+    convert::IntoColorUnclamped, encoding, Hsl, Hsv, IntoColor, Lab, Lch, LinSrgb, Mix, Oklab,
+    Oklch, Srgb,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{colors::convergence_speed, linear_to_srgb8, pixel_data::PixelDataDetail, LinearColor};
+
+///////////////////////////////////////////////////////////////////
+
+/// Colors for each root that run through a gradient as iteration count goes up, instead of a
+/// single hue getting darker. Roots take the gradients in order, starting over from the first
+/// when there are more roots than gradients.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct GradientPalette {
+    pub gradients: Vec<Gradient>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Gradient {
+    pub space: GradientSpace,
+    /// In order of position
+    pub stops: Vec<ColorStop>,
+}
+
+/// A gradient's color at a position from 0, for pixels that converged straight away, to 1, for
+/// pixels that took as long as possible
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ColorStop {
+    pub position: f32,
+    pub color: Oklab,
+}
+
+/// The color space a gradient blends its stops in
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum GradientSpace {
+    #[default]
+    Oklab,
+    /// Goes around the hue circle the short way
+    Oklch,
+    Linear,
+}
+
+// Below this, hue is meaningless, so blending into gray doesn't swing through other hues
+const ACHROMATIC_CHROMA: f32 = 1e-4;
+
+///////////////////////////////////////////////////////////////////
+
+/// Colors a pixel from its root's gradient, with dropoff spreading out the iteration counts the
+/// same way it does for `pixel_color`
+pub fn gradient_color<P: Into<PixelDataDetail>>(
+    pixel_data: P,
+    palette: &GradientPalette,
+    luminance_max: f32,
+    dropoff: f32,
+    is_dark_non_convergence: bool,
+) -> [u8; 4] {
+    linear_to_srgb8(gradient_color_linear(
+        pixel_data,
+        palette,
+        luminance_max,
+        dropoff,
+        is_dark_non_convergence,
+    ))
+}
+
+pub fn gradient_color_linear<P: Into<PixelDataDetail>>(
+    pixel_data: P,
+    palette: &GradientPalette,
+    luminance_max: f32,
+    dropoff: f32,
+    is_dark_non_convergence: bool,
+) -> LinearColor {
+    let PixelDataDetail { root_index, frac } = pixel_data.into();
+    if frac == 1.0 {
+        return match is_dark_non_convergence {
+            true => [0., 0., 0., 1.],
+            false => [1., 1., 1., 1.],
+        };
+    }
+
+    let position = 1. - convergence_speed(frac, luminance_max, dropoff);
+    let rgb = palette.gradient(root_index).sample(position);
+    [rgb.red, rgb.green, rgb.blue, 1.]
+}
+
+///////////////////////////////////////////////////////////////////
+
+impl TryFrom<String> for GradientPalette {
+    type Error = anyhow::Error;
+
+    fn try_from(text: String) -> Result<Self> {
+        Self::parse(&text)
+    }
+}
+
+impl From<GradientPalette> for String {
+    fn from(palette: GradientPalette) -> Self {
+        palette.to_text()
+    }
+}
+
+impl GradientPalette {
+    /// Reads the text format, which is a `gradient` line for each gradient, optionally followed
+    /// by the space to blend in (`oklab`, `oklch` or `linear`), then a line per stop with its
+    /// position and color:
+    ///
+    /// ```text
+    /// # Embers, then ice
+    /// gradient oklch
+    /// 0    #fff4c0
+    /// 0.4  oklch(0.7 0.18 50)
+    /// 1    black
+    ///
+    /// gradient
+    /// 0    hsl(190 0.8 0.85)
+    /// 1    linear(0 0.01 0.05)
+    /// ```
+    ///
+    /// Colors can be `#rgb` or `#rrggbb`, `black` or `white`, or a space with its components:
+    /// `rgb` (sRGB, 0 to 255), `linear` (linear sRGB, 0 to 1), `hsl` and `hsv` (hue in degrees,
+    /// the rest 0 to 1), `lab` and `lch` (CIE L*a*b*, with L from 0 to 100), or `oklab` and `oklch`.
+    /// Lines starting with `#`, and anything after a `#` and a space, are comments.
+    pub fn parse(text: &str) -> Result<Self> {
+        let mut gradients: Vec<(usize, GradientSpace, Vec<ColorStop>)> = Vec::new();
+
+        for (i, line) in text.lines().enumerate() {
+            let line_number = i + 1;
+            let line = strip_comment(line).trim();
+            if line.is_empty() {
+                continue;
+            }
+
+            let (first, rest) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+            let rest = rest.trim();
+            if first == "gradient" {
+                let space = match rest {
+                    "" => GradientSpace::default(),
+                    space => GradientSpace::parse(space)
+                        .with_context(|| format!("Line {line_number}"))?,
+                };
+                gradients.push((line_number, space, Vec::new()));
+                continue;
+            }
+
+            let Some((_, _, stops)) = gradients.last_mut() else {
+                bail!("Line {line_number}: Stops must come after a `gradient` line");
+            };
+            let stop =
+                ColorStop::parse(first, rest).with_context(|| format!("Line {line_number}"))?;
+            stops.push(stop);
+        }
+
+        if gradients.is_empty() {
+            bail!("The palette has no gradients");
+        }
+        let gradients = gradients
+            .into_iter()
+            .map(|(line_number, space, stops)| {
+                Gradient::new(space, stops)
+                    .with_context(|| format!("The gradient on line {line_number}"))
+            })
+            .collect::<Result<_>>()?;
+        Ok(Self { gradients })
+    }
+
+    /// Writes the text format back out, with every stop as `oklab`, so nothing is lost
+    pub fn to_text(&self) -> String {
+        let mut text = String::new();
+        for (i, gradient) in self.gradients.iter().enumerate() {
+            if i > 0 {
+                text.push('\n');
+            }
+            text.push_str(match gradient.space {
+                GradientSpace::Oklab => "gradient oklab\n",
+                GradientSpace::Oklch => "gradient oklch\n",
+                GradientSpace::Linear => "gradient linear\n",
+            });
+            for ColorStop { position, color } in &gradient.stops {
+                // Writing to a String can't fail
+                let _ = writeln!(
+                    text,
+                    "{position} oklab({} {} {})",
+                    color.l, color.a, color.b
+                );
+            }
+        }
+        text
+    }
+
+    /// The gradient for a root, cycling through them
+    pub fn gradient(&self, root_index: usize) -> &Gradient {
+        &self.gradients[root_index % self.gradients.len()]
+    }
+}
+
+impl Gradient {
+    /// Sorts the stops by position, which must be within 0 to 1
+    pub fn new(space: GradientSpace, mut stops: Vec<ColorStop>) -> Result<Self> {
+        if stops.is_empty() {
+            bail!("A gradient needs at least one stop");
+        }
+        if let Some(stop) = stops
+            .iter()
+            .find(|stop| !(0. ..=1.).contains(&stop.position))
+        {
+            bail!(
+                "Stop positions must be within 0 to 1, not {}",
+                stop.position
+            );
+        }
+        stops.sort_by(|a, b| a.position.total_cmp(&b.position));
+        Ok(Self { space, stops })
+    }
+
+    /// The color at a position from 0 to 1, held at the end stops' colors beyond them
+    pub fn sample(&self, position: f32) -> LinSrgb {
+        let next = self.stops.partition_point(|stop| stop.position <= position);
+        let (a, b) = match next {
+            0 => return self.stops[0].color.into_color_unclamped(),
+            n if n == self.stops.len() => return self.stops[n - 1].color.into_color_unclamped(),
+            n => (self.stops[n - 1], self.stops[n]),
+        };
+
+        // Unclamped, so colors outside sRGB survive into linear output, as with `pixel_color`
+        let factor = (position - a.position) / (b.position - a.position);
+        match self.space {
+            GradientSpace::Oklab => a.color.mix(b.color, factor).into_color_unclamped(),
+            GradientSpace::Oklch => {
+                let mut a: Oklch = a.color.into_color();
+                let mut b: Oklch = b.color.into_color();
+                if a.chroma < ACHROMATIC_CHROMA {
+                    a.hue = b.hue;
+                }
+                if b.chroma < ACHROMATIC_CHROMA {
+                    b.hue = a.hue;
+                }
+                a.mix(b, factor).into_color_unclamped()
+            }
+            GradientSpace::Linear => {
+                let a: LinSrgb = a.color.into_color_unclamped();
+                let b: LinSrgb = b.color.into_color_unclamped();
+                a.mix(b, factor)
+            }
+        }
+    }
+}
+
+impl GradientSpace {
+    fn parse(text: &str) -> Result<Self> {
+        match text {
+            "oklab" => Ok(Self::Oklab),
+            "oklch" => Ok(Self::Oklch),
+            "linear" => Ok(Self::Linear),
+            _ => bail!("Gradients blend in oklab, oklch or linear, not {text}"),
+        }
+    }
+}
+
+impl ColorStop {
+    fn parse(position: &str, color: &str) -> Result<Self> {
+        let position = position
+            .parse()
+            .map_err(|_| anyhow!("{position} isn't a stop position"))?;
+        Ok(Self {
+            position,
+            color: parse_color(color)?,
+        })
+    }
+}
+
+/// Comments start with a `#` at the start of a line, or one followed by whitespace, which
+/// leaves hex colors alone
+fn strip_comment(line: &str) -> &str {
+    if line.trim_start().starts_with('#') {
+        return "";
+    }
+    let end = line
+        .match_indices('#')
+        .map(|(i, _)| i)
+        .find(|&i| line[i + 1..].chars().next().is_none_or(char::is_whitespace));
+    &line[..end.unwrap_or(line.len())]
+}
+
+fn parse_color(text: &str) -> Result<Oklab> {
+    match text {
+        "black" => return Ok(Srgb::new(0., 0., 0.).into_color()),
+        "white" => return Ok(Srgb::new(1., 1., 1.).into_color()),
+        _ => {}
+    }
+
+    if let Some(hex) = text.strip_prefix('#') {
+        let rgb: Srgb<u8> = hex
+            .parse()
+            .map_err(|_| anyhow!("{text} isn't a hex color"))?;
+        return Ok(rgb.into_format::<f32>().into_color());
+    }
+
+    let Some((space, args)) = text.strip_suffix(')').and_then(|text| text.split_once('(')) else {
+        bail!("{text} isn't a color");
+    };
+    let args = args
+        .split(|c: char| c == ',' || c.is_whitespace())
+        .filter(|arg| !arg.is_empty())
+        .map(|arg| arg.parse::<f32>())
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|_| anyhow!("{text} has a component that isn't a number"))?;
+    let &[x, y, z] = args.as_slice() else {
+        bail!("{text} should have 3 components, but has {}", args.len());
+    };
+    if ![x, y, z].iter().all(|v| v.is_finite()) {
+        bail!("{text} has a component that isn't finite");
+    }
+
+    Ok(match space.trim() {
+        "rgb" => Srgb::new(x / 255., y / 255., z / 255.).into_color(),
+        "linear" => LinSrgb::new(x, y, z).into_color(),
+        "hsl" => Hsl::<encoding::Srgb, f32>::new(x, y, z).into_color(),
+        "hsv" => Hsv::<encoding::Srgb, f32>::new(x, y, z).into_color(),
+        "lab" => Lab::new(x, y, z).into_color(),
+        "lch" => Lch::new(x, y, z).into_color(),
+        "oklab" => Oklab::new(x, y, z),
+        "oklch" => Oklch::new(x, y, z).into_color(),
+        space => bail!("{space} isn't a color space"),
+    })
+}
+
+///////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::calc_luminance_max;
+
+    const PALETTE: &str = "
+        # Embers, then ice
+        gradient oklch
+        0    #fff4c0 # pale yellow
+        1    black
+        0.4  oklch(0.7 0.18 50)
+
+        gradient
+        0    hsl(190, 0.8, 0.85)
+        1    linear(0 0.01 0.05)
+    ";
+
+    fn srgb8(color: LinSrgb) -> [u8; 3] {
+        Srgb::<f32>::from_linear(color).into_format::<u8>().into()
+    }
+
+    #[test]
+    fn test_parse() -> Result<()> {
+        let palette = GradientPalette::parse(PALETTE)?;
+        assert_eq!(palette.gradients.len(), 2);
+
+        let embers = &palette.gradients[0];
+        assert_eq!(embers.space, GradientSpace::Oklch);
+        let positions: Vec<_> = embers.stops.iter().map(|stop| stop.position).collect();
+        assert_eq!(positions, [0., 0.4, 1.]);
+        assert_eq!(srgb8(embers.sample(0.)), [0xff, 0xf4, 0xc0]);
+        assert_eq!(srgb8(embers.sample(1.)), [0, 0, 0]);
+
+        // Roots past the last gradient start over
+        assert_eq!(palette.gradient(3), &palette.gradients[1]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_round_trip() -> Result<()> {
+        let palette = GradientPalette::parse(PALETTE)?;
+        let again = GradientPalette::parse(&palette.to_text())?;
+        for (a, b) in palette.gradients.iter().zip(&again.gradients) {
+            assert_eq!(a.space, b.space);
+            for t in [0., 0.2, 0.4, 0.7, 1.] {
+                assert_eq!(srgb8(a.sample(t)), srgb8(b.sample(t)));
+            }
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_blending() -> Result<()> {
+        let red: Oklab = Srgb::new(1., 0., 0.).into_color();
+        let blue: Oklab = Srgb::new(0., 0., 1.).into_color();
+        let white: Oklab = Srgb::new(1., 1., 1.).into_color();
+        let stops = |a, b| {
+            vec![
+                ColorStop {
+                    position: 0.2,
+                    color: a,
+                },
+                ColorStop {
+                    position: 0.8,
+                    color: b,
+                },
+            ]
+        };
+
+        // Held at the ends, and the same everywhere at the stops
+        for space in [
+            GradientSpace::Oklab,
+            GradientSpace::Oklch,
+            GradientSpace::Linear,
+        ] {
+            let gradient = Gradient::new(space, stops(red, blue))?;
+            assert_eq!(srgb8(gradient.sample(0.)), [255, 0, 0]);
+            assert_eq!(srgb8(gradient.sample(0.2)), [255, 0, 0]);
+            assert_eq!(srgb8(gradient.sample(1.)), [0, 0, 255]);
+        }
+
+        // Halfway in linear light is the plain average
+        let linear = Gradient::new(GradientSpace::Linear, stops(red, blue))?.sample(0.5);
+        assert!((linear.red - 0.5).abs() < 1e-4 && (linear.blue - 0.5).abs() < 1e-4);
+
+        // Fading to white in Oklch keeps the hue, rather than borrowing white's arbitrary one
+        let rose: Oklab = Srgb::new(0.7, 0.3, 0.35).into_color();
+        let fade = Gradient::new(GradientSpace::Oklch, stops(rose, white))?.sample(0.5);
+        let fade: Oklch = fade.into_color();
+        let rose_hue = IntoColor::<Oklch>::into_color(rose).hue;
+        assert!((fade.hue - rose_hue).into_degrees().abs() < 0.1);
+        Ok(())
+    }
+
+    #[test]
+    fn test_gradient_color() -> Result<()> {
+        let palette = GradientPalette::parse("gradient\n0 white\n1 #000000")?;
+        let dropoff = 0.8;
+        let luminance_max = calc_luminance_max(dropoff);
+        let color = |frac| {
+            let pixel = PixelDataDetail {
+                root_index: 0,
+                frac,
+            };
+            gradient_color(pixel, &palette, luminance_max, dropoff, false)
+        };
+
+        // Quick pixels take the start of the gradient, and chaos stays its own color
+        assert_eq!(color(0.), [255; 4]);
+        assert!(color(0.99)[..3].iter().all(|&v| v < 10));
+        assert_eq!(color(1.), [255; 4]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_invalid() {
+        let invalid = [
+            "",
+            "0 white",
+            "gradient",
+            "gradient hsl\n0 white",
+            "gradient\n2 white",
+            "gradient\nzero white",
+            "gradient\n0 #12345",
+            "gradient\n0 oklab(0.5 0)",
+            "gradient\n0 oklab(0.5 0 x)",
+            "gradient\n0 cmyk(0 0 0)",
+            "gradient\n0 grey",
+        ];
+        for text in invalid {
+            assert!(GradientPalette::parse(text).is_err(), "{text}");
+        }
+    }
+}
@@ -1,25 +1,70 @@
 pub mod calculate;
+mod color_scheme;
 mod colors;
+mod critical;
+mod cycles;
+mod frame;
+mod gradient;
 mod lerp;
+mod lightness_curve;
+mod methods;
 mod pixel_data;
+mod pixel_record;
+mod png_image;
 mod polynomial;
 mod polynomial_term;
+mod progressive;
+mod random;
+mod raw_data;
 mod roots;
+mod saved_frame;
+mod scene;
+mod scene_code;
+#[cfg(feature = "simd")]
+mod simd;
+mod subdivide;
+mod viewport;
 
 ///////////////////////////////////////////////////////////////////
 
-pub use calculate::{calculate_row, IterRoot};
-pub use colors::{brightness_transform, calc_luminance_max, pixel_color, LightnessMode};
+pub use calculate::{calculate_row, calculate_row_records, IterRoot};
+pub use color_scheme::{ColorScheme, SchemeOptions};
+pub use colors::{
+    brightness_transform, calc_luminance_max, linear_to_srgb16, linear_to_srgb8, pixel_color,
+    pixel_color_linear, record_color, record_color_linear, LightnessMode, LinearColor, ShadingMode,
+};
+pub use critical::{critical_orbits, Coefficients, CriticalFate, CriticalOrbit};
+pub use cycles::{superattracting_cycle, CycleSpec};
+pub use frame::{render_frame, render_frame_records, render_frame_wide};
+pub use gradient::{
+    gradient_color, gradient_color_linear, ColorStop, Gradient, GradientPalette, GradientSpace,
+};
 pub use lerp::Lerp;
-pub use pixel_data::{PixelData, PixelDataDetail};
+pub use lightness_curve::{CurveExpression, LightnessCurve};
+pub use methods::{orbit, IterMethod, MethodParam, ITER_METHODS};
+pub use pixel_data::{PackedPixel, PixelData, PixelData32, PixelDataDetail, MAX_ROOTS};
+pub use pixel_record::{CalculatedPixel, PixelRecord};
+pub use png_image::{encode_png, encode_png16, png_scene, PNG_SCENE_KEYWORD};
 pub use polynomial::FPolynomial as Polynomial;
+pub use progressive::{PassRow, ProgressiveRender};
+pub use random::{
+    random_polynomial, CoefficientKind, RandomPolynomial, RandomSpec, RootConstraint,
+};
+pub use raw_data::{RawData, RawFormat};
 pub use roots::{OklchColor, Roots};
+pub use saved_frame::SavedFrame;
+pub use scene::{ChaosColor, Scene, SceneColoring, SceneView, SCENE_VERSION};
+#[cfg(feature = "simd")]
+pub use simd::{calculate_row_simd, ComplexX8, SimdIterRoot, LANES};
+pub use subdivide::{compare_subdivided, render_frame_subdivided, SubdivideReport, SubdivideSpec};
+pub use viewport::{Affine, Viewport};
 
 ///////////////////////////////////////////////////////////////////
 
+// Size of the default viewport
 pub const CANVAS_SIZE: usize = 1024;
 
-// All calculations are done within the window:
+// The default viewport covers the window:
 // (-COMPLEX_WINDOW - i * COMPLEX_WINDOW) to (COMPLEX_WINDOW + i * COMPLEX_WINDOW)
 pub const COMPLEX_WINDOW: f32 = 1.5;
 
@@ -0,0 +1,653 @@
+use anyhow::{anyhow, bail, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::{Lerp, LightnessMode};
+
+///////////////////////////////////////////////////////////////////
+
+/// Shapes a converged pixel's lightness. A curve maps how quickly the pixel converged, from 0 for
+/// slowest up to 1 for straight away, onto its Oklab lightness, which is clamped to 0 to 1.
+///
+/// Curves are written as text, which is also how scenes hold them:
+///
+/// - `normal`, `inverted`, `parabola` or `inverted-parabola` for the built-in modes
+/// - `points 0,0 0.5,0.8 1,1` for straight lines between points
+/// - `bezier 0.25 0.1 0.25 1` for a cubic Bézier through two control points, like CSS's
+///   `cubic-bezier`
+/// - `gamma 2.2` for the speed raised to a power
+/// - `expr 1 - 4(v - 0.5)^2` for a formula of the speed `v`
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub enum LightnessCurve {
+    Mode(LightnessMode),
+    /// Points in order of x, both within 0 to 1. The curve is flat past the first and last.
+    Points(Vec<[f32; 2]>),
+    /// Control points x1, y1, x2, y2 of a Bézier from (0, 0) to (1, 1). Both x are within 0 to 1,
+    /// so there's one y for every x.
+    Bezier([f32; 4]),
+    Gamma(f32),
+    Expression(CurveExpression),
+}
+
+/// A formula of `v`, with `+ - * / ^`, brackets, `pi`, and the functions `abs`, `sqrt`, `exp`,
+/// `ln`, `sin`, `cos`, `min` and `max`. A number or bracket right after another multiplies it.
+#[derive(Clone, Debug, PartialEq)]
+pub struct CurveExpression {
+    text: String,
+    node: Node,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Node {
+    Number(f32),
+    V,
+    Negate(Box<Node>),
+    Binary(char, Box<Node>, Box<Node>),
+    Call(Function, Vec<Node>),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Function {
+    Abs,
+    Sqrt,
+    Exp,
+    Ln,
+    Sin,
+    Cos,
+    Min,
+    Max,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Token<'a> {
+    Number(f32),
+    Name(&'a str),
+    Symbol(char),
+}
+
+// Bisection steps to find where a Bézier reaches an x, which narrows it down past f32 precision
+const BEZIER_STEPS: usize = 24;
+
+// Limits on expressions, so a hostile one can't overflow the stack while it's parsed, evaluated
+// or dropped. Nesting is counted in parentheses, calls, leading minuses and powers.
+const MAX_EXPRESSION_TOKENS: usize = 1000;
+const MAX_EXPRESSION_DEPTH: usize = 64;
+
+///////////////////////////////////////////////////////////////////
+
+impl Default for LightnessCurve {
+    fn default() -> Self {
+        Self::Mode(LightnessMode::Normal)
+    }
+}
+
+impl From<LightnessMode> for LightnessCurve {
+    fn from(mode: LightnessMode) -> Self {
+        Self::Mode(mode)
+    }
+}
+
+impl TryFrom<String> for LightnessCurve {
+    type Error = anyhow::Error;
+
+    fn try_from(text: String) -> Result<Self> {
+        Self::parse(&text)
+    }
+}
+
+impl From<LightnessCurve> for String {
+    fn from(curve: LightnessCurve) -> Self {
+        curve.to_text()
+    }
+}
+
+impl LightnessCurve {
+    pub fn parse(text: &str) -> Result<Self> {
+        use LightnessMode as LM;
+
+        let text = text.trim();
+        let (kind, rest) = text
+            .split_once(char::is_whitespace)
+            .map_or((text, ""), |(kind, rest)| (kind, rest.trim()));
+        let numbers = |rest: &str| -> Result<Vec<f32>> {
+            rest.split(|c: char| c.is_whitespace() || c == ',')
+                .filter(|number| !number.is_empty())
+                .map(|number| {
+                    number
+                        .parse()
+                        .map_err(|_| anyhow!("\"{number}\" isn't a number"))
+                })
+                .collect()
+        };
+
+        let curve = match kind {
+            "normal" | "inverted" | "parabola" | "inverted-parabola" if !rest.is_empty() => {
+                bail!("The {kind} lightness curve takes no values")
+            }
+            "normal" => Self::Mode(LM::Normal),
+            "inverted" => Self::Mode(LM::Inverted),
+            "parabola" => Self::Mode(LM::Parabola),
+            "inverted-parabola" => Self::Mode(LM::InvertedParabola),
+            "points" => {
+                let mut points = rest
+                    .split_whitespace()
+                    .map(|point| match numbers(point)?[..] {
+                        [x, y] => Ok([x, y]),
+                        _ => bail!("Points are written x,y, but got \"{point}\""),
+                    })
+                    .collect::<Result<Vec<_>>>()?;
+                points.sort_by(|a, b| a[0].total_cmp(&b[0]));
+                Self::Points(points)
+            }
+            "bezier" => match numbers(rest)?[..] {
+                [x1, y1, x2, y2] => Self::Bezier([x1, y1, x2, y2]),
+                _ => bail!("A Bézier curve takes 4 values: x1 y1 x2 y2"),
+            },
+            "gamma" => match numbers(rest)?[..] {
+                [gamma] => Self::Gamma(gamma),
+                _ => bail!("A gamma curve takes 1 value"),
+            },
+            "expr" => Self::Expression(CurveExpression::parse(rest)?),
+            "" => bail!("The lightness curve is empty"),
+            _ => bail!("Unknown lightness curve: {kind}"),
+        };
+        curve.validate()?;
+        Ok(curve)
+    }
+
+    /// The inverse of `parse`
+    pub fn to_text(&self) -> String {
+        use LightnessMode as LM;
+
+        match self {
+            Self::Mode(LM::Normal) => "normal".to_string(),
+            Self::Mode(LM::Inverted) => "inverted".to_string(),
+            Self::Mode(LM::Parabola) => "parabola".to_string(),
+            Self::Mode(LM::InvertedParabola) => "inverted-parabola".to_string(),
+            Self::Points(points) => {
+                let points: Vec<String> = points.iter().map(|[x, y]| format!("{x},{y}")).collect();
+                format!("points {}", points.join(" "))
+            }
+            Self::Bezier([x1, y1, x2, y2]) => format!("bezier {x1} {y1} {x2} {y2}"),
+            Self::Gamma(gamma) => format!("gamma {gamma}"),
+            Self::Expression(expression) => format!("expr {}", expression.text),
+        }
+    }
+
+    /// Checks curves built by hand the same way `parse` does
+    pub fn validate(&self) -> Result<()> {
+        let is_unit = |v: &f32| (0. ..=1.).contains(v);
+        match self {
+            Self::Mode(_) | Self::Expression(_) => {}
+            Self::Points(points) => {
+                if points.len() < 2 {
+                    bail!("A lightness curve needs at least 2 points");
+                }
+                if !points.iter().flatten().all(is_unit) {
+                    bail!("Lightness curve points must be within 0 to 1");
+                }
+                if points.windows(2).any(|pair| pair[0][0] > pair[1][0]) {
+                    bail!("Lightness curve points must be in order of x");
+                }
+            }
+            &Self::Bezier([x1, y1, x2, y2]) => {
+                if !(is_unit(&x1) && is_unit(&x2) && y1.is_finite() && y2.is_finite()) {
+                    bail!("A Bézier curve's x values must be within 0 to 1");
+                }
+            }
+            &Self::Gamma(gamma) => {
+                if !(gamma.is_finite() && gamma > 0.) {
+                    bail!("Gamma must be above 0, but was {gamma}");
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Lightness for a convergence speed, both from 0 to 1
+    pub fn evaluate(&self, v: f32) -> f32 {
+        use LightnessMode as LM;
+
+        let lightness = match self {
+            Self::Mode(LM::Normal) => v,
+            Self::Mode(LM::Inverted) => 1. - v,
+            Self::Mode(LM::Parabola) => 4. * (v - 0.5) * (v - 0.5),
+            Self::Mode(LM::InvertedParabola) => 1. - 4. * (v - 0.5) * (v - 0.5),
+            Self::Points(points) => {
+                let i = points.partition_point(|point| point[0] < v);
+                match (points.get(i.wrapping_sub(1)), points.get(i)) {
+                    (Some(&[x0, y0]), Some(&[x1, y1])) => ((v - x0) / (x1 - x0)).lerp(y0, y1),
+                    (None, Some(&[_, y])) | (Some(&[_, y]), None) => y,
+                    (None, None) => 0.,
+                }
+            }
+            &Self::Bezier([x1, y1, x2, y2]) => {
+                let (mut lo, mut hi) = (0., 1.);
+                for _ in 0..BEZIER_STEPS {
+                    let t = 0.5 * (lo + hi);
+                    match bezier(x1, x2, t) < v {
+                        true => lo = t,
+                        false => hi = t,
+                    }
+                }
+                bezier(y1, y2, 0.5 * (lo + hi))
+            }
+            &Self::Gamma(gamma) => v.max(0.).powf(gamma),
+            Self::Expression(expression) => expression.evaluate(v),
+        };
+
+        match lightness.is_nan() {
+            true => 0.,
+            false => lightness.clamp(0., 1.),
+        }
+    }
+}
+
+/// One coordinate of a cubic Bézier from 0 to 1, with control values `a` and `b`
+fn bezier(a: f32, b: f32, t: f32) -> f32 {
+    let s = 1. - t;
+    3. * s * s * t * a + 3. * s * t * t * b + t * t * t
+}
+
+///////////////////////////////////////////////////////////////////
+
+impl CurveExpression {
+    pub fn parse(text: &str) -> Result<Self> {
+        let tokens = tokenize(text)?;
+        if tokens.len() > MAX_EXPRESSION_TOKENS {
+            bail!("The expression is too long, at over {MAX_EXPRESSION_TOKENS} tokens");
+        }
+        let mut parser = Parser {
+            tokens: &tokens,
+            pos: 0,
+            depth: 0,
+        };
+        let node = parser.sum()?;
+        if let Some(token) = parser.peek() {
+            bail!("Unexpected {} in the expression", describe(token));
+        }
+        Ok(Self {
+            text: text.trim().to_string(),
+            node,
+        })
+    }
+
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    pub fn evaluate(&self, v: f32) -> f32 {
+        self.node.evaluate(v)
+    }
+}
+
+impl Node {
+    fn evaluate(&self, v: f32) -> f32 {
+        match self {
+            Node::Number(n) => *n,
+            Node::V => v,
+            Node::Negate(node) => -node.evaluate(v),
+            Node::Binary(op, a, b) => {
+                let (a, b) = (a.evaluate(v), b.evaluate(v));
+                match op {
+                    '+' => a + b,
+                    '-' => a - b,
+                    '*' => a * b,
+                    '/' => a / b,
+                    _ => a.powf(b),
+                }
+            }
+            Node::Call(function, args) => {
+                let arg = |i: usize| args[i].evaluate(v);
+                match function {
+                    Function::Abs => arg(0).abs(),
+                    Function::Sqrt => arg(0).sqrt(),
+                    Function::Exp => arg(0).exp(),
+                    Function::Ln => arg(0).ln(),
+                    Function::Sin => arg(0).sin(),
+                    Function::Cos => arg(0).cos(),
+                    Function::Min => arg(0).min(arg(1)),
+                    Function::Max => arg(0).max(arg(1)),
+                }
+            }
+        }
+    }
+}
+
+impl Function {
+    fn by_name(name: &str) -> Option<Self> {
+        Some(match name {
+            "abs" => Self::Abs,
+            "sqrt" => Self::Sqrt,
+            "exp" => Self::Exp,
+            "ln" => Self::Ln,
+            "sin" => Self::Sin,
+            "cos" => Self::Cos,
+            "min" => Self::Min,
+            "max" => Self::Max,
+            _ => return None,
+        })
+    }
+
+    fn arg_count(self) -> usize {
+        match self {
+            Self::Min | Self::Max => 2,
+            _ => 1,
+        }
+    }
+}
+
+fn tokenize(text: &str) -> Result<Vec<Token<'_>>> {
+    let mut tokens = Vec::new();
+    let mut rest = text;
+    while let Some(c) = rest.chars().next() {
+        let len = match c {
+            _ if c.is_whitespace() => c.len_utf8(),
+            '0'..='9' | '.' => {
+                let len = rest
+                    .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+                    .unwrap_or(rest.len());
+                let number = &rest[..len];
+                let Ok(number) = number.parse() else {
+                    bail!("\"{number}\" isn't a number");
+                };
+                tokens.push(Token::Number(number));
+                len
+            }
+            'a'..='z' => {
+                let len = rest
+                    .find(|c: char| !c.is_ascii_lowercase())
+                    .unwrap_or(rest.len());
+                tokens.push(Token::Name(&rest[..len]));
+                len
+            }
+            '+' | '-' | '*' | '/' | '^' | '(' | ')' | ',' => {
+                tokens.push(Token::Symbol(c));
+                1
+            }
+            _ => bail!("Unexpected \"{c}\" in the expression"),
+        };
+        rest = &rest[len..];
+    }
+    Ok(tokens)
+}
+
+fn describe(token: Token) -> String {
+    match token {
+        Token::Number(n) => format!("\"{n}\""),
+        Token::Name(name) => format!("\"{name}\""),
+        Token::Symbol(c) => format!("\"{c}\""),
+    }
+}
+
+/// Recursive descent, from the loosest binding operators down to single values
+struct Parser<'a> {
+    tokens: &'a [Token<'a>],
+    pos: usize,
+    /// How many `unary` calls are under way, which every level of nesting goes through
+    depth: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn peek(&self) -> Option<Token<'a>> {
+        self.tokens.get(self.pos).copied()
+    }
+
+    fn next(&mut self) -> Result<Token<'a>> {
+        let token = self
+            .peek()
+            .ok_or_else(|| anyhow!("The expression ends too soon"))?;
+        self.pos += 1;
+        Ok(token)
+    }
+
+    fn expect(&mut self, symbol: char) -> Result<()> {
+        match self.next()? {
+            Token::Symbol(c) if c == symbol => Ok(()),
+            token => bail!("Expected \"{symbol}\", but got {}", describe(token)),
+        }
+    }
+
+    fn sum(&mut self) -> Result<Node> {
+        let mut node = self.product()?;
+        while let Some(Token::Symbol(op @ ('+' | '-'))) = self.peek() {
+            self.pos += 1;
+            node = Node::Binary(op, Box::new(node), Box::new(self.product()?));
+        }
+        Ok(node)
+    }
+
+    fn product(&mut self) -> Result<Node> {
+        let mut node = self.unary()?;
+        loop {
+            let op = match self.peek() {
+                Some(Token::Symbol(op @ ('*' | '/'))) => {
+                    self.pos += 1;
+                    op
+                }
+                // Implied multiplication, as in 4v or 2(v - 1)
+                Some(Token::Number(_) | Token::Name(_) | Token::Symbol('(')) => '*',
+                _ => return Ok(node),
+            };
+            node = Node::Binary(op, Box::new(node), Box::new(self.unary()?));
+        }
+    }
+
+    fn unary(&mut self) -> Result<Node> {
+        if self.depth == MAX_EXPRESSION_DEPTH {
+            bail!("The expression is nested more than {MAX_EXPRESSION_DEPTH} deep");
+        }
+        self.depth += 1;
+        let node = match self.peek() {
+            Some(Token::Symbol('-')) => {
+                self.pos += 1;
+                self.unary().map(|node| Node::Negate(Box::new(node)))
+            }
+            _ => self.power(),
+        };
+        self.depth -= 1;
+        node
+    }
+
+    /// Right associative, and binds tighter than a leading minus, so -v^2 is -(v^2)
+    fn power(&mut self) -> Result<Node> {
+        let base = self.value()?;
+        match self.peek() {
+            Some(Token::Symbol('^')) => {
+                self.pos += 1;
+                Ok(Node::Binary('^', Box::new(base), Box::new(self.unary()?)))
+            }
+            _ => Ok(base),
+        }
+    }
+
+    fn value(&mut self) -> Result<Node> {
+        match self.next()? {
+            Token::Number(n) => Ok(Node::Number(n)),
+            Token::Name("v") => Ok(Node::V),
+            Token::Name("pi") => Ok(Node::Number(std::f32::consts::PI)),
+            Token::Name(name) => {
+                let Some(function) = Function::by_name(name) else {
+                    bail!("Unknown name in the expression: {name}");
+                };
+                self.expect('(')?;
+                let mut args = vec![self.sum()?];
+                while let Some(Token::Symbol(',')) = self.peek() {
+                    self.pos += 1;
+                    args.push(self.sum()?);
+                }
+                self.expect(')')?;
+
+                let count = function.arg_count();
+                if args.len() != count {
+                    bail!("{name} takes {count} values, but was given {}", args.len());
+                }
+                Ok(Node::Call(function, args))
+            }
+            Token::Symbol('(') => {
+                let node = self.sum()?;
+                self.expect(')')?;
+                Ok(node)
+            }
+            token => bail!("Unexpected {} in the expression", describe(token)),
+        }
+    }
+}
+
+///////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(text: &str) -> LightnessCurve {
+        LightnessCurve::parse(text).unwrap()
+    }
+
+    fn assert_close(a: f32, b: f32) {
+        assert!((a - b).abs() < 1e-4, "{a} != {b}");
+    }
+
+    #[test]
+    fn test_modes() {
+        let modes = [
+            LightnessMode::Normal,
+            LightnessMode::Inverted,
+            LightnessMode::Parabola,
+            LightnessMode::InvertedParabola,
+        ];
+        let equivalents = [
+            "gamma 1",
+            "points 0,1 1,0",
+            "expr 4(v - 0.5)^2",
+            "expr 1 - 4 * (v - 0.5) ^ 2",
+        ];
+        for (mode, text) in modes.into_iter().zip(equivalents) {
+            let curve = LightnessCurve::from(mode);
+            assert_eq!(parse(&curve.to_text()), curve);
+            for i in 0..=20 {
+                let v = i as f32 / 20.;
+                assert_close(parse(text).evaluate(v), curve.evaluate(v));
+            }
+        }
+        assert_eq!(LightnessMode::try_from(3).unwrap(), modes[3]);
+        assert!(LightnessMode::try_from(4).is_err());
+    }
+
+    #[test]
+    fn test_points() {
+        let curve = parse("points 1,1 0.2,0 0.6,0.8");
+        assert_eq!(
+            curve,
+            LightnessCurve::Points(vec![[0.2, 0.], [0.6, 0.8], [1., 1.]])
+        );
+        assert_close(curve.evaluate(0.), 0.);
+        assert_close(curve.evaluate(0.4), 0.4);
+        assert_close(curve.evaluate(0.8), 0.9);
+        assert_close(curve.evaluate(1.), 1.);
+
+        // A step, where two points share an x
+        let step = parse("points 0,0 0.5,0 0.5,1 1,1");
+        assert_close(step.evaluate(0.49), 0.);
+        assert_close(step.evaluate(0.51), 1.);
+    }
+
+    #[test]
+    fn test_bezier() {
+        let linear = parse("bezier 0.25 0.25 0.75 0.75");
+        let ease = parse("bezier 0.42, 0, 0.58, 1");
+        for i in 0..=20 {
+            let v = i as f32 / 20.;
+            assert_close(linear.evaluate(v), v);
+            assert_close(ease.evaluate(v), 1. - ease.evaluate(1. - v));
+        }
+        assert!(ease.evaluate(0.2) < 0.2);
+
+        // Overshooting control points are clamped
+        let overshoot = parse("bezier 0.3 1.8 0.7 1.8");
+        assert_eq!(overshoot.evaluate(0.5), 1.);
+    }
+
+    #[test]
+    fn test_expressions() {
+        let eval = |text: &str, v: f32| CurveExpression::parse(text).unwrap().evaluate(v);
+        assert_close(eval("2 + 3 * 4", 0.), 14.);
+        assert_close(eval("2^3^2", 0.), 512.);
+        assert_close(eval("-v^2", 3.), -9.);
+        assert_close(eval("(1 - v) / 2", 0.5), 0.25);
+        assert_close(eval("3v(v + 1)", 2.), 18.);
+        assert_close(eval("sqrt(v) + min(v, 0.1) + max(abs(-1), 0)", 0.25), 1.6);
+        assert_close(eval("sin(pi v) + cos(0) + ln(exp(2))", 0.5), 4.);
+
+        // The curve clamps what the expression gives back
+        assert_eq!(parse("expr 2v").evaluate(0.75), 1.);
+        assert_eq!(parse("expr ln(v - 1)").evaluate(0.5), 0.);
+
+        let curve = parse("  expr   sqrt(v) ");
+        assert_eq!(curve.to_text(), "expr sqrt(v)");
+        assert_eq!(parse(&curve.to_text()), curve);
+    }
+
+    #[test]
+    fn test_serde() {
+        let curve = parse("points 0,0.2 0.5,1 1,0.4");
+        let json = serde_json::to_string(&curve).unwrap();
+        assert_eq!(json, "\"points 0,0.2 0.5,1 1,0.4\"");
+        assert_eq!(
+            serde_json::from_str::<LightnessCurve>(&json).unwrap(),
+            curve
+        );
+        assert!(serde_json::from_str::<LightnessCurve>("\"gamma 0\"").is_err());
+    }
+
+    #[test]
+    fn test_invalid() {
+        let invalid = [
+            "",
+            "linear",
+            "normal 2",
+            "gamma",
+            "gamma 0",
+            "gamma -1",
+            "gamma nan",
+            "points",
+            "points 0,0",
+            "points 0,0 1",
+            "points 0,0 1,2",
+            "points 0,0 x,1",
+            "bezier 0 0 1",
+            "bezier -0.5 0 1 1",
+            "bezier 0 0 1.5 1",
+            "expr",
+            "expr v +",
+            "expr (v",
+            "expr v)",
+            "expr x",
+            "expr sqrt v",
+            "expr min(v)",
+            "expr v % 2",
+            "expr 1..2",
+        ];
+        for text in invalid {
+            assert!(LightnessCurve::parse(text).is_err(), "{text}");
+        }
+
+        // Deep enough to overflow the stack without the limits
+        let deep = [
+            format!("expr {}v{}", "(".repeat(100_000), ")".repeat(100_000)),
+            format!("expr {}v", "-".repeat(100_000)),
+            format!("expr v{}", "^v".repeat(100_000)),
+            format!("expr {}v{}", "sqrt(".repeat(100_000), ")".repeat(100_000)),
+            format!("expr v{}", " + v".repeat(100_000)),
+        ];
+        for text in deep {
+            assert!(LightnessCurve::parse(&text).is_err());
+        }
+        let nested = format!("expr {}v{}", "(".repeat(50), ")".repeat(50));
+        assert_close(parse(&nested).evaluate(0.5), 0.5);
+        assert!(LightnessCurve::Gamma(-2.).validate().is_err());
+        assert!(LightnessCurve::Points(vec![[1., 0.], [0., 1.]])
+            .validate()
+            .is_err());
+    }
+}
@@ -0,0 +1,296 @@
+use std::borrow::Cow;
+
+use anyhow::{anyhow, bail, Result};
+use num_complex::Complex32;
+
+#[cfg(feature = "simd")]
+use crate::simd::{calculate_row_simd, SimdIterRoot};
+use crate::{
+    calculate::{
+        self, calculate_row, calculate_row_records, HalleysMethod, IterRoot, NewtonsMethod,
+        SchroedersMethod, SchroedersMethod2, SteffensensMethod,
+    },
+    critical::Coefficients,
+    pixel_data::PixelData,
+    pixel_record::PixelRecord,
+    Polynomial,
+};
+
+///////////////////////////////////////////////////////////////////
+
+/// A tunable input to a root finding method
+pub struct MethodParam {
+    pub name: &'static str,
+    pub default: f32,
+    pub min: f32,
+    pub max: f32,
+}
+
+type CalculateRowFn = fn(&Polynomial, &[Complex32], Complex32, Complex32, &[f32], &mut [PixelData]);
+type CalculateRecordsFn =
+    fn(&Polynomial, &[Complex32], Complex32, Complex32, &[f32], &mut [PixelRecord]);
+type OrbitFn = fn(&Polynomial, Complex32, &[f32]) -> Vec<Complex32>;
+type StepFn = fn(&Polynomial, &mut Complex32, &[f32]);
+type CriticalPolynomialFn = fn(&Coefficients, &[f32]) -> Option<Coefficients>;
+
+/// A registered root finding method. Everything the frontend needs to know about a method
+/// comes from here, so adding a method means adding an `IterRoot` and an entry below.
+pub struct IterMethod {
+    pub id: u32,
+    pub name: &'static str,
+    pub formula: &'static str,
+    /// Highest derivative of f(z) the method evaluates
+    pub derivative_order: u32,
+    pub params: &'static [MethodParam],
+    calculate_row: CalculateRowFn,
+    calculate_row_records: CalculateRecordsFn,
+    orbit: OrbitFn,
+    step: StepFn,
+    free_critical_polynomial: CriticalPolynomialFn,
+}
+
+///////////////////////////////////////////////////////////////////
+
+pub static ITER_METHODS: [IterMethod; 5] = [
+    IterMethod::new::<NewtonsMethod>(0, "Newton's Method", "z -= f(z)/f'(z)", 1, &[])
+        .with_simd::<NewtonsMethod>(),
+    IterMethod::new::<SchroedersMethod>(
+        1,
+        "Schröder's Method for Multiple Roots",
+        "z -= f(z)*f'(z)/(f'(z)^2 - f(z)*f''(z))",
+        2,
+        &[],
+    ),
+    IterMethod::new::<SchroedersMethod2>(
+        2,
+        "Schröder's Method #2",
+        "z -= f(z)/f'(z) - f''(z)*f(z)^2/(2*f'(z)^3)",
+        2,
+        &[],
+    ),
+    IterMethod::new::<HalleysMethod>(
+        3,
+        "Halley's Method",
+        "z -= 2*f(z)*f'(z)/(2*f'(z)^2 - f(z)*f''(z))",
+        2,
+        &[],
+    )
+    .with_simd::<HalleysMethod>(),
+    IterMethod::new::<SteffensensMethod>(
+        4,
+        "Steffensen's Method",
+        "z -= f(z)^2/(f(z + f(z)) - f(z))",
+        0,
+        &[],
+    ),
+];
+
+///////////////////////////////////////////////////////////////////
+
+impl IterMethod {
+    const fn new<I: IterRoot>(
+        id: u32,
+        name: &'static str,
+        formula: &'static str,
+        derivative_order: u32,
+        params: &'static [MethodParam],
+    ) -> Self {
+        Self {
+            id,
+            name,
+            formula,
+            derivative_order,
+            params,
+            calculate_row: calculate_row::<f32, I>,
+            calculate_row_records: calculate_row_records::<f32, I>,
+            orbit: calculate::orbit::<f32, I>,
+            step: calculate::step::<f32, I>,
+            free_critical_polynomial: I::free_critical_polynomial,
+        }
+    }
+
+    /// Calculates rows several pixels at a time. The output is identical, only faster.
+    #[cfg(feature = "simd")]
+    const fn with_simd<I: SimdIterRoot>(mut self) -> Self {
+        self.calculate_row = calculate_row_simd::<I>;
+        self
+    }
+
+    // Keeps the same signature, so `ITER_METHODS` doesn't need to know about the feature
+    #[cfg(not(feature = "simd"))]
+    #[allow(clippy::extra_unused_type_parameters)]
+    const fn with_simd<I: IterRoot>(self) -> Self {
+        self
+    }
+
+    pub fn by_id(id: u32) -> Result<&'static Self> {
+        ITER_METHODS
+            .iter()
+            .find(|method| method.id == id)
+            .ok_or_else(|| anyhow!("Invalid root iter method type: {id}"))
+    }
+
+    pub fn default_params(&self) -> Vec<f32> {
+        self.params.iter().map(|param| param.default).collect()
+    }
+
+    /// Checks user supplied parameters against the schema. An empty list selects the defaults.
+    pub fn resolve_params(&self, params: &[f32]) -> Result<Vec<f32>> {
+        if params.is_empty() {
+            return Ok(self.default_params());
+        }
+
+        if params.len() != self.params.len() {
+            bail!(
+                "{} takes {} parameters, but {} were given",
+                self.name,
+                self.params.len(),
+                params.len()
+            );
+        }
+
+        for (param, &value) in self.params.iter().zip(params) {
+            if !(param.min..=param.max).contains(&value) {
+                bail!(
+                    "{} must be within {} to {}, but was {value}",
+                    param.name,
+                    param.min,
+                    param.max
+                );
+            }
+        }
+
+        Ok(params.to_vec())
+    }
+
+    /// Iterates a row of pixels, starting at z and moving by `step` for each pixel. An empty
+    /// params list selects the defaults, as with `resolve_params`.
+    pub fn calculate_row(
+        &self,
+        fz: &Polynomial,
+        roots: &[Complex32],
+        z: Complex32,
+        step: Complex32,
+        params: &[f32],
+        pixel_data: &mut [PixelData],
+    ) {
+        let params = self.params_or_defaults(params);
+        (self.calculate_row)(fz, roots, z, step, &params, pixel_data);
+    }
+
+    /// Same as `calculate_row`, into full `PixelRecord`s
+    pub fn calculate_row_records(
+        &self,
+        fz: &Polynomial,
+        roots: &[Complex32],
+        z: Complex32,
+        step: Complex32,
+        params: &[f32],
+        records: &mut [PixelRecord],
+    ) {
+        let params = self.params_or_defaults(params);
+        (self.calculate_row_records)(fz, roots, z, step, &params, records);
+    }
+
+    pub fn orbit(&self, fz: &Polynomial, z: Complex32, params: &[f32]) -> Vec<Complex32> {
+        (self.orbit)(fz, z, &self.params_or_defaults(params))
+    }
+
+    pub fn step(&self, fz: &Polynomial, z: &mut Complex32, params: &[f32]) {
+        (self.step)(fz, z, &self.params_or_defaults(params));
+    }
+
+    pub fn free_critical_polynomial(
+        &self,
+        f: &Coefficients,
+        params: &[f32],
+    ) -> Option<Coefficients> {
+        (self.free_critical_polynomial)(f, &self.params_or_defaults(params))
+    }
+
+    /// Fills in the defaults for an empty list, so a method never reads past the params it's
+    /// given. Anything else is passed through, so check it with `resolve_params` first.
+    fn params_or_defaults<'a>(&self, params: &'a [f32]) -> Cow<'a, [f32]> {
+        match params.is_empty() {
+            true => Cow::Owned(self.default_params()),
+            false => Cow::Borrowed(params),
+        }
+    }
+}
+
+///////////////////////////////////////////////////////////////////
+
+/// Traces the path a single starting point takes under `method`. These are exactly the
+/// iterates `calculate_row` walks through for a pixel starting at z0.
+pub fn orbit(
+    fz: &Polynomial,
+    method: &IterMethod,
+    z0: Complex32,
+    params: &[f32],
+) -> Vec<Complex32> {
+    method.orbit(fz, z0, params)
+}
+
+///////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{calculate::nearest_root, PixelDataDetail, Roots};
+
+    #[test]
+    fn test_orbit_matches_row() -> Result<()> {
+        let fz = Polynomial::parse("z^5 + 3z^3 + z + 3")?;
+        let roots = Roots::new(&fz).unwrap().roots;
+        let z = Complex32::new(-1.2, 0.7);
+        let step = Complex32::new(0.3, 0.1);
+
+        for method in &ITER_METHODS {
+            let params = method.default_params();
+            let mut pixel_data = vec![PixelData::default(); 8];
+            method.calculate_row(&fz, &roots, z, step, &params, &mut pixel_data);
+
+            let mut z0 = z;
+            for &pixel in &pixel_data {
+                let zs = orbit(&fz, method, z0, &params);
+                assert_eq!(zs[0], z0);
+
+                let PixelDataDetail { root_index, .. } = pixel.into();
+                assert_eq!(nearest_root(*zs.last().unwrap(), &roots), root_index);
+                z0 += step;
+            }
+        }
+
+        Ok(())
+    }
+    #[test]
+    fn test_registry() {
+        for (i, method) in ITER_METHODS.iter().enumerate() {
+            assert_eq!(method.id, i as u32);
+            assert_eq!(IterMethod::by_id(method.id).unwrap().name, method.name);
+            assert_eq!(method.resolve_params(&[]).unwrap(), method.default_params());
+            assert!(method.resolve_params(&[1.; 4]).is_err());
+        }
+        assert!(IterMethod::by_id(ITER_METHODS.len() as u32).is_err());
+    }
+
+    #[test]
+    fn test_empty_params() -> Result<()> {
+        // Leaving params out is the same as passing the defaults, rather than reading past the end
+        let fz = Polynomial::parse("z^3 - 1")?;
+        let roots = Roots::new(&fz).unwrap().roots;
+        let z = Complex32::new(-1.2, 0.7);
+        let step = Complex32::new(0.3, 0.1);
+
+        for method in &ITER_METHODS {
+            let defaults = method.default_params();
+            let mut with_defaults = vec![PixelData::default(); 8];
+            let mut empty = vec![PixelData::default(); 8];
+            method.calculate_row(&fz, &roots, z, step, &defaults, &mut with_defaults);
+            method.calculate_row(&fz, &roots, z, step, &[], &mut empty);
+            assert_eq!(empty, with_defaults, "{}", method.name);
+            assert_eq!(method.orbit(&fz, z, &[]), method.orbit(&fz, z, &defaults));
+        }
+        Ok(())
+    }
+}
@@ -1,11 +1,29 @@
-#[derive(Clone, Copy, Default)]
+use crate::CalculatedPixel;
+
+/// One packed pixel, the root it went to and its iteration fraction, in 16 bits. That's room
+/// for 16 roots, which covers most polynomials in the least memory.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
 pub struct PixelData(pub u16);
 
+/// The same as `PixelData` in 32 bits, which makes room for more roots and a finer iteration
+/// fraction. Which of the two to use is up to whatever's rendering, once it knows the roots.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct PixelData32(pub u32);
+
 pub struct PixelDataDetail {
     pub root_index: usize,
     pub frac: f32,
 }
 
+/// Either packed pixel layout, for code that works the same with both
+pub trait PackedPixel:
+    CalculatedPixel + PartialEq + From<(usize, f32)> + Into<PixelDataDetail>
+{
+}
+
+/// How many roots the widest pixel layout can tell apart
+pub const MAX_ROOTS: usize = PixelData32::MAX_ROOTS;
+
 ///////////////////////////////////////////////////////////////////
 
 const fn mask(n: u32) -> u32 {
@@ -14,31 +32,88 @@ const fn mask(n: u32) -> u32 {
 
 // f32 = [S][E: 8][M: 23]
 // P16 = [I: 4][E: 3][M: 9]
+// P32 = [I: 8][E: 3][M: 21]
 
-const INDEX_SIZE: u32 = 4; // Should handle up to z^15
-const INDEX_MASK: u32 = mask(INDEX_SIZE);
-const FRACT_SIZE: u32 = 16 - INDEX_SIZE;
-const FRACT_MASK: u32 = mask(FRACT_SIZE);
 const EXP_BITS: u32 = 3; // Should handle up to MAX_NEWTON_COUNT=1<<((1<<3)-1)=128
 const EXP_MASK_OR: u32 = (0x7F & !mask(EXP_BITS)) << 23;
-const FRACT_SHIFT: u32 = 23 - FRACT_SIZE + EXP_BITS;
+
+struct Layout {
+    index_size: u32,
+    fract_size: u32,
+}
+
+const LAYOUT_16: Layout = Layout::new(16, 4);
+const LAYOUT_32: Layout = Layout::new(32, 8);
+
+impl Layout {
+    const fn new(bits: u32, index_size: u32) -> Self {
+        Self {
+            index_size,
+            fract_size: bits - index_size,
+        }
+    }
+
+    const fn fract_shift(&self) -> u32 {
+        23 + EXP_BITS - self.fract_size
+    }
+
+    fn pack(&self, root_index: usize, frac: f32) -> u32 {
+        // log::info!("in -- frac: {frac}, {:08X}", frac.to_bits());
+        let v_root = (root_index as u32 & mask(self.index_size)) << self.fract_size;
+        let v_frac = (frac.to_bits() >> self.fract_shift()) & mask(self.fract_size);
+        v_root | v_frac
+    }
+
+    fn unpack(&self, bits: u32) -> PixelDataDetail {
+        let root_index = (bits >> self.fract_size) as usize;
+        let frac =
+            f32::from_bits(((bits & mask(self.fract_size)) << self.fract_shift()) | EXP_MASK_OR);
+        // log::info!("out -- frac: {frac}, {:08X}", frac.to_bits());
+        PixelDataDetail { root_index, frac }
+    }
+}
+
+///////////////////////////////////////////////////////////////////
+
+impl PixelData {
+    pub const BITS: u32 = u16::BITS;
+    /// How many roots this layout can tell apart
+    pub const MAX_ROOTS: usize = 1 << LAYOUT_16.index_size;
+}
+
+impl PixelData32 {
+    pub const BITS: u32 = u32::BITS;
+    /// How many roots this layout can tell apart
+    pub const MAX_ROOTS: usize = 1 << LAYOUT_32.index_size;
+}
+
+impl PackedPixel for PixelData {}
+
+impl PackedPixel for PixelData32 {}
 
 impl From<(usize, f32)> for PixelData {
     fn from(value: (usize, f32)) -> Self {
         let (root_index, frac) = value;
-        // log::info!("in -- frac: {frac}, {:08X}", frac.to_bits());
-        let v_root = (root_index as u32 & INDEX_MASK) << FRACT_SIZE; // 4 bits
-        let v_frac = (frac.to_bits() >> FRACT_SHIFT) & FRACT_MASK;
-        PixelData((v_root | v_frac) as u16)
+        PixelData(LAYOUT_16.pack(root_index, frac) as u16)
+    }
+}
+
+impl From<(usize, f32)> for PixelData32 {
+    fn from(value: (usize, f32)) -> Self {
+        let (root_index, frac) = value;
+        PixelData32(LAYOUT_32.pack(root_index, frac))
     }
 }
 
 impl From<PixelData> for PixelDataDetail {
     fn from(value: PixelData) -> Self {
-        let root_index = value.0 as usize >> FRACT_SIZE;
-        let frac = f32::from_bits(((value.0 as u32 & FRACT_MASK) << FRACT_SHIFT) | EXP_MASK_OR);
-        // log::info!("out -- frac: {frac}, {:08X}", frac.to_bits());
-        PixelDataDetail { root_index, frac }
+        LAYOUT_16.unpack(value.0 as u32)
+    }
+}
+
+impl From<PixelData32> for PixelDataDetail {
+    fn from(value: PixelData32) -> Self {
+        LAYOUT_32.unpack(value.0)
     }
 }
 
@@ -47,3 +122,63 @@ impl From<PixelDataDetail> for PixelData {
         (value.root_index, value.frac).into()
     }
 }
+
+impl From<PixelDataDetail> for PixelData32 {
+    fn from(value: PixelDataDetail) -> Self {
+        (value.root_index, value.frac).into()
+    }
+}
+
+/// Widening never loses anything
+impl From<PixelData> for PixelData32 {
+    fn from(value: PixelData) -> Self {
+        PixelDataDetail::from(value).into()
+    }
+}
+
+///////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip() {
+        for (max_roots, layout) in [(PixelData::MAX_ROOTS, LAYOUT_16), (MAX_ROOTS, LAYOUT_32)] {
+            for root_index in [0, 1, max_roots - 1] {
+                for frac in [0.01, 0.37, 0.5, 0.99] {
+                    let detail: PixelDataDetail = match max_roots == MAX_ROOTS {
+                        true => PixelData32::from((root_index, frac)).into(),
+                        false => PixelData::from((root_index, frac)).into(),
+                    };
+                    assert_eq!(detail.root_index, root_index);
+                    // Truncating the mantissa only ever rounds down
+                    let precision = 2f32.powi(-((layout.fract_size - EXP_BITS) as i32));
+                    assert!(frac - detail.frac < precision && frac >= detail.frac);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_layout_sizes() {
+        assert_eq!(PixelData::MAX_ROOTS, 16);
+        assert_eq!(PixelData32::MAX_ROOTS, 256);
+        assert_eq!(
+            std::mem::size_of::<PixelData>() * 8,
+            PixelData::BITS as usize
+        );
+        assert_eq!(
+            std::mem::size_of::<PixelData32>() * 8,
+            PixelData32::BITS as usize
+        );
+    }
+
+    #[test]
+    fn test_widening() {
+        let pixel = PixelData::from((3, 0.6));
+        let wide = PixelData32::from(pixel);
+        let (a, b): (PixelDataDetail, PixelDataDetail) = (pixel.into(), wide.into());
+        assert_eq!((a.root_index, a.frac), (b.root_index, b.frac));
+    }
+}
@@ -0,0 +1,186 @@
+use num_complex::Complex32;
+
+use crate::{pixel_data::PixelDataDetail, IterMethod, PixelData, PixelData32, Polynomial};
+
+///////////////////////////////////////////////////////////////////
+
+/// Everything known about a pixel once iteration stops. Many times the size of `PixelData`,
+/// for shading modes that need more than the root and the iteration fraction.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct PixelRecord {
+    pub root_index: usize,
+    /// Iteration count as a fraction of the maximum, unpacked, so at full precision
+    pub frac: f32,
+    /// Where z ended up
+    pub z: Complex32,
+    /// Whole iterations taken, without the interpolation `frac` has
+    pub iterations: u32,
+    /// |f(z)| at the final z
+    pub residual: f32,
+    /// |f'(z)| at the final z
+    pub derivative: f32,
+    /// How far z moved on the last step, or 0 if it started out close enough to a root
+    pub step_size: f32,
+}
+
+/// A pixel format that rows can be calculated into
+pub trait CalculatedPixel: Copy + Default + Send + Sync {
+    fn calculate_row(
+        method: &IterMethod,
+        fz: &Polynomial,
+        roots: &[Complex32],
+        z: Complex32,
+        step: Complex32,
+        params: &[f32],
+        pixels: &mut [Self],
+    );
+}
+
+///////////////////////////////////////////////////////////////////
+
+impl From<PixelRecord> for PixelDataDetail {
+    fn from(value: PixelRecord) -> Self {
+        PixelDataDetail {
+            root_index: value.root_index,
+            frac: value.frac,
+        }
+    }
+}
+
+impl From<&PixelRecord> for PixelDataDetail {
+    fn from(value: &PixelRecord) -> Self {
+        (*value).into()
+    }
+}
+
+impl CalculatedPixel for PixelData {
+    fn calculate_row(
+        method: &IterMethod,
+        fz: &Polynomial,
+        roots: &[Complex32],
+        z: Complex32,
+        step: Complex32,
+        params: &[f32],
+        pixels: &mut [Self],
+    ) {
+        method.calculate_row(fz, roots, z, step, params, pixels);
+    }
+}
+
+/// The methods only write packed pixels in the narrow layout, so this packs records instead,
+/// which keep the iteration fraction at full precision
+impl CalculatedPixel for PixelData32 {
+    fn calculate_row(
+        method: &IterMethod,
+        fz: &Polynomial,
+        roots: &[Complex32],
+        z: Complex32,
+        step: Complex32,
+        params: &[f32],
+        pixels: &mut [Self],
+    ) {
+        let mut records = vec![PixelRecord::default(); pixels.len()];
+        method.calculate_row_records(fz, roots, z, step, params, &mut records);
+        for (pixel, record) in pixels.iter_mut().zip(records) {
+            *pixel = (record.root_index, record.frac).into();
+        }
+    }
+}
+
+impl CalculatedPixel for PixelRecord {
+    fn calculate_row(
+        method: &IterMethod,
+        fz: &Polynomial,
+        roots: &[Complex32],
+        z: Complex32,
+        step: Complex32,
+        params: &[f32],
+        pixels: &mut [Self],
+    ) {
+        method.calculate_row_records(fz, roots, z, step, params, pixels);
+    }
+}
+
+///////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ProgressiveRender, Roots, Viewport, ITER_METHODS, LOG_EPSILON, MAX_NEWTON_COUNT};
+
+    #[test]
+    fn test_matches_pixel_data() -> anyhow::Result<()> {
+        let fz = Polynomial::parse("z^5 + 3z^3 + z + 3")?;
+        let roots = Roots::new(&fz)?.roots;
+        let z = Complex32::new(-1.5, 0.9);
+        let step = Complex32::new(0.013, -0.004);
+
+        for method in &ITER_METHODS {
+            let params = method.default_params();
+            let mut pixel_data = vec![PixelData::default(); 200];
+            let mut records = vec![PixelRecord::default(); 200];
+            method.calculate_row(&fz, &roots, z, step, &params, &mut pixel_data);
+            method.calculate_row_records(&fz, &roots, z, step, &params, &mut records);
+
+            let mut z_start = z;
+            for (&pixel, record) in pixel_data.iter().zip(&records) {
+                let packed: PixelDataDetail = pixel.into();
+                let unpacked: PixelDataDetail =
+                    PixelData::from((record.root_index, record.frac)).into();
+                assert_eq!(packed.root_index, unpacked.root_index, "{}", method.name);
+                assert_eq!(packed.frac, unpacked.frac, "{}", method.name);
+
+                let count = record.frac * MAX_NEWTON_COUNT;
+                assert!(
+                    record.iterations as f32 <= count && count <= record.iterations as f32 + 1.
+                );
+                if record.frac < 1. {
+                    assert!(2. * record.residual.log10() <= LOG_EPSILON);
+                }
+
+                // The distance between the last two points of the orbit
+                let orbit = method.orbit(&fz, z_start, &params);
+                let step_size = match orbit.as_slice() {
+                    [.., z_prev, z_last] => (z_last - z_prev).norm(),
+                    _ => 0.,
+                };
+                // Orbits that blow up end in NaN either way
+                let is_same = record.step_size == step_size
+                    || (record.step_size.is_nan() && step_size.is_nan());
+                assert!(is_same, "{}: {record:?}", method.name);
+                z_start += step;
+            }
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_progressive_records() -> anyhow::Result<()> {
+        let fz = Polynomial::parse("z^3 - 1")?;
+        let roots = Roots::new(&fz)?.roots;
+        let method = IterMethod::by_id(0)?;
+        let params = method.default_params();
+        let viewport = Viewport::new(30, 20);
+
+        let mut pixel_data = vec![PixelData::default(); 30 * 20];
+        let mut records = vec![PixelRecord::default(); 30 * 20];
+        let mut progressive = ProgressiveRender::new(viewport, 2);
+        while progressive
+            .next_pass(&fz, &roots, method, &params, &mut pixel_data, 4)
+            .is_some()
+        {}
+        let mut progressive = ProgressiveRender::new(viewport, 2);
+        while progressive
+            .next_pass(&fz, &roots, method, &params, &mut records, 4)
+            .is_some()
+        {}
+
+        for (&pixel, record) in pixel_data.iter().zip(&records) {
+            let detail: PixelDataDetail = pixel.into();
+            assert_eq!(detail.root_index, record.root_index);
+        }
+
+        Ok(())
+    }
+}
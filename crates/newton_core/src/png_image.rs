@@ -0,0 +1,171 @@
+use std::io::Cursor;
+
+use anyhow::{anyhow, bail, Result};
+use png::{BitDepth, ColorType, Decoder, Encoder, Info};
+
+use crate::Scene;
+
+///////////////////////////////////////////////////////////////////
+
+/// Keyword of the text chunk holding the scene, as JSON
+pub const PNG_SCENE_KEYWORD: &str = "newton-scene";
+
+///////////////////////////////////////////////////////////////////
+
+/// Encodes RGBA bytes in row order as a PNG, with the scene that made them in an `iTXt` chunk
+pub fn encode_png(width: usize, height: usize, rgba: &[u8], scene: &Scene) -> Result<Vec<u8>> {
+    check_len(width, height, rgba.len())?;
+    write_png(width, height, BitDepth::Eight, rgba, scene)
+}
+
+/// Like `encode_png`, with 16 bits a channel
+pub fn encode_png16(width: usize, height: usize, rgba: &[u16], scene: &Scene) -> Result<Vec<u8>> {
+    check_len(width, height, rgba.len())?;
+    let data: Vec<u8> = rgba.iter().flat_map(|v| v.to_be_bytes()).collect();
+    write_png(width, height, BitDepth::Sixteen, &data, scene)
+}
+
+fn check_len(width: usize, height: usize, len: usize) -> Result<()> {
+    if len != 4 * width * height {
+        bail!(
+            "There are {len} channel values, but a {width}x{height} image needs {}",
+            4 * width * height
+        );
+    }
+    Ok(())
+}
+
+fn write_png(
+    width: usize,
+    height: usize,
+    depth: BitDepth,
+    data: &[u8],
+    scene: &Scene,
+) -> Result<Vec<u8>> {
+    let mut bytes = Vec::new();
+    let mut encoder = Encoder::new(&mut bytes, width as u32, height as u32);
+    encoder.set_color(ColorType::Rgba);
+    encoder.set_depth(depth);
+    encoder.add_itxt_chunk(PNG_SCENE_KEYWORD.to_string(), scene.to_json()?)?;
+
+    let mut writer = encoder.write_header()?;
+    writer.write_image_data(data)?;
+    writer.finish()?;
+    Ok(bytes)
+}
+
+/// Reads the scene back out of a PNG from `encode_png`. Other tools may rewrite the chunk as
+/// `tEXt`, or move it after the image data, so both are looked for.
+pub fn png_scene(bytes: &[u8]) -> Result<Scene> {
+    let mut reader = Decoder::new(Cursor::new(bytes)).read_info()?;
+    if let Some(json) = find_scene_text(reader.info())? {
+        return Scene::from_json(&json);
+    }
+
+    reader.finish()?;
+    let json =
+        find_scene_text(reader.info())?.ok_or_else(|| anyhow!("The PNG has no scene in it"))?;
+    Scene::from_json(&json)
+}
+
+fn find_scene_text(info: &Info) -> Result<Option<String>> {
+    if let Some(chunk) = info
+        .utf8_text
+        .iter()
+        .find(|chunk| chunk.keyword == PNG_SCENE_KEYWORD)
+    {
+        return Ok(Some(chunk.get_text()?));
+    }
+
+    Ok(info
+        .uncompressed_latin1_text
+        .iter()
+        .find(|chunk| chunk.keyword == PNG_SCENE_KEYWORD)
+        .map(|chunk| chunk.text.clone()))
+}
+
+///////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{calc_luminance_max, pixel_color, render_frame, IterMethod, OklchColor};
+
+    #[test]
+    fn test_round_trip() -> Result<()> {
+        let scene = Scene {
+            root_colors: Some(vec![
+                OklchColor { h: 10., c: 0.1 },
+                OklchColor { h: 130., c: 0.2 },
+                OklchColor { h: 250., c: 0.3 },
+            ]),
+            ..Scene::new("z^3 - 1")
+        };
+        let roots = scene.roots()?;
+        let method = IterMethod::by_id(scene.method)?;
+        let params = method.resolve_params(&scene.params)?;
+        let viewport = scene.viewport(40, 30);
+
+        let pixel_data = render_frame(&scene.fz()?, &roots.roots, method, &params, &viewport);
+        let dropoff = scene.coloring.pixel_dropoff();
+        let rgba: Vec<u8> = pixel_data
+            .iter()
+            .flat_map(|&pixel| {
+                pixel_color(
+                    pixel,
+                    &roots.colors,
+                    calc_luminance_max(dropoff),
+                    dropoff,
+                    &scene.coloring.lightness,
+                    scene.coloring.is_dark_non_convergence(),
+                )
+            })
+            .collect();
+
+        let bytes = encode_png(40, 30, &rgba, &scene)?;
+        assert_eq!(png_scene(&bytes)?, scene);
+
+        let mut reader = Decoder::new(Cursor::new(&bytes)).read_info()?;
+        let mut decoded = vec![0; reader.output_buffer_size().unwrap()];
+        reader.next_frame(&mut decoded)?;
+        assert_eq!(decoded, rgba);
+        Ok(())
+    }
+
+    #[test]
+    fn test_sixteen_bit() -> Result<()> {
+        let scene = Scene::new("z^3 - 1");
+        let rgba: Vec<u16> = (0..4 * 3 * 2).map(|i| i * 2731).collect();
+
+        let bytes = encode_png16(3, 2, &rgba, &scene)?;
+        assert_eq!(png_scene(&bytes)?, scene);
+
+        let mut reader = Decoder::new(Cursor::new(&bytes)).read_info()?;
+        assert_eq!(reader.info().bit_depth, BitDepth::Sixteen);
+        let mut decoded = vec![0; reader.output_buffer_size().unwrap()];
+        reader.next_frame(&mut decoded)?;
+        let decoded: Vec<u16> = decoded
+            .chunks_exact(2)
+            .map(|pair| u16::from_be_bytes([pair[0], pair[1]]))
+            .collect();
+        assert_eq!(decoded, rgba);
+        Ok(())
+    }
+
+    #[test]
+    fn test_plain_png() -> Result<()> {
+        let mut bytes = Vec::new();
+        let mut encoder = Encoder::new(&mut bytes, 2, 2);
+        encoder.set_color(ColorType::Rgba);
+        encoder.add_text_chunk("Software".to_string(), "paint".to_string())?;
+        let mut writer = encoder.write_header()?;
+        writer.write_image_data(&[0; 16])?;
+        writer.finish()?;
+
+        assert!(png_scene(&bytes).is_err());
+        assert!(png_scene(b"not a png").is_err());
+        assert!(encode_png(2, 2, &[0; 15], &Scene::new("z^3 - 1")).is_err());
+        assert!(encode_png16(2, 2, &[0; 17], &Scene::new("z^3 - 1")).is_err());
+        Ok(())
+    }
+}
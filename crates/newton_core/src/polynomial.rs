@@ -61,6 +61,18 @@ impl<T: TPolynomial> Polynomial<T> {
         Self::eval_terms(&self.derivative3, z)
     }
 
+    /// Terms of f(z) for order 0, or of its nth derivative, up to the third
+    #[cfg(feature = "simd")]
+    pub(crate) fn derivative_terms(&self, order: usize) -> &[PolynomialTerm<T>] {
+        match order {
+            0 => &self.function,
+            1 => &self.derivative,
+            2 => &self.derivative2,
+            3 => &self.derivative3,
+            _ => &[],
+        }
+    }
+
     /// Sums C*z^p polynomial terms
     fn eval_terms(terms: &[PolynomialTerm<T>], z: Complex32) -> Complex32 {
         terms
@@ -231,6 +243,50 @@ impl<T: TPolynomial> From<&Polynomial<T>> for CPolynomial {
     }
 }
 
+/// Only succeeds if every coefficient is real
+impl TryFrom<&CPolynomial> for FPolynomial {
+    type Error = anyhow::Error;
+
+    fn try_from(fz: &CPolynomial) -> Result<Self> {
+        let terms = fz
+            .function
+            .iter()
+            .map(|term| match term.coefficient.im {
+                0. => Ok((term.coefficient.re, term.power)),
+                _ => bail!("Polynomial has complex coefficients"),
+            })
+            .collect::<Result<Vec<_>>>()?;
+        Ok(terms.into())
+    }
+}
+
+///////////////////////////////////////////////////////////////////
+
+/// Writes the polynomial back out in the same form `parse` reads, highest power first
+impl std::fmt::Display for FPolynomial {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (i, term) in self.terms().iter().rev().enumerate() {
+            let &PolynomialTerm { coefficient, power } = term;
+            match (i, coefficient < 0.) {
+                (0, true) => write!(f, "-")?,
+                (0, false) => {}
+                (_, true) => write!(f, " - ")?,
+                (_, false) => write!(f, " + ")?,
+            }
+
+            let coefficient = coefficient.abs();
+            match (coefficient == 1., power) {
+                (_, 0) => write!(f, "{coefficient}")?,
+                (true, 1) => write!(f, "z")?,
+                (true, _) => write!(f, "z^{power}")?,
+                (false, 1) => write!(f, "{coefficient}z")?,
+                (false, _) => write!(f, "{coefficient}z^{power}")?,
+            }
+        }
+        Ok(())
+    }
+}
+
 ///////////////////////////////////////////////////////////////////
 
 #[cfg(test)]
@@ -300,6 +356,13 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_display() -> Result<()> {
+        let fz = FPolynomial::parse("-z^5 + 2.5z^3 - z^2 + z - 0.25")?;
+        assert_eq!(fz.to_string(), "-z^5 + 2.5z^3 - z^2 + z - 0.25");
+        Ok(())
+    }
+
     fn terms_to_vec(terms: &[PolynomialTerm<f32>]) -> Vec<(f32, i32)> {
         let mut terms = terms.iter().map(Into::into).collect::<Vec<_>>();
         terms.sort_by_key(|&(_, p)| -p);
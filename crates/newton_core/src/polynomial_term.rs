@@ -76,9 +76,10 @@ fn try_zed_parse(function_str: &str) -> Result<(f32, i32)> {
         .split_once('z')
         .ok_or_else(|| anyhow!("No z found in function"))?;
 
-    let coefficient = (!coef.is_empty())
-        .then(|| coef.parse::<f32>())
-        .unwrap_or(Ok(1.))?;
+    let coefficient = match coef.is_empty() {
+        true => 1.,
+        false => coef.parse::<f32>()?,
+    };
 
     let power = (!power.is_empty())
         .then(|| power.strip_prefix('^'))
@@ -120,7 +121,6 @@ impl<T: TPolynomial> From<&PolynomialTerm<T>> for PolynomialTerm<Complex32> {
 
 #[cfg(test)]
 mod tests {
-    #![allow(illegal_floating_point_literal_pattern)]
     use super::*;
 
     #[test]
@@ -0,0 +1,341 @@
+use std::ops::Range;
+
+use num_complex::Complex32;
+
+use crate::{CalculatedPixel, IterMethod, Polynomial, Viewport};
+
+///////////////////////////////////////////////////////////////////
+
+/// Renders a frame coarse to fine, in interlaced passes. The first pass iterates every
+/// `1 << coarsest_scale_factor`th pixel in both directions, and each pass after that halves the
+/// spacing, only iterating the samples the earlier passes don't already have. Every pixel of
+/// the frame ends up iterated exactly once.
+///
+/// The frame lives in a full resolution buffer owned by the caller. Each sample is written to
+/// its own pixel and blown up over the block to its lower right, so the buffer always holds
+/// the best preview so far. Finer blocks never cover a coarser sample, so samples are never
+/// lost.
+#[derive(Clone, Debug)]
+pub struct ProgressiveRender {
+    viewport: Viewport,
+    coarsest_scale_factor: u32,
+    scale_factor: u32,
+    /// Next row of samples in the current pass
+    row: usize,
+    is_done: bool,
+    computed: usize,
+}
+
+/// The samples of one row of a pass: every `stride`th pixel of row `y`, from `x0`
+#[derive(Clone, Debug, PartialEq)]
+pub struct PassRow<P> {
+    pub y: usize,
+    pub x0: usize,
+    pub stride: usize,
+    pub samples: Vec<P>,
+}
+
+///////////////////////////////////////////////////////////////////
+
+impl ProgressiveRender {
+    pub fn new(viewport: Viewport, coarsest_scale_factor: u32) -> Self {
+        Self {
+            viewport,
+            coarsest_scale_factor,
+            scale_factor: coarsest_scale_factor,
+            row: 0,
+            is_done: viewport.width == 0 || viewport.height == 0,
+            computed: 0,
+        }
+    }
+
+    /// A render with nothing left to do, for a frame that came from somewhere else, like a file
+    pub fn finished(viewport: Viewport) -> Self {
+        Self {
+            viewport,
+            coarsest_scale_factor: 0,
+            scale_factor: 0,
+            row: viewport.height,
+            is_done: true,
+            computed: 0,
+        }
+    }
+
+    pub fn viewport(&self) -> &Viewport {
+        &self.viewport
+    }
+
+    /// Pixel spacing of the samples in the current pass
+    pub fn scale(&self) -> usize {
+        1 << self.scale_factor
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.is_done
+    }
+
+    /// How many samples have been iterated so far
+    pub fn computed(&self) -> usize {
+        self.computed
+    }
+
+    /// Iterates up to `row_count` more rows of samples from the current pass into `pixel_data`,
+    /// which must hold the whole frame, in any `CalculatedPixel` format. With the `rayon`
+    /// feature, the rows are iterated in parallel. Returns the range of frame rows that changed,
+    /// or `None` once the frame is finished. Params must already have been resolved with
+    /// `resolve_params`.
+    pub fn next_pass<P: CalculatedPixel>(
+        &mut self,
+        fz: &Polynomial,
+        roots: &[Complex32],
+        method: &IterMethod,
+        params: &[f32],
+        pixel_data: &mut [P],
+        row_count: usize,
+    ) -> Option<Range<usize>> {
+        let rows = self.next_rows(row_count)?;
+        let samples = self.calculate_rows(fz, roots, method, params, &rows);
+        Some(self.write_rows(samples, pixel_data))
+    }
+
+    /// The first half of `next_pass`: picks up to `row_count` more rows of the current pass and
+    /// moves past them, or returns `None` once the frame is finished. Their samples can then be
+    /// iterated anywhere, with `calculate_rows` on a copy of this, and handed back to
+    /// `write_rows` before the next call.
+    pub fn next_rows(&mut self, row_count: usize) -> Option<Vec<usize>> {
+        self.start_next_pass_if_needed();
+        if self.is_done {
+            return None;
+        }
+
+        let scale = self.scale();
+        let rows: Vec<usize> = (self.row..self.viewport.height)
+            .step_by(scale)
+            .take(row_count.max(1))
+            .collect();
+        self.row = rows[rows.len() - 1] + scale;
+        Some(rows)
+    }
+
+    /// Iterates the samples of `rows` from `next_rows`, in parallel with the `rayon` feature
+    pub fn calculate_rows<P: CalculatedPixel>(
+        &self,
+        fz: &Polynomial,
+        roots: &[Complex32],
+        method: &IterMethod,
+        params: &[f32],
+        rows: &[usize],
+    ) -> Vec<PassRow<P>> {
+        let calculate_row = |&y: &usize| self.calculate_row(fz, roots, method, params, y);
+        #[cfg(feature = "rayon")]
+        {
+            use rayon::prelude::*;
+            rows.par_iter().map(calculate_row).collect()
+        }
+        #[cfg(not(feature = "rayon"))]
+        rows.iter().map(calculate_row).collect()
+    }
+
+    /// Blows the samples from `calculate_rows` up into `pixel_data`, which must hold the whole
+    /// frame. Returns the range of frame rows that changed.
+    pub fn write_rows<P: CalculatedPixel>(
+        &mut self,
+        rows: Vec<PassRow<P>>,
+        pixel_data: &mut [P],
+    ) -> Range<usize> {
+        let Viewport { width, height, .. } = self.viewport;
+        assert_eq!(pixel_data.len(), width * height);
+
+        let scale = self.scale();
+        let end = self.row.min(height);
+        let start = rows.first().map_or(end, |row| row.y);
+        for PassRow {
+            y,
+            x0,
+            stride,
+            samples,
+        } in rows
+        {
+            self.computed += samples.len();
+            let block_rows = y..(y + scale).min(height);
+            for (i, sample) in samples.into_iter().enumerate() {
+                let x = x0 + i * stride;
+                let block_columns = x..(x + scale).min(width);
+                for block_row in block_rows.clone() {
+                    let offset = block_row * width;
+                    pixel_data[offset + block_columns.start..offset + block_columns.end]
+                        .fill(sample);
+                }
+            }
+        }
+
+        start..end
+    }
+
+    fn start_next_pass_if_needed(&mut self) {
+        while !self.is_done && self.row >= self.viewport.height {
+            match self.scale_factor {
+                0 => self.is_done = true,
+                _ => {
+                    self.scale_factor -= 1;
+                    self.row = 0;
+                }
+            }
+        }
+    }
+
+    /// Iterates the samples in row y that earlier passes haven't
+    fn calculate_row<P: CalculatedPixel>(
+        &self,
+        fz: &Polynomial,
+        roots: &[Complex32],
+        method: &IterMethod,
+        params: &[f32],
+        y: usize,
+    ) -> PassRow<P> {
+        let scale = self.scale();
+        let is_coarsest = self.scale_factor == self.coarsest_scale_factor;
+
+        // Rows the previous pass went through already have every other sample
+        let (x0, stride) = match !is_coarsest && y.is_multiple_of(2 * scale) {
+            true => (scale, 2 * scale),
+            false => (0, scale),
+        };
+
+        let len = self.viewport.width.saturating_sub(x0).div_ceil(stride);
+        let mut samples = vec![P::default(); len];
+        if len > 0 {
+            let z = self.viewport.pixel_to_complex(x0 as f32, y as f32);
+            let step = self.viewport.pixel_step() * stride as f32;
+            P::calculate_row(method, fz, roots, z, step, params, &mut samples);
+        }
+
+        PassRow {
+            y,
+            x0,
+            stride,
+            samples,
+        }
+    }
+}
+
+///////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{render_frame, PixelData, PixelDataDetail, Roots};
+
+    fn render_all(
+        progressive: &mut ProgressiveRender,
+        fz: &Polynomial,
+        roots: &[Complex32],
+        method: &IterMethod,
+        row_count: usize,
+    ) -> Vec<PixelData> {
+        let Viewport { width, height, .. } = *progressive.viewport();
+        let params = method.default_params();
+        let mut pixel_data = vec![PixelData::default(); width * height];
+        while let Some(rows) =
+            progressive.next_pass(fz, roots, method, &params, &mut pixel_data, row_count)
+        {
+            assert!(!rows.is_empty() && rows.end <= height);
+        }
+        pixel_data
+    }
+
+    #[test]
+    fn test_iterates_each_pixel_once() -> anyhow::Result<()> {
+        let fz = Polynomial::parse("z^3 - 1")?;
+        let roots = Roots::new(&fz).unwrap().roots;
+        let method = IterMethod::by_id(0)?;
+
+        // Odd sizes, so the last blocks in each direction get cropped
+        for row_count in [1, 3] {
+            let viewport = Viewport::new(45, 29);
+            let mut progressive = ProgressiveRender::new(viewport, 3);
+            render_all(&mut progressive, &fz, &roots, method, row_count);
+            assert!(progressive.is_done());
+            assert_eq!(progressive.computed(), 45 * 29);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_matches_full_frame() -> anyhow::Result<()> {
+        let fz = Polynomial::parse("z^5 + 3z^3 + z + 3")?;
+        let roots = Roots::new(&fz).unwrap().roots;
+        let method = IterMethod::by_id(0)?;
+        let viewport = Viewport::new(64, 48);
+
+        let mut progressive = ProgressiveRender::new(viewport, 4);
+        let progressive_frame = render_all(&mut progressive, &fz, &roots, method, 2);
+        let frame = render_frame(&fz, &roots, method, &method.default_params(), &viewport);
+
+        // Samples start from a slightly different z than a full row would accumulate to, so
+        // the odd pixel right on a basin boundary can flip
+        let mismatched = progressive_frame
+            .iter()
+            .zip(&frame)
+            .filter(|&(&a, &b)| {
+                let (a, b): (PixelDataDetail, PixelDataDetail) = (a.into(), b.into());
+                a.root_index != b.root_index
+            })
+            .count();
+        assert!(mismatched * 200 < frame.len());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_coarse_pass_fills_frame() -> anyhow::Result<()> {
+        let fz = Polynomial::parse("z^3 - 1")?;
+        let roots = Roots::new(&fz).unwrap().roots;
+        let method = IterMethod::by_id(0)?;
+        let params = method.default_params();
+        let viewport = Viewport::new(20, 12);
+
+        // One pass at scale 8 covers everything, with each sample blown up into its block
+        let mut progressive = ProgressiveRender::new(viewport, 3);
+        let mut pixel_data = vec![PixelData(u16::MAX); 20 * 12];
+        let rows = progressive.next_pass(&fz, &roots, method, &params, &mut pixel_data, 8);
+        assert_eq!(rows, Some(0..12));
+        assert_eq!(progressive.computed(), 3 * 2);
+        for (index, &pixel) in pixel_data.iter().enumerate() {
+            let (x, y) = (index % 20, index / 20);
+            assert_eq!(pixel, pixel_data[(y - y % 8) * 20 + x - x % 8]);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_split_pass_matches_next_pass() -> anyhow::Result<()> {
+        let fz = Polynomial::parse("z^4 - 1")?;
+        let roots = Roots::new(&fz).unwrap().roots;
+        let method = IterMethod::by_id(0)?;
+        let params = method.default_params();
+        let viewport = Viewport::new(37, 23);
+
+        let mut progressive = ProgressiveRender::new(viewport, 3);
+        let mut split = progressive.clone();
+        let mut pixel_data = vec![PixelData::default(); 37 * 23];
+        let mut split_pixel_data = pixel_data.clone();
+        while let Some(rows) =
+            progressive.next_pass(&fz, &roots, method, &params, &mut pixel_data, 3)
+        {
+            // The samples are iterated on a copy, like a worker would
+            let split_rows = split.next_rows(3).unwrap();
+            let samples = split
+                .clone()
+                .calculate_rows(&fz, &roots, method, &params, &split_rows);
+            assert_eq!(split.write_rows(samples, &mut split_pixel_data), rows);
+        }
+        assert_eq!(split.next_rows(3), None);
+        assert_eq!(split_pixel_data, pixel_data);
+        assert_eq!(split.computed(), 37 * 23);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,231 @@
+use std::ops::RangeInclusive;
+
+use anyhow::{bail, Result};
+use num_complex::Complex32;
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+
+use crate::{polynomial::CPolynomial, roots::roots_of};
+
+///////////////////////////////////////////////////////////////////
+
+// How many candidates are drawn before giving up on meeting the constraint
+const MAX_RANDOM_ATTEMPTS: usize = 1000;
+
+///////////////////////////////////////////////////////////////////
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CoefficientKind {
+    /// Whole numbers, which make for formulas that are easy to read and share
+    Integer,
+    Float,
+    /// Complex coefficients can't be rendered yet, but are available for analysis
+    Complex,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum RootConstraint {
+    /// Every root lies strictly inside |z| < 1
+    InsideUnitDisk,
+}
+
+#[derive(Clone, Debug)]
+pub struct RandomSpec {
+    pub degree: RangeInclusive<usize>,
+    pub kind: CoefficientKind,
+    /// Coefficients are drawn from [-magnitude, magnitude] (per component, for complex ones)
+    pub magnitude: f32,
+    /// Chance that each term between the leading and constant terms is present. Those two are
+    /// always present, so the polynomial has its full degree and no roots stuck at zero.
+    pub density: f32,
+    pub constraint: Option<RootConstraint>,
+}
+
+/// A generated polynomial, along with the seed that reproduces it
+#[derive(Clone, Debug)]
+pub struct RandomPolynomial {
+    pub seed: u64,
+    pub fz: CPolynomial,
+}
+
+///////////////////////////////////////////////////////////////////
+
+impl TryFrom<u32> for CoefficientKind {
+    type Error = anyhow::Error;
+
+    fn try_from(value: u32) -> Result<Self> {
+        use CoefficientKind as CK;
+        Ok(match value {
+            0 => CK::Integer,
+            1 => CK::Float,
+            2 => CK::Complex,
+            _ => bail!("Invalid coefficient kind: {value}"),
+        })
+    }
+}
+
+impl Default for RandomSpec {
+    fn default() -> Self {
+        Self {
+            degree: 3..=9,
+            kind: CoefficientKind::Integer,
+            magnitude: 20.,
+            density: 0.5,
+            constraint: None,
+        }
+    }
+}
+
+///////////////////////////////////////////////////////////////////
+
+/// Draws a polynomial matching `spec`. The same spec and seed always give the same polynomial.
+pub fn random_polynomial(spec: &RandomSpec, seed: u64) -> Result<RandomPolynomial> {
+    if spec.degree.is_empty() || *spec.degree.start() == 0 {
+        bail!("Degree range must be non-empty and start at 1 or more");
+    }
+    let min_magnitude = match spec.kind {
+        CoefficientKind::Integer => 1.,
+        _ => f32::MIN_POSITIVE,
+    };
+    if !(spec.magnitude.is_finite() && spec.magnitude >= min_magnitude) {
+        bail!(
+            "Coefficient magnitude must be finite and at least {min_magnitude}, but was {}",
+            spec.magnitude
+        );
+    }
+    if !(0. ..=1.).contains(&spec.density) {
+        bail!("Density must be within 0 to 1, but was {}", spec.density);
+    }
+
+    let mut rng = ChaCha8Rng::seed_from_u64(seed);
+    for _ in 0..MAX_RANDOM_ATTEMPTS {
+        let degree = rng.gen_range(spec.degree.clone());
+        let coefficients = (0..=degree)
+            .map(|power| match power == 0 || power == degree {
+                true => nonzero_coefficient(&mut rng, spec),
+                false => match rng.gen_bool(spec.density as f64) {
+                    true => coefficient(&mut rng, spec),
+                    false => Complex32::default(),
+                },
+            })
+            .collect::<Vec<_>>();
+
+        let fz: CPolynomial = coefficients.into();
+        if meets_constraint(&fz, spec.constraint) {
+            return Ok(RandomPolynomial { seed, fz });
+        }
+    }
+
+    bail!("No polynomial met the constraints after {MAX_RANDOM_ATTEMPTS} attempts")
+}
+
+fn coefficient(rng: &mut ChaCha8Rng, spec: &RandomSpec) -> Complex32 {
+    let m = spec.magnitude;
+    match spec.kind {
+        CoefficientKind::Integer => rng.gen_range(-m..=m).round().into(),
+        CoefficientKind::Float => rng.gen_range(-m..=m).into(),
+        CoefficientKind::Complex => Complex32::new(rng.gen_range(-m..=m), rng.gen_range(-m..=m)),
+    }
+}
+
+fn nonzero_coefficient(rng: &mut ChaCha8Rng, spec: &RandomSpec) -> Complex32 {
+    loop {
+        let c = coefficient(rng, spec);
+        if c != Complex32::default() {
+            return c;
+        }
+    }
+}
+
+fn meets_constraint(fz: &CPolynomial, constraint: Option<RootConstraint>) -> bool {
+    match constraint {
+        None => true,
+        Some(RootConstraint::InsideUnitDisk) => {
+            let roots = roots_of(fz);
+            roots.len() == fz.order() && roots.iter().all(|root| root.norm() < 1.)
+        }
+    }
+}
+
+///////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Polynomial;
+
+    #[test]
+    fn test_seed_reproduces() -> Result<()> {
+        let spec = RandomSpec::default();
+        let a = random_polynomial(&spec, 1234)?;
+        let b = random_polynomial(&spec, 1234)?;
+        assert_eq!(a.fz.coefficients(), b.fz.coefficients());
+
+        let fz = Polynomial::try_from(&a.fz)?;
+        assert!(spec.degree.contains(&fz.order()));
+        assert!(fz.coefficients().iter().all(|c| c.fract() == 0.));
+        Ok(())
+    }
+
+    #[test]
+    fn test_complex_coefficients() -> Result<()> {
+        let spec = RandomSpec {
+            kind: CoefficientKind::Complex,
+            density: 1.,
+            ..Default::default()
+        };
+        let fz = random_polynomial(&spec, 7)?.fz;
+        assert!(Polynomial::try_from(&fz).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_invalid_spec() {
+        let invalid = [
+            RandomSpec {
+                degree: 0..=3,
+                ..Default::default()
+            },
+            RandomSpec {
+                magnitude: f32::INFINITY,
+                ..Default::default()
+            },
+            RandomSpec {
+                magnitude: f32::NAN,
+                ..Default::default()
+            },
+            RandomSpec {
+                magnitude: 0.5,
+                ..Default::default()
+            },
+            RandomSpec {
+                density: 1.5,
+                ..Default::default()
+            },
+        ];
+        for spec in invalid {
+            assert!(random_polynomial(&spec, 1).is_err(), "{spec:?}");
+        }
+        assert_eq!(
+            CoefficientKind::try_from(1).unwrap(),
+            CoefficientKind::Float
+        );
+        assert!(CoefficientKind::try_from(3).is_err());
+    }
+
+    #[test]
+    fn test_unit_disk_constraint() -> Result<()> {
+        let spec = RandomSpec {
+            degree: 3..=5,
+            kind: CoefficientKind::Float,
+            magnitude: 1.,
+            constraint: Some(RootConstraint::InsideUnitDisk),
+            ..Default::default()
+        };
+        for seed in 0..10 {
+            let fz = random_polynomial(&spec, seed)?.fz;
+            assert!(roots_of(&fz).iter().all(|root| root.norm() < 1.));
+        }
+        Ok(())
+    }
+}
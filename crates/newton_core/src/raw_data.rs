@@ -0,0 +1,423 @@
+use std::{fmt::Write as _, io::Write};
+
+use anyhow::{bail, Result};
+use flate2::{write::DeflateEncoder, Compression};
+use num_complex::Complex32;
+
+use crate::{pixel_data::PixelDataDetail, Affine, PixelRecord, Viewport, MAX_NEWTON_COUNT};
+
+///////////////////////////////////////////////////////////////////
+
+/// The numbers behind a render rather than its colors, one value per pixel in row order, for
+/// analysis elsewhere. Pixel (x, y) started iterating at `transform.to_complex(x, y)`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RawData {
+    pub width: usize,
+    pub height: usize,
+    pub transform: Affine,
+    /// Root each pixel converged to, or -1 where it didn't converge
+    pub root_index: Vec<i16>,
+    /// Smooth iteration count, with the fraction of the last iteration interpolated
+    pub iterations: Vec<f32>,
+    /// Where z ended up, which only records keep
+    pub z: Option<Vec<Complex32>>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum RawFormat {
+    /// A NumPy `.npz` archive, with `root_index`, `iterations`, optionally `z`, and `transform`
+    /// holding the origin, dx and dy as complex numbers
+    Npz,
+    /// The same arrays as plain `.npy` files, one per array, from `RawData::to_npy_files`
+    Npy,
+    /// One row per pixel, after `#` comment lines giving the transform
+    Csv,
+}
+
+///////////////////////////////////////////////////////////////////
+
+impl RawData {
+    /// From packed pixels, or records when the final z isn't wanted
+    pub fn from_pixels<P: Copy + Into<PixelDataDetail>>(pixels: &[P], viewport: &Viewport) -> Self {
+        let (root_index, iterations) = pixels
+            .iter()
+            .map(|&pixel| {
+                let PixelDataDetail { root_index, frac } = pixel.into();
+                let root_index = match frac == 1.0 {
+                    true => -1,
+                    false => root_index as i16,
+                };
+                (root_index, frac * MAX_NEWTON_COUNT)
+            })
+            .unzip();
+
+        Self {
+            width: viewport.width,
+            height: viewport.height,
+            transform: viewport.affine(),
+            root_index,
+            iterations,
+            z: None,
+        }
+    }
+
+    pub fn from_records(records: &[PixelRecord], viewport: &Viewport) -> Self {
+        Self {
+            z: Some(records.iter().map(|record| record.z).collect()),
+            ..Self::from_pixels(records, viewport)
+        }
+    }
+
+    /// The formats that fit in one file. `.npy` is a file per array, from `to_npy_files`.
+    pub fn encode(&self, format: RawFormat) -> Result<Vec<u8>> {
+        match format {
+            RawFormat::Npz => self.to_npz(),
+            RawFormat::Npy => bail!("Raw data as .npy is a file per array, not a single file"),
+            RawFormat::Csv => Ok(self.to_csv().into_bytes()),
+        }
+    }
+
+    /// The arrays of `to_npz` as they'd be unpacked, named like `root_index.npy`
+    pub fn to_npy_files(&self) -> Vec<(String, Vec<u8>)> {
+        let shape = [self.height, self.width];
+        let Affine { origin, dx, dy } = self.transform;
+
+        let mut arrays = vec![
+            (
+                "root_index",
+                npy("<i2", &shape, &self.root_index, |v| v.to_le_bytes()),
+            ),
+            (
+                "iterations",
+                npy("<f4", &shape, &self.iterations, |v| v.to_le_bytes()),
+            ),
+        ];
+        if let Some(z) = &self.z {
+            arrays.push(("z", npy("<c8", &shape, z, complex_bytes)));
+        }
+        arrays.push((
+            "transform",
+            npy("<c8", &[3], &[origin, dx, dy], complex_bytes),
+        ));
+
+        arrays
+            .into_iter()
+            .map(|(name, bytes)| (format!("{name}.npy"), bytes))
+            .collect()
+    }
+
+    /// Arrays are shaped (height, width), so they index as `[y, x]`
+    pub fn to_npz(&self) -> Result<Vec<u8>> {
+        zip(&self.to_npy_files())
+    }
+
+    pub fn to_csv(&self) -> String {
+        let Affine { origin, dx, dy } = self.transform;
+        let mut csv = format!(
+            "# {}x{} pixels, starting at z = origin + x * dx + y * dy\n\
+             # origin: {} {}\n\
+             # dx: {} {}\n\
+             # dy: {} {}\n",
+            self.width, self.height, origin.re, origin.im, dx.re, dx.im, dy.re, dy.im
+        );
+        csv.push_str(match self.z {
+            Some(_) => "x,y,root_index,iterations,z_re,z_im\n",
+            None => "x,y,root_index,iterations\n",
+        });
+
+        for i in 0..self.root_index.len() {
+            let (x, y) = (i % self.width, i / self.width);
+            // Writing to a String can't fail
+            let _ = write!(csv, "{x},{y},{},{}", self.root_index[i], self.iterations[i]);
+            if let Some(z) = &self.z {
+                let _ = write!(csv, ",{},{}", z[i].re, z[i].im);
+            }
+            csv.push('\n');
+        }
+        csv
+    }
+}
+
+impl RawFormat {
+    /// Picks the format from a file extension, or a name like "npz"
+    pub fn from_extension(extension: &str) -> Result<Self> {
+        match extension.to_ascii_lowercase().as_str() {
+            "npz" => Ok(Self::Npz),
+            "npy" => Ok(Self::Npy),
+            "csv" => Ok(Self::Csv),
+            _ => bail!("Raw data is written as .npz, .npy or .csv, not .{extension}"),
+        }
+    }
+}
+
+fn complex_bytes(z: Complex32) -> [u8; 8] {
+    let mut bytes = [0; 8];
+    bytes[..4].copy_from_slice(&z.re.to_le_bytes());
+    bytes[4..].copy_from_slice(&z.im.to_le_bytes());
+    bytes
+}
+
+/// A version 1.0 `.npy` file, in C order
+fn npy<T: Copy, const N: usize>(
+    descr: &str,
+    shape: &[usize],
+    values: &[T],
+    to_bytes: impl Fn(T) -> [u8; N],
+) -> Vec<u8> {
+    let shape = match shape {
+        [len] => format!("({len},)"),
+        _ => format!(
+            "({})",
+            shape
+                .iter()
+                .map(|v| v.to_string())
+                .collect::<Vec<_>>()
+                .join(", ")
+        ),
+    };
+    let mut header = format!("{{'descr': '{descr}', 'fortran_order': False, 'shape': {shape}, }}");
+
+    // The magic, version and length take 10 bytes, and the data should start 64 byte aligned
+    let padding = 63 - (10 + header.len()) % 64;
+    header.extend(std::iter::repeat_n(' ', padding));
+    header.push('\n');
+
+    let mut bytes = b"\x93NUMPY\x01\x00".to_vec();
+    bytes.extend((header.len() as u16).to_le_bytes());
+    bytes.extend(header.as_bytes());
+    values.iter().for_each(|&v| bytes.extend(to_bytes(v)));
+    bytes
+}
+
+/// A zip archive of deflated `.npy` files, as `numpy.savez_compressed` writes
+fn zip(files: &[(String, Vec<u8>)]) -> Result<Vec<u8>> {
+    // DOS date for 1980-01-01, the earliest a zip can hold
+    const DATE: u16 = (1 << 5) | 1;
+
+    let mut bytes = Vec::new();
+    let mut directory = Vec::new();
+    for (name, data) in files {
+        let mut encoder = DeflateEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(data)?;
+        let compressed = encoder.finish()?;
+
+        let (Ok(offset), Ok(size), Ok(compressed_size)) = (
+            u32::try_from(bytes.len()),
+            u32::try_from(data.len()),
+            u32::try_from(compressed.len()),
+        ) else {
+            bail!("The raw data is too big for a zip archive");
+        };
+
+        // Fields shared by the local header and the central directory entry: version needed,
+        // flags, method (deflate), time, date, CRC-32, sizes, name length and extra length
+        let mut common = Vec::new();
+        common.extend(20u16.to_le_bytes());
+        common.extend(0u16.to_le_bytes());
+        common.extend(8u16.to_le_bytes());
+        common.extend(0u16.to_le_bytes());
+        common.extend(DATE.to_le_bytes());
+        common.extend(crc32(data).to_le_bytes());
+        common.extend(compressed_size.to_le_bytes());
+        common.extend(size.to_le_bytes());
+        common.extend((name.len() as u16).to_le_bytes());
+        common.extend(0u16.to_le_bytes());
+
+        bytes.extend(0x04034b50u32.to_le_bytes());
+        bytes.extend(&common);
+        bytes.extend(name.as_bytes());
+        bytes.extend(compressed);
+
+        directory.extend(0x02014b50u32.to_le_bytes());
+        directory.extend(20u16.to_le_bytes());
+        directory.extend(&common);
+        // Comment length, disk, internal and external attributes
+        directory.extend([0; 10]);
+        directory.extend(offset.to_le_bytes());
+        directory.extend(name.as_bytes());
+    }
+
+    let Ok(directory_offset) = u32::try_from(bytes.len()) else {
+        bail!("The raw data is too big for a zip archive");
+    };
+    let count = files.len() as u16;
+    let directory_size = directory.len() as u32;
+    bytes.extend(directory);
+    bytes.extend(0x06054b50u32.to_le_bytes());
+    bytes.extend([0; 4]);
+    bytes.extend(count.to_le_bytes());
+    bytes.extend(count.to_le_bytes());
+    bytes.extend(directory_size.to_le_bytes());
+    bytes.extend(directory_offset.to_le_bytes());
+    bytes.extend(0u16.to_le_bytes());
+    Ok(bytes)
+}
+
+/// The CRC-32 zip uses, bit by bit, since it only runs once per export
+fn crc32(bytes: &[u8]) -> u32 {
+    !bytes.iter().fold(!0u32, |crc, &byte| {
+        (0..8).fold(crc ^ byte as u32, |crc, _| {
+            (crc >> 1) ^ (0xedb88320 & (crc & 1).wrapping_neg())
+        })
+    })
+}
+
+///////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use std::io::Read;
+
+    use flate2::read::DeflateDecoder;
+
+    use super::*;
+    use crate::{render_frame, render_frame_records, IterMethod, Polynomial, Roots};
+
+    fn new_raw_data(with_z: bool) -> Result<RawData> {
+        let fz = Polynomial::parse("z^3 - 1")?;
+        let roots = Roots::new(&fz)?.roots;
+        let method = IterMethod::by_id(0)?;
+        let params = method.default_params();
+        let viewport = Viewport {
+            rotation: 0.3,
+            ..Viewport::new(20, 12)
+        };
+
+        Ok(match with_z {
+            true => RawData::from_records(
+                &render_frame_records(&fz, &roots, method, &params, &viewport),
+                &viewport,
+            ),
+            false => RawData::from_pixels(
+                &render_frame(&fz, &roots, method, &params, &viewport),
+                &viewport,
+            ),
+        })
+    }
+
+    /// Reads the archive back by its central directory, checking each file's CRC
+    fn unzip(bytes: &[u8]) -> Vec<(String, Vec<u8>)> {
+        let u16_at = |i: usize| u16::from_le_bytes([bytes[i], bytes[i + 1]]) as usize;
+        let u32_at = |i: usize| u32::from_le_bytes(bytes[i..i + 4].try_into().unwrap()) as usize;
+
+        let end = bytes.len() - 22;
+        assert_eq!(u32_at(end), 0x06054b50);
+        let mut entry = u32_at(end + 16);
+        (0..u16_at(end + 10))
+            .map(|_| {
+                assert_eq!(u32_at(entry), 0x02014b50);
+                let (crc, compressed_size) = (u32_at(entry + 16), u32_at(entry + 20));
+                let name_len = u16_at(entry + 28);
+                let name = String::from_utf8(bytes[entry + 46..][..name_len].to_vec()).unwrap();
+
+                let local = u32_at(entry + 42);
+                assert_eq!(u32_at(local), 0x04034b50);
+                let start = local + 30 + u16_at(local + 26) + u16_at(local + 28);
+                let mut data = Vec::new();
+                DeflateDecoder::new(&bytes[start..][..compressed_size])
+                    .read_to_end(&mut data)
+                    .unwrap();
+                assert_eq!(crc32(&data) as usize, crc);
+
+                entry += 46 + name_len;
+                (name, data)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_crc32() {
+        assert_eq!(crc32(b""), 0);
+        assert_eq!(crc32(b"123456789"), 0xcbf43926);
+    }
+
+    #[test]
+    fn test_npz() -> Result<()> {
+        let raw_data = new_raw_data(true)?;
+        let files = unzip(&raw_data.to_npz()?);
+        let names: Vec<_> = files.iter().map(|(name, _)| name.as_str()).collect();
+        assert_eq!(
+            names,
+            ["root_index.npy", "iterations.npy", "z.npy", "transform.npy"]
+        );
+
+        for (name, data) in &files {
+            assert_eq!(&data[..8], b"\x93NUMPY\x01\x00");
+            let header_len = u16::from_le_bytes([data[8], data[9]]) as usize;
+            assert_eq!((10 + header_len) % 64, 0);
+
+            let header = std::str::from_utf8(&data[10..10 + header_len])?;
+            assert!(header.ends_with('\n'));
+            let (shape, item_size) = match name.as_str() {
+                "root_index.npy" => ("(12, 20)", 2),
+                "transform.npy" => ("(3,)", 8),
+                "z.npy" => ("(12, 20)", 8),
+                _ => ("(12, 20)", 4),
+            };
+            assert!(header.contains(&format!("'shape': {shape}")), "{header}");
+            let len = if shape == "(3,)" { 3 } else { 20 * 12 };
+            assert_eq!(data.len() - 10 - header_len, len * item_size);
+        }
+
+        let (_, transform) = &files[3];
+        let start = transform.len() - 3 * 8;
+        let origin = f32::from_le_bytes(transform[start..start + 4].try_into()?);
+        assert_eq!(origin, raw_data.transform.origin.re);
+        assert!(new_raw_data(false)?.z.is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn test_npy_files() -> Result<()> {
+        // The same files the archive holds, uncompressed
+        let raw_data = new_raw_data(false)?;
+        let files = raw_data.to_npy_files();
+        assert_eq!(files, unzip(&raw_data.to_npz()?));
+        let names: Vec<_> = files.iter().map(|(name, _)| name.as_str()).collect();
+        assert_eq!(names, ["root_index.npy", "iterations.npy", "transform.npy"]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_csv() -> Result<()> {
+        let raw_data = new_raw_data(true)?;
+        let csv = raw_data.to_csv();
+        let mut lines = csv.lines().filter(|line| !line.starts_with('#'));
+        assert_eq!(lines.next(), Some("x,y,root_index,iterations,z_re,z_im"));
+        assert_eq!(lines.count(), 20 * 12);
+
+        // The transform in the header maps the pixels back to where they started
+        let numbers = |key: &str| -> Vec<f32> {
+            let line = csv.lines().find(|line| line.starts_with(key)).unwrap();
+            line[key.len()..]
+                .split_whitespace()
+                .map(|v| v.parse().unwrap())
+                .collect()
+        };
+        let [origin, dx, dy] = ["# origin:", "# dx:", "# dy:"].map(|key| {
+            let v = numbers(key);
+            Complex32::new(v[0], v[1])
+        });
+        assert_eq!(Affine { origin, dx, dy }, raw_data.transform);
+
+        let line = new_raw_data(false)?.to_csv();
+        assert!(line.contains("\nx,y,root_index,iterations\n"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_non_convergence() {
+        let viewport = Viewport::new(2, 1);
+        let pixels = [
+            crate::PixelData::from((2, 0.25)),
+            crate::PixelData::from((0, 1.0)),
+        ];
+        let raw_data = RawData::from_pixels(&pixels, &viewport);
+        assert_eq!(raw_data.root_index, [2, -1]);
+        assert_eq!(raw_data.iterations[1], MAX_NEWTON_COUNT);
+        assert_eq!(RawFormat::from_extension("npy").unwrap(), RawFormat::Npy);
+        assert_eq!(RawFormat::from_extension("CSV").unwrap(), RawFormat::Csv);
+        assert!(RawFormat::from_extension("png").is_err());
+        assert!(raw_data.encode(RawFormat::Npy).is_err());
+    }
+}
@@ -1,19 +1,21 @@
+use anyhow::{bail, Result};
 use num_complex::{Complex32, ComplexFloat};
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 
 use crate::{
     polynomial::{CPolynomial, Polynomial, TPolynomial},
-    Lerp, DISTANCE_PER_PIXEL, SEPARATE_ROOTS_PIXEL_DISTANCE,
+    Lerp, DISTANCE_PER_PIXEL, MAX_ROOTS, SEPARATE_ROOTS_PIXEL_DISTANCE,
 };
 
 ///////////////////////////////////////////////////////////////////
 
-#[derive(Deserialize, Clone)]
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
 pub struct OklchColor {
     pub h: f32,
     pub c: f32,
 }
 
+#[derive(Clone, Debug)]
 pub struct Roots {
     pub roots: Vec<Complex32>,
     pub colors: Vec<OklchColor>,
@@ -22,14 +24,21 @@ pub struct Roots {
 ///////////////////////////////////////////////////////////////////
 
 impl Roots {
-    pub fn new<T: TPolynomial>(fz: &Polynomial<T>) -> Option<Self> {
+    /// Fails if there are no roots, or more than even the widest pixel layout can tell apart
+    pub fn new<T: TPolynomial>(fz: &Polynomial<T>) -> Result<Self> {
         if fz.is_constant() {
-            return None;
+            bail!("No roots found for polynomial");
         }
 
         let roots = roots_of(fz);
         if roots.is_empty() {
-            return None;
+            bail!("No roots found for polynomial");
+        }
+        if roots.len() > MAX_ROOTS {
+            bail!(
+                "Found {} roots, but pixels can only hold {MAX_ROOTS}",
+                roots.len()
+            );
         }
 
         let colors = roots
@@ -41,7 +50,7 @@ impl Roots {
             })
             .collect();
 
-        Some(Self { roots, colors })
+        Ok(Self { roots, colors })
     }
 }
 
@@ -133,3 +142,21 @@ fn merge_nearby_roots(roots: Vec<Complex32>) -> Vec<Complex32> {
 
     new_roots
 }
+
+///////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::polynomial::FPolynomial;
+
+    #[test]
+    fn test_too_many_roots() -> Result<()> {
+        let fz = FPolynomial::parse("z^20 - 1")?;
+        assert_eq!(Roots::new(&fz)?.roots.len(), 20);
+
+        assert!(Roots::new(&FPolynomial::parse("z^15 - 1")?).is_ok());
+        assert!(Roots::new(&FPolynomial::parse("3")?).is_err());
+        Ok(())
+    }
+}
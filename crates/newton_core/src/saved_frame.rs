@@ -0,0 +1,328 @@
+use std::io::{Read, Write};
+
+use anyhow::{anyhow, bail, Context, Result};
+use flate2::{read::DeflateDecoder, write::DeflateEncoder, Compression};
+use num_complex::Complex32;
+
+use crate::{
+    IterMethod, OklchColor, PixelData32, PixelDataDetail, Polynomial, Roots, SceneColoring,
+    Viewport, MAX_ROOTS,
+};
+
+///////////////////////////////////////////////////////////////////
+
+/// A calculated frame, with everything needed to recolor it or carry on from it later. Pixels
+/// are always saved 32 bits wide, so any frame loads the same whatever layout it was made in.
+#[derive(Clone, Debug)]
+pub struct SavedFrame {
+    pub fz: Polynomial,
+    pub roots: Roots,
+    pub viewport: Viewport,
+    pub method: u32,
+    pub params: Vec<f32>,
+    pub coloring: SceneColoring,
+    pub pixel_data: Vec<PixelData32>,
+}
+
+// Layout, all little endian:
+//   magic, version: u16, pixel bits: u8
+//   formula: u32 length + UTF-8
+//   method id: u32, params: u32 count + f32s
+//   roots: u32 count + (re, im, h, c) f32s each
+//   viewport: width, height: u32, center re, im, scale, rotation, shear: f32
+//   coloring: u32 length + JSON, as in scenes
+//   pixel data: u32 length + deflated pixel bits
+const MAGIC: &[u8; 4] = b"NWTF";
+const VERSION: u16 = 2;
+
+///////////////////////////////////////////////////////////////////
+
+impl SavedFrame {
+    pub fn save(&self) -> Result<Vec<u8>> {
+        let Viewport {
+            width,
+            height,
+            center,
+            scale,
+            rotation,
+            shear,
+        } = self.viewport;
+        if self.pixel_data.len() != width * height {
+            bail!(
+                "There are {} pixels, but the view is {width}x{height}",
+                self.pixel_data.len()
+            );
+        }
+
+        let mut bytes = MAGIC.to_vec();
+        bytes.extend(VERSION.to_le_bytes());
+        bytes.push(PixelData32::BITS as u8);
+
+        let formula = self.fz.to_string();
+        put_u32(&mut bytes, formula.len());
+        bytes.extend(formula.as_bytes());
+
+        put_u32(&mut bytes, self.method as usize);
+        put_u32(&mut bytes, self.params.len());
+        self.params
+            .iter()
+            .for_each(|&param| put_f32(&mut bytes, param));
+
+        put_u32(&mut bytes, self.roots.roots.len());
+        for (root, color) in self.roots.roots.iter().zip(&self.roots.colors) {
+            [root.re, root.im, color.h, color.c]
+                .into_iter()
+                .for_each(|v| put_f32(&mut bytes, v));
+        }
+
+        put_u32(&mut bytes, width);
+        put_u32(&mut bytes, height);
+        [center.re, center.im, scale, rotation, shear]
+            .into_iter()
+            .for_each(|v| put_f32(&mut bytes, v));
+
+        self.coloring.validate()?;
+        let coloring = serde_json::to_string(&self.coloring)?;
+        put_u32(&mut bytes, coloring.len());
+        bytes.extend(coloring.as_bytes());
+
+        let mut encoder = DeflateEncoder::new(Vec::new(), Compression::default());
+        for pixel in &self.pixel_data {
+            encoder.write_all(&pixel.0.to_le_bytes())?;
+        }
+        let compressed = encoder.finish()?;
+        put_u32(&mut bytes, compressed.len());
+        bytes.extend(compressed);
+
+        Ok(bytes)
+    }
+
+    /// Checks everything it reads, so a damaged or hand-made file can't produce a frame that
+    /// would panic when colored
+    pub fn load(bytes: &[u8]) -> Result<Self> {
+        let mut reader = Reader { bytes };
+        if reader.take(MAGIC.len())? != MAGIC {
+            bail!("Not a saved frame");
+        }
+
+        let version = u16::from_le_bytes(reader.array()?);
+        if version != VERSION {
+            bail!("Saved frames of version {version} aren't supported, only {VERSION}");
+        }
+        let pixel_bits = reader.array::<1>()?[0] as u32;
+        if pixel_bits != PixelData32::BITS {
+            bail!(
+                "The frame was saved with {pixel_bits}-bit pixels, but only {}-bit pixels are supported",
+                PixelData32::BITS
+            );
+        }
+
+        let formula_len = reader.u32()?;
+        let formula = std::str::from_utf8(reader.take(formula_len)?)?;
+        let fz = Polynomial::parse(formula)?;
+
+        let method = reader.u32()? as u32;
+        let params = (0..reader.u32()?)
+            .map(|_| reader.f32())
+            .collect::<Result<Vec<_>>>()?;
+        let params = IterMethod::by_id(method)?.resolve_params(&params)?;
+
+        let root_count = reader.u32()?;
+        if root_count == 0 || root_count > MAX_ROOTS {
+            bail!("There should be 1 to {MAX_ROOTS} roots, but there are {root_count}");
+        }
+        let mut roots = Roots {
+            roots: Vec::with_capacity(root_count),
+            colors: Vec::with_capacity(root_count),
+        };
+        for _ in 0..root_count {
+            let [re, im, h, c] = [reader.f32()?, reader.f32()?, reader.f32()?, reader.f32()?];
+            roots.roots.push(Complex32::new(re, im));
+            roots.colors.push(OklchColor { h, c });
+        }
+
+        let (width, height) = (reader.u32()?, reader.u32()?);
+        let [re, im, scale, rotation, shear] = [
+            reader.f32()?,
+            reader.f32()?,
+            reader.f32()?,
+            reader.f32()?,
+            reader.f32()?,
+        ];
+        let viewport = Viewport {
+            width,
+            height,
+            center: Complex32::new(re, im),
+            scale,
+            rotation,
+            shear,
+        };
+
+        let coloring_len = reader.u32()?;
+        let coloring: SceneColoring = serde_json::from_slice(reader.take(coloring_len)?)?;
+        coloring.validate()?;
+
+        let compressed_len = reader.u32()?;
+        let compressed = reader.take(compressed_len)?;
+        if !reader.bytes.is_empty() {
+            bail!(
+                "There are {} bytes past the end of the frame",
+                reader.bytes.len()
+            );
+        }
+
+        let pixel_len = width
+            .checked_mul(height)
+            .and_then(|len| len.checked_mul(std::mem::size_of::<u32>()))
+            .ok_or_else(|| anyhow!("A {width}x{height} frame is too big"))?;
+        let mut pixel_bytes = Vec::new();
+        DeflateDecoder::new(compressed)
+            .take(pixel_len as u64 + 1)
+            .read_to_end(&mut pixel_bytes)
+            .context("The pixel data is damaged")?;
+        if pixel_bytes.len() != pixel_len {
+            bail!("The pixel data doesn't fit a {width}x{height} frame");
+        }
+
+        let pixel_data: Vec<PixelData32> = pixel_bytes
+            .chunks_exact(std::mem::size_of::<u32>())
+            .map(|chunk| PixelData32(u32::from_le_bytes(chunk.try_into().unwrap())))
+            .collect();
+        for &pixel in &pixel_data {
+            let PixelDataDetail { root_index, .. } = pixel.into();
+            if root_index >= root_count {
+                bail!("A pixel converged to root {root_index}, but there are only {root_count}");
+            }
+        }
+
+        Ok(Self {
+            fz,
+            roots,
+            viewport,
+            method,
+            params,
+            coloring,
+            pixel_data,
+        })
+    }
+}
+
+fn put_u32(bytes: &mut Vec<u8>, value: usize) {
+    bytes.extend((value as u32).to_le_bytes());
+}
+
+pub(crate) fn put_f32(bytes: &mut Vec<u8>, value: f32) {
+    bytes.extend(value.to_le_bytes());
+}
+
+/// Reads from the front of the bytes, failing rather than panicking when they run out
+pub(crate) struct Reader<'a> {
+    pub bytes: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    pub fn take(&mut self, len: usize) -> Result<&'a [u8]> {
+        if len > self.bytes.len() {
+            bail!("The file ends early");
+        }
+        let (taken, rest) = self.bytes.split_at(len);
+        self.bytes = rest;
+        Ok(taken)
+    }
+
+    pub fn array<const N: usize>(&mut self) -> Result<[u8; N]> {
+        Ok(self.take(N)?.try_into().unwrap())
+    }
+
+    pub fn u32(&mut self) -> Result<usize> {
+        Ok(u32::from_le_bytes(self.array()?) as usize)
+    }
+
+    pub fn f32(&mut self) -> Result<f32> {
+        Ok(f32::from_le_bytes(self.array()?))
+    }
+}
+
+///////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{render_frame_wide, ChaosColor, LightnessCurve};
+
+    fn new_frame() -> Result<SavedFrame> {
+        let fz = Polynomial::parse("z^5 + 3z^3 + z + 3")?;
+        let roots = Roots::new(&fz)?;
+        let method = IterMethod::by_id(0)?;
+        let params = Vec::new();
+        let viewport = Viewport {
+            center: Complex32::new(0.25, -0.5),
+            rotation: 0.3,
+            ..Viewport::new(48, 32)
+        };
+        let pixel_data = render_frame_wide(&fz, &roots.roots, method, &params, &viewport);
+        Ok(SavedFrame {
+            fz,
+            roots,
+            viewport,
+            method: method.id,
+            params,
+            coloring: SceneColoring {
+                lightness: LightnessCurve::parse("gamma 2.2")?,
+                dropoff: 0.3,
+                chaos: ChaosColor::White,
+                palette: None,
+            },
+            pixel_data,
+        })
+    }
+
+    #[test]
+    fn test_round_trip() -> Result<()> {
+        let frame = new_frame()?;
+        let bytes = frame.save()?;
+        let loaded = SavedFrame::load(&bytes)?;
+
+        assert_eq!(loaded.fz.to_string(), frame.fz.to_string());
+        assert_eq!(loaded.roots.roots, frame.roots.roots);
+        assert_eq!(loaded.roots.colors, frame.roots.colors);
+        assert_eq!(loaded.viewport, frame.viewport);
+        assert_eq!(loaded.method, frame.method);
+        assert_eq!(loaded.params, frame.params);
+        assert_eq!(loaded.coloring, frame.coloring);
+        assert_eq!(loaded.pixel_data, frame.pixel_data);
+
+        // Pixels in the same basin share their top bits, so even with the header it's smaller
+        let raw_len = frame.pixel_data.len() * std::mem::size_of::<u32>();
+        assert!(bytes.len() < raw_len);
+        Ok(())
+    }
+
+    #[test]
+    fn test_rejects_bad_files() -> Result<()> {
+        let bytes = new_frame()?.save()?;
+
+        assert!(SavedFrame::load(&bytes[..bytes.len() - 1]).is_err());
+        assert!(SavedFrame::load(&[bytes.as_slice(), &[0]].concat()).is_err());
+        assert!(SavedFrame::load(b"PNG").is_err());
+
+        let mut wrong_version = bytes.clone();
+        wrong_version[4] = 1;
+        assert!(SavedFrame::load(&wrong_version).is_err());
+
+        // A frame claiming fewer roots than its pixels go to
+        let mut frame = new_frame()?;
+        frame.roots.roots.truncate(2);
+        frame.roots.colors.truncate(2);
+        assert!(SavedFrame::load(&frame.save()?).is_err());
+
+        let mut frame = new_frame()?;
+        frame.pixel_data.pop();
+        assert!(frame.save().is_err());
+
+        let mut frame = new_frame()?;
+        frame.coloring.dropoff = 2.;
+        assert!(frame.save().is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,355 @@
+use anyhow::{bail, Result};
+use num_complex::Complex32;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    GradientPalette, IterMethod, Lerp, LightnessCurve, OklchColor, Polynomial, Roots, Viewport,
+    COMPLEX_WINDOW,
+};
+
+///////////////////////////////////////////////////////////////////
+
+/// Everything that makes a picture, apart from its size, in the same terms as the web app's
+/// settings. Scenes are what the CLI and the web app exchange.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields, rename_all = "camelCase")]
+pub struct Scene {
+    pub version: u32,
+    pub formula: String,
+    /// Root finding method id, where 0 is Newton's method
+    #[serde(default)]
+    pub method: u32,
+    /// Left empty for the method's defaults
+    #[serde(default)]
+    pub params: Vec<f32>,
+    #[serde(default)]
+    pub view: SceneView,
+    #[serde(default)]
+    pub coloring: SceneColoring,
+    /// A color for each root, in order. Left out, the roots get their default colors.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub root_colors: Option<Vec<OklchColor>>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "camelCase")]
+pub struct SceneView {
+    /// Real and imaginary parts of the middle of the picture
+    pub center: [f32; 2],
+    /// Zoom relative to the default window, where smaller values zoom in
+    pub scale: f32,
+    /// In degrees
+    pub rotation: f32,
+    pub shear: f32,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "camelCase")]
+pub struct SceneColoring {
+    /// One of the built-in modes by name, or any other curve in `LightnessCurve`'s text format
+    pub lightness: LightnessCurve,
+    /// How quickly colors fade with iteration count, on the web app's 0 to 1 slider scale
+    pub dropoff: f32,
+    pub chaos: ChaosColor,
+    /// Gradients in `GradientPalette`'s text format, which color each root in place of the
+    /// lightness curve
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub palette: Option<GradientPalette>,
+}
+
+/// Color for points that never converge
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ChaosColor {
+    #[default]
+    Black,
+    White,
+}
+
+pub const SCENE_VERSION: u32 = 1;
+
+///////////////////////////////////////////////////////////////////
+
+impl Default for SceneView {
+    fn default() -> Self {
+        Self {
+            center: [0., 0.],
+            scale: 1.,
+            rotation: 0.,
+            shear: 0.,
+        }
+    }
+}
+
+impl Default for SceneColoring {
+    fn default() -> Self {
+        Self {
+            lightness: LightnessCurve::default(),
+            dropoff: 0.5,
+            chaos: ChaosColor::Black,
+            palette: None,
+        }
+    }
+}
+
+impl Scene {
+    /// The default view and coloring of a formula
+    pub fn new(formula: &str) -> Self {
+        Self {
+            version: SCENE_VERSION,
+            formula: formula.to_string(),
+            method: 0,
+            params: Vec::new(),
+            view: SceneView::default(),
+            coloring: SceneColoring::default(),
+            root_colors: None,
+        }
+    }
+
+    pub fn from_json(json: &str) -> Result<Self> {
+        let scene: Self = serde_json::from_str(json)?;
+        scene.validate()?;
+        Ok(scene)
+    }
+
+    pub fn to_json(&self) -> Result<String> {
+        self.validate()?;
+        Ok(serde_json::to_string_pretty(self)?)
+    }
+
+    #[cfg(feature = "toml")]
+    pub fn from_toml(text: &str) -> Result<Self> {
+        let scene: Self = toml::from_str(text)?;
+        scene.validate()?;
+        Ok(scene)
+    }
+
+    #[cfg(feature = "toml")]
+    pub fn to_toml(&self) -> Result<String> {
+        self.validate()?;
+        Ok(toml::to_string_pretty(self)?)
+    }
+
+    /// Checks that the scene can be rendered as it is
+    pub fn validate(&self) -> Result<()> {
+        if self.version != SCENE_VERSION {
+            bail!(
+                "Scenes of version {} aren't supported, only {SCENE_VERSION}",
+                self.version
+            );
+        }
+
+        self.roots()?;
+        IterMethod::by_id(self.method)?.resolve_params(&self.params)?;
+
+        let SceneView {
+            center,
+            scale,
+            rotation,
+            shear,
+        } = self.view;
+        if !(scale.is_finite() && scale > 0.) {
+            bail!("The view's scale must be above 0, but was {scale}");
+        }
+        if ![center[0], center[1], rotation, shear]
+            .iter()
+            .all(|v| v.is_finite())
+        {
+            bail!("The view's center, rotation and shear must be finite numbers");
+        }
+
+        self.coloring.validate()
+    }
+
+    pub fn fz(&self) -> Result<Polynomial> {
+        Polynomial::parse(&self.formula)
+    }
+
+    /// The roots of the formula, with the scene's colors if it has any
+    pub fn roots(&self) -> Result<Roots> {
+        let mut roots = Roots::new(&self.fz()?)?;
+        if let Some(colors) = &self.root_colors {
+            if colors.len() != roots.colors.len() {
+                bail!(
+                    "{} has {} roots, but the scene gives {} colors",
+                    self.formula,
+                    roots.colors.len(),
+                    colors.len()
+                );
+            }
+            if colors
+                .iter()
+                .any(|color| !(color.h.is_finite() && color.c >= 0.))
+            {
+                bail!("Root colors need a finite hue and a chroma of at least 0");
+            }
+            roots.colors = colors.clone();
+        }
+        Ok(roots)
+    }
+
+    pub fn viewport(&self, width: usize, height: usize) -> Viewport {
+        let SceneView {
+            center,
+            scale,
+            rotation,
+            shear,
+        } = self.view;
+        Viewport {
+            width,
+            height,
+            center: Complex32::new(center[0], center[1]),
+            scale: COMPLEX_WINDOW * scale,
+            rotation: rotation.to_radians(),
+            shear,
+        }
+    }
+}
+
+impl SceneView {
+    /// The inverse of `Scene::viewport`, leaving the size behind
+    pub fn from_viewport(viewport: &Viewport) -> Self {
+        Self {
+            center: [viewport.center.re, viewport.center.im],
+            scale: viewport.scale / COMPLEX_WINDOW,
+            rotation: viewport.rotation.to_degrees(),
+            shear: viewport.shear,
+        }
+    }
+}
+
+impl SceneColoring {
+    pub fn validate(&self) -> Result<()> {
+        self.lightness.validate()?;
+        let dropoff = self.dropoff;
+        if !(0. ..=1.).contains(&dropoff) {
+            bail!("Dropoff must be within 0 to 1, but was {dropoff}");
+        }
+        Ok(())
+    }
+
+    /// Dropoff as `pixel_color` takes it, mapped from the slider scale the same way as the web app
+    pub fn pixel_dropoff(&self) -> f32 {
+        Self::pixel_dropoff_from_slider(self.dropoff)
+    }
+
+    /// `pixel_dropoff` for a dropoff on the slider scale, from anything that keeps it that way
+    pub fn pixel_dropoff_from_slider(dropoff: f32) -> f32 {
+        dropoff.lerp(1.0, 0.6)
+    }
+
+    /// The inverse of `pixel_dropoff`, back onto the slider scale
+    pub fn slider_dropoff(pixel_dropoff: f32) -> f32 {
+        pixel_dropoff.ilerp_clamped(1.0, 0.6)
+    }
+
+    pub fn is_dark_non_convergence(&self) -> bool {
+        self.chaos == ChaosColor::Black
+    }
+}
+
+///////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::LightnessMode;
+
+    fn new_scene() -> Scene {
+        Scene {
+            method: 0,
+            params: Vec::new(),
+            view: SceneView {
+                center: [0.5, -0.25],
+                scale: 0.1,
+                rotation: 30.,
+                shear: 0.2,
+            },
+            coloring: SceneColoring {
+                lightness: LightnessMode::InvertedParabola.into(),
+                dropoff: 0.3,
+                chaos: ChaosColor::White,
+                palette: None,
+            },
+            root_colors: Some(vec![
+                OklchColor { h: 10., c: 0.1 },
+                OklchColor { h: 130., c: 0.2 },
+                OklchColor { h: 250., c: 0.3 },
+            ]),
+            ..Scene::new("z^3 - 1")
+        }
+    }
+
+    #[test]
+    fn test_json_round_trip() -> Result<()> {
+        let scene = new_scene();
+        let json = scene.to_json()?;
+        assert!(json.contains("\"lightness\": \"inverted-parabola\""));
+        assert_eq!(Scene::from_json(&json)?, scene);
+
+        let curve = Scene {
+            coloring: SceneColoring {
+                lightness: LightnessCurve::parse("bezier 0.4 0 0.2 1")?,
+                ..Default::default()
+            },
+            ..new_scene()
+        };
+        let json = curve.to_json()?;
+        assert!(json.contains("\"lightness\": \"bezier 0.4 0 0.2 1\""));
+        assert_eq!(Scene::from_json(&json)?, curve);
+
+        let palette = Scene {
+            coloring: SceneColoring {
+                palette: Some(GradientPalette::parse(
+                    "gradient oklch\n0 #102040\n1 white\ngradient\n0 black\n0.5 #f00\n1 #ff0",
+                )?),
+                ..Default::default()
+            },
+            ..new_scene()
+        };
+        let json = palette.to_json()?;
+        assert!(json.contains("\"palette\": \"gradient oklch\\n0 oklab("));
+        assert_eq!(Scene::from_json(&json)?, palette);
+        assert!(!new_scene().to_json()?.contains("palette"));
+        Ok(())
+    }
+
+    #[cfg(feature = "toml")]
+    #[test]
+    fn test_toml_round_trip() -> Result<()> {
+        let scene = new_scene();
+        assert_eq!(Scene::from_toml(&scene.to_toml()?)?, scene);
+        Ok(())
+    }
+
+    #[test]
+    fn test_defaults() -> Result<()> {
+        let scene = Scene::from_json(r#"{ "version": 1, "formula": "z^4 - 1" }"#)?;
+        assert_eq!(scene, Scene::new("z^4 - 1"));
+        assert_eq!(scene.viewport(100, 100), Viewport::new(100, 100));
+
+        let view = SceneView::from_viewport(&new_scene().viewport(30, 20));
+        assert!((view.scale - 0.1).abs() < 1e-6 && (view.rotation - 30.).abs() < 1e-4);
+        let dropoff = SceneColoring::slider_dropoff(new_scene().coloring.pixel_dropoff());
+        assert!((dropoff - 0.3).abs() < 1e-6);
+        Ok(())
+    }
+
+    #[test]
+    fn test_validation() {
+        let invalid = [
+            r#"{ "version": 2, "formula": "z^3 - 1" }"#,
+            r#"{ "version": 1, "formula": "z^" }"#,
+            r#"{ "version": 1, "formula": "z^3 - 1", "method": 9 }"#,
+            r#"{ "version": 1, "formula": "z^3 - 1", "params": [7] }"#,
+            r#"{ "version": 1, "formula": "z^3 - 1", "view": { "scale": 0 } }"#,
+            r#"{ "version": 1, "formula": "z^3 - 1", "coloring": { "dropoff": 2 } }"#,
+            r#"{ "version": 1, "formula": "z^3 - 1", "coloring": { "lightness": "gamma -1" } }"#,
+            r#"{ "version": 1, "formula": "z^3 - 1", "rootColors": [{ "h": 0, "c": 0.1 }] }"#,
+            r#"{ "version": 1, "formula": "z^3 - 1", "colour": "red" }"#,
+        ];
+        for json in invalid {
+            assert!(Scene::from_json(json).is_err(), "{json}");
+        }
+    }
+}
@@ -0,0 +1,384 @@
+use anyhow::{bail, Result};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+
+use crate::{
+    saved_frame::{put_f32, Reader},
+    ChaosColor, GradientPalette, LightnessCurve, LightnessMode, OklchColor, Scene, SceneColoring,
+    SceneView, SCENE_VERSION,
+};
+
+///////////////////////////////////////////////////////////////////
+
+// Layout, all little endian, before base64url:
+//   version: u8
+//   formula: u16 length + UTF-8
+//   method id: u8, params: u8 count + f32s
+//   view: center re, im, scale, rotation, shear: f32
+//   coloring: flags: u8 (lightness mode in bits 0-1, white chaos in bit 2), dropoff: f32
+//   root colors: u8 count, 0 for the defaults, + (h, c) f32s each
+// Version 2 adds:
+//   lightness curve: u16 length + its text, empty for the built-in modes, which stay in the flags
+// Version 3 adds:
+//   gradient palette: u16 length + its text, empty for none
+//
+// Later versions only ever append fields, so a code from a newer build still decodes, losing
+// whatever it added. Version 1 codes leave custom curves behind as normal lightness.
+const CODE_VERSION: u8 = 3;
+
+const LIGHTNESS_MASK: u8 = 0b11;
+const WHITE_CHAOS: u8 = 0b100;
+
+///////////////////////////////////////////////////////////////////
+
+impl Scene {
+    /// A short, URL-safe code for the scene, to pass around in a link
+    pub fn encode(&self) -> Result<String> {
+        self.validate()?;
+
+        let mut bytes = vec![CODE_VERSION];
+
+        let Ok(formula_len) = u16::try_from(self.formula.len()) else {
+            bail!("The formula is too long to encode");
+        };
+        bytes.extend(formula_len.to_le_bytes());
+        bytes.extend(self.formula.as_bytes());
+
+        // Validation has checked the method and its parameter count, so these fit
+        bytes.push(self.method as u8);
+        bytes.push(self.params.len() as u8);
+        self.params
+            .iter()
+            .for_each(|&param| put_f32(&mut bytes, param));
+
+        let SceneView {
+            center,
+            scale,
+            rotation,
+            shear,
+        } = self.view;
+        [center[0], center[1], scale, rotation, shear]
+            .into_iter()
+            .for_each(|v| put_f32(&mut bytes, v));
+
+        let SceneColoring {
+            lightness,
+            dropoff,
+            chaos,
+            palette,
+        } = &self.coloring;
+        let (mode, curve) = match lightness {
+            LightnessCurve::Mode(mode) => (*mode, String::new()),
+            curve => (LightnessMode::Normal, curve.to_text()),
+        };
+        let chaos_flag = match chaos {
+            ChaosColor::Black => 0,
+            ChaosColor::White => WHITE_CHAOS,
+        };
+        bytes.push(mode as u8 | chaos_flag);
+        put_f32(&mut bytes, *dropoff);
+
+        let colors = self.root_colors.as_deref().unwrap_or_default();
+        let Ok(color_count) = u8::try_from(colors.len()) else {
+            bail!("There are too many root colors to encode");
+        };
+        bytes.push(color_count);
+        for color in colors {
+            put_f32(&mut bytes, color.h);
+            put_f32(&mut bytes, color.c);
+        }
+
+        let Ok(curve_len) = u16::try_from(curve.len()) else {
+            bail!("The lightness curve is too long to encode");
+        };
+        bytes.extend(curve_len.to_le_bytes());
+        bytes.extend(curve.as_bytes());
+
+        let palette = palette
+            .as_ref()
+            .map(GradientPalette::to_text)
+            .unwrap_or_default();
+        let Ok(palette_len) = u16::try_from(palette.len()) else {
+            bail!("The gradient palette is too long to encode");
+        };
+        bytes.extend(palette_len.to_le_bytes());
+        bytes.extend(palette.as_bytes());
+
+        Ok(URL_SAFE_NO_PAD.encode(bytes))
+    }
+
+    /// Reads a code from `encode`, checking that the scene can be rendered
+    pub fn decode(code: &str) -> Result<Self> {
+        let Ok(bytes) = URL_SAFE_NO_PAD.decode(code.trim()) else {
+            bail!("Not a scene code");
+        };
+        let mut reader = Reader { bytes: &bytes };
+
+        let version = reader.array::<1>()?[0];
+        if version == 0 {
+            bail!("Not a scene code");
+        }
+
+        let formula_len = u16::from_le_bytes(reader.array()?) as usize;
+        let formula = std::str::from_utf8(reader.take(formula_len)?)?.to_string();
+
+        let method = reader.array::<1>()?[0] as u32;
+        let params = (0..reader.array::<1>()?[0])
+            .map(|_| reader.f32())
+            .collect::<Result<Vec<_>>>()?;
+
+        let [re, im, scale, rotation, shear] = [
+            reader.f32()?,
+            reader.f32()?,
+            reader.f32()?,
+            reader.f32()?,
+            reader.f32()?,
+        ];
+
+        let flags = reader.array::<1>()?[0];
+        let mut lightness = LightnessMode::try_from((flags & LIGHTNESS_MASK) as u32)?.into();
+        let chaos = match flags & WHITE_CHAOS {
+            0 => ChaosColor::Black,
+            _ => ChaosColor::White,
+        };
+        let dropoff = reader.f32()?;
+
+        let color_count = reader.array::<1>()?[0];
+        let colors = (0..color_count)
+            .map(|_| {
+                Ok(OklchColor {
+                    h: reader.f32()?,
+                    c: reader.f32()?,
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        if version >= 2 {
+            let curve_len = u16::from_le_bytes(reader.array()?) as usize;
+            if curve_len > 0 {
+                lightness = LightnessCurve::parse(std::str::from_utf8(reader.take(curve_len)?)?)?;
+            }
+        }
+
+        let mut palette = None;
+        if version >= 3 {
+            let palette_len = u16::from_le_bytes(reader.array()?) as usize;
+            if palette_len > 0 {
+                let text = std::str::from_utf8(reader.take(palette_len)?)?;
+                palette = Some(GradientPalette::parse(text)?);
+            }
+        }
+
+        if version == CODE_VERSION && !reader.bytes.is_empty() {
+            bail!(
+                "There are {} bytes past the end of the scene",
+                reader.bytes.len()
+            );
+        }
+
+        let scene = Self {
+            version: SCENE_VERSION,
+            formula,
+            method,
+            params,
+            view: SceneView {
+                center: [re, im],
+                scale,
+                rotation,
+                shear,
+            },
+            coloring: SceneColoring {
+                lightness,
+                dropoff,
+                chaos,
+                palette,
+            },
+            root_colors: (color_count > 0).then_some(colors),
+        };
+        scene.validate()?;
+        Ok(scene)
+    }
+}
+
+///////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use proptest::prelude::*;
+
+    use super::*;
+    use crate::{IterMethod, Roots, ITER_METHODS};
+
+    const FORMULAS: [&str; 4] = [
+        "z^3 - 1",
+        "z^5 + 3z^3 + z + 3",
+        "5z^5 + 5z^3 - 69z^2 - 70z - 207",
+        "z^8 + 15z^4 - 16",
+    ];
+
+    fn lightness() -> impl Strategy<Value = LightnessCurve> {
+        prop_oneof![
+            Just(LightnessMode::Normal.into()),
+            Just(LightnessMode::Inverted.into()),
+            Just(LightnessMode::Parabola.into()),
+            Just(LightnessMode::InvertedParabola.into()),
+            (0.1f32..10.).prop_map(LightnessCurve::Gamma),
+            (0f32..=1., 0f32..=1.).prop_map(|(y0, y1)| {
+                LightnessCurve::Points(vec![[0., y0], [0.5, y1], [1., 1.]])
+            }),
+            (0f32..=1., -1f32..2.).prop_map(|(x1, y1)| LightnessCurve::Bezier([
+                x1,
+                y1,
+                1. - x1,
+                1. - y1
+            ])),
+            Just(LightnessCurve::parse("expr sqrt(v) * (1 - v / 2)").unwrap()),
+        ]
+    }
+
+    fn palette() -> impl Strategy<Value = Option<GradientPalette>> {
+        prop::option::of(Just(
+            GradientPalette::parse(
+                "gradient oklch\n0 #fff4c0\n1 black\ngradient\n0 white\n1 #204080",
+            )
+            .unwrap(),
+        ))
+    }
+
+    fn scene() -> impl Strategy<Value = Scene> {
+        let formula = prop::sample::select(&FORMULAS[..]);
+        let method = 0..ITER_METHODS.len() as u32;
+        (formula, method)
+            .prop_flat_map(|(formula, method)| {
+                let root_count = Roots::new(&Scene::new(formula).fz().unwrap())
+                    .unwrap()
+                    .roots
+                    .len();
+                let params = IterMethod::by_id(method)
+                    .unwrap()
+                    .params
+                    .iter()
+                    .map(|param| param.min..=param.max)
+                    .collect::<Vec<_>>();
+                let view = (
+                    [-10f32..10., -10f32..10.],
+                    1e-6f32..10.,
+                    -180f32..180.,
+                    -2f32..2.,
+                );
+                let coloring = (lightness(), 0f32..=1., any::<bool>(), palette());
+                let colors = prop::option::of(prop::collection::vec(
+                    (-360f32..360., 0f32..0.4),
+                    root_count,
+                ));
+                (Just(formula), Just(method), params, view, coloring, colors)
+            })
+            .prop_map(|(formula, method, params, view, coloring, colors)| {
+                let (center, scale, rotation, shear) = view;
+                let (lightness, dropoff, is_white, palette) = coloring;
+                Scene {
+                    method,
+                    params,
+                    view: SceneView {
+                        center,
+                        scale,
+                        rotation,
+                        shear,
+                    },
+                    coloring: SceneColoring {
+                        lightness,
+                        dropoff,
+                        chaos: match is_white {
+                            true => ChaosColor::White,
+                            false => ChaosColor::Black,
+                        },
+                        palette,
+                    },
+                    root_colors: colors.map(|colors| {
+                        colors
+                            .into_iter()
+                            .map(|(h, c)| OklchColor { h, c })
+                            .collect()
+                    }),
+                    ..Scene::new(formula)
+                }
+            })
+    }
+
+    proptest! {
+        #[test]
+        fn test_round_trip(scene in scene()) {
+            let code = scene.encode().unwrap();
+            prop_assert!(code.bytes().all(|b| b.is_ascii_alphanumeric() || b == b'-' || b == b'_'));
+            prop_assert_eq!(Scene::decode(&code).unwrap(), scene);
+        }
+
+        #[test]
+        fn test_newer_versions(scene in scene(), extra in prop::collection::vec(any::<u8>(), 1..16)) {
+            // A later version with fields of its own tacked on the end
+            let mut bytes = URL_SAFE_NO_PAD.decode(scene.encode().unwrap()).unwrap();
+            bytes[0] = CODE_VERSION + 1;
+            bytes.extend(extra);
+            prop_assert_eq!(Scene::decode(&URL_SAFE_NO_PAD.encode(bytes)).unwrap(), scene);
+        }
+
+        #[test]
+        fn test_garbage_fails_cleanly(code in "[A-Za-z0-9_-]{0,64}") {
+            let _ = Scene::decode(&code);
+        }
+    }
+
+    #[test]
+    fn test_rejects_bad_codes() -> Result<()> {
+        let code = Scene::new("z^3 - 1").encode()?;
+        let bytes = URL_SAFE_NO_PAD.decode(&code)?;
+
+        assert!(Scene::decode("not a code!").is_err());
+        assert!(Scene::decode(&URL_SAFE_NO_PAD.encode(&bytes[..bytes.len() - 1])).is_err());
+        assert!(Scene::decode(&URL_SAFE_NO_PAD.encode([bytes.as_slice(), &[0]].concat())).is_err());
+
+        let mut version_zero = bytes.clone();
+        version_zero[0] = 0;
+        assert!(Scene::decode(&URL_SAFE_NO_PAD.encode(version_zero)).is_err());
+
+        let mut bad_curve = URL_SAFE_NO_PAD.decode(encode_curve("gamma 2")?)?;
+        // The curve's last byte, before the empty palette's length
+        let len = bad_curve.len();
+        bad_curve[len - 3] = b'0';
+        assert!(Scene::decode(&URL_SAFE_NO_PAD.encode(bad_curve)).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_version_one() -> Result<()> {
+        // Version 1 codes end before the curve's length
+        let mut bytes = URL_SAFE_NO_PAD.decode(encode_curve("inverted")?)?;
+        bytes.truncate(bytes.len() - 4);
+        bytes[0] = 1;
+        let scene = Scene::decode(&URL_SAFE_NO_PAD.encode(bytes))?;
+        assert_eq!(scene.coloring.lightness, LightnessMode::Inverted.into());
+        Ok(())
+    }
+
+    #[test]
+    fn test_version_two() -> Result<()> {
+        // Version 2 codes end before the palette's length
+        let mut bytes = URL_SAFE_NO_PAD.decode(encode_curve("gamma 2")?)?;
+        bytes.truncate(bytes.len() - 2);
+        bytes[0] = 2;
+        let scene = Scene::decode(&URL_SAFE_NO_PAD.encode(bytes))?;
+        assert_eq!(scene.coloring.lightness, LightnessCurve::Gamma(2.));
+        assert_eq!(scene.coloring.palette, None);
+        Ok(())
+    }
+
+    fn encode_curve(curve: &str) -> Result<String> {
+        Scene {
+            coloring: SceneColoring {
+                lightness: LightnessCurve::parse(curve)?,
+                ..Default::default()
+            },
+            ..Scene::new("z^3 - 1")
+        }
+        .encode()
+    }
+}
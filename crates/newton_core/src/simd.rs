@@ -0,0 +1,326 @@
+use std::ops::{Add, Div, Mul, Sub};
+
+use num_complex::Complex32;
+use wide::{f32x8, CmpLe};
+
+use crate::{
+    calculate::{nearest_root, HalleysMethod, IterRoot, NewtonsMethod},
+    pixel_data::PixelData,
+    polynomial_term::PolynomialTerm,
+    Lerp, Polynomial, LOG_EPSILON,
+};
+
+///////////////////////////////////////////////////////////////////
+
+/// How many pixels are iterated together
+pub const LANES: usize = 8;
+
+/// A complex number per lane, stored as separate real and imaginary vectors. The arithmetic
+/// mirrors `num_complex` operation for operation, so every lane comes out bit-identical to the
+/// scalar path.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ComplexX8 {
+    pub re: f32x8,
+    pub im: f32x8,
+}
+
+/// A root finding method that can also step `LANES` pixels at once
+pub trait SimdIterRoot: IterRoot {
+    fn iter_root_simd(fz: &Polynomial, f0: ComplexX8, z: ComplexX8, params: &[f32]) -> ComplexX8;
+}
+
+// The SIMD convergence test is only a prefilter, with some slack so that the exact scalar
+// log10 test gets the final say
+const CONVERGED_NORM_BOUND: f32 = 1.01e-5;
+
+///////////////////////////////////////////////////////////////////
+
+/// Same as `calculate_row`, with `LANES` neighbouring pixels iterated in lockstep. Pixels that
+/// have converged are masked out, and the batch finishes once all of them have.
+pub fn calculate_row_simd<I: SimdIterRoot>(
+    fz: &Polynomial,
+    roots: &[Complex32],
+    mut z: Complex32,
+    step: Complex32,
+    params: &[f32],
+    pixel_data: &mut [PixelData],
+) {
+    pixel_data.chunks_mut(LANES).for_each(|pixels| {
+        // Accumulated one pixel at a time, to start from exactly the same z as the scalar path
+        let mut zs = [z; LANES];
+        for z_lane in &mut zs[..pixels.len()] {
+            *z_lane = z;
+            z += step;
+        }
+
+        let (z_final, fracs) = newtons_method_simd::<I>(fz, zs, pixels.len(), params);
+        for (lane, pixel) in pixels.iter_mut().enumerate() {
+            let root_index = nearest_root(z_final[lane], roots);
+            *pixel = (root_index, fracs[lane]).into();
+        }
+    });
+}
+
+/// Iterates the first `len` lanes of `zs`, returning where each ended up and its iteration
+/// count as a fraction of the maximum
+fn newtons_method_simd<I: SimdIterRoot>(
+    fz: &Polynomial,
+    zs: [Complex32; LANES],
+    len: usize,
+    params: &[f32],
+) -> ([Complex32; LANES], [f32; LANES]) {
+    let mut z = ComplexX8::from(zs);
+    let mut active: u32 = (1 << len) - 1;
+    let mut counts = [0f32; LANES];
+    let mut prev_norms = [0f32; LANES];
+
+    let mut count = 0.;
+    while active != 0 && count < I::max_count() {
+        let f0 = eval_terms(fz.derivative_terms(0), z);
+        let norm = f0.norm_sqr();
+        let norms = norm.to_array();
+
+        let near_mask = norm.cmp_le(f32x8::splat(CONVERGED_NORM_BOUND));
+        let mut near = active & near_mask.move_mask() as u32;
+        while near != 0 {
+            let lane = near.trailing_zeros() as usize;
+            near &= near - 1;
+
+            let log_norm = norms[lane].log10();
+            if log_norm <= LOG_EPSILON {
+                let prev_log_norm = match count == 0. {
+                    true => LOG_EPSILON,
+                    false => prev_norms[lane].log10(),
+                };
+                counts[lane] = count + LOG_EPSILON.ilerp_clamped(prev_log_norm, log_norm);
+                active &= !(1 << lane);
+            }
+        }
+
+        let z_next = I::iter_root_simd(fz, f0, z, params);
+        z = z_next.select(lane_mask(active), z);
+
+        prev_norms = norms;
+        count += 1.;
+    }
+
+    for (lane, lane_count) in counts.iter_mut().enumerate() {
+        if active & (1 << lane) != 0 {
+            *lane_count = count;
+        }
+    }
+
+    (z.into(), counts.map(|count| count / I::max_count()))
+}
+
+fn lane_mask(bits: u32) -> f32x8 {
+    let on = f32::from_bits(u32::MAX);
+    f32x8::new(std::array::from_fn(|lane| match bits & (1 << lane) {
+        0 => 0.,
+        _ => on,
+    }))
+}
+
+/// Sums C*z^p polynomial terms, in the same order as `Polynomial::eval_terms`
+fn eval_terms(terms: &[PolynomialTerm<f32>], z: ComplexX8) -> ComplexX8 {
+    terms.iter().fold(ComplexX8::default(), |sum, term| {
+        sum + z.powi(term.power).scale(term.coefficient)
+    })
+}
+
+///////////////////////////////////////////////////////////////////
+
+impl ComplexX8 {
+    pub fn splat(z: Complex32) -> Self {
+        Self {
+            re: f32x8::splat(z.re),
+            im: f32x8::splat(z.im),
+        }
+    }
+
+    pub fn norm_sqr(self) -> f32x8 {
+        self.re * self.re + self.im * self.im
+    }
+
+    pub fn scale(self, t: f32) -> Self {
+        let t = f32x8::splat(t);
+        Self {
+            re: t * self.re,
+            im: t * self.im,
+        }
+    }
+
+    fn inv(self) -> Self {
+        let norm_sqr = self.norm_sqr();
+        Self {
+            re: self.re / norm_sqr,
+            im: -self.im / norm_sqr,
+        }
+    }
+
+    /// Exponentiation by squaring, step for step with `Complex32::powi`
+    pub fn powi(self, exp: i32) -> Self {
+        if exp < 0 {
+            return self.inv().powu(exp.unsigned_abs());
+        }
+        self.powu(exp as u32)
+    }
+
+    fn powu(self, mut exp: u32) -> Self {
+        if exp == 0 {
+            return Self::splat(Complex32::new(1., 0.));
+        }
+
+        let mut base = self;
+        while exp & 1 == 0 {
+            base = base * base;
+            exp >>= 1;
+        }
+        if exp == 1 {
+            return base;
+        }
+
+        let mut acc = base;
+        while exp > 1 {
+            exp >>= 1;
+            base = base * base;
+            if exp & 1 == 1 {
+                acc = acc * base;
+            }
+        }
+        acc
+    }
+
+    /// Takes lanes from self where `mask` is set, and from `other` elsewhere
+    pub fn select(self, mask: f32x8, other: Self) -> Self {
+        Self {
+            re: mask.blend(self.re, other.re),
+            im: mask.blend(self.im, other.im),
+        }
+    }
+}
+
+impl Add for ComplexX8 {
+    type Output = Self;
+
+    fn add(self, other: Self) -> Self {
+        Self {
+            re: self.re + other.re,
+            im: self.im + other.im,
+        }
+    }
+}
+
+impl Sub for ComplexX8 {
+    type Output = Self;
+
+    fn sub(self, other: Self) -> Self {
+        Self {
+            re: self.re - other.re,
+            im: self.im - other.im,
+        }
+    }
+}
+
+impl Mul for ComplexX8 {
+    type Output = Self;
+
+    fn mul(self, other: Self) -> Self {
+        Self {
+            re: self.re * other.re - self.im * other.im,
+            im: self.re * other.im + self.im * other.re,
+        }
+    }
+}
+
+impl Div for ComplexX8 {
+    type Output = Self;
+
+    fn div(self, other: Self) -> Self {
+        let norm_sqr = other.norm_sqr();
+        let re = self.re * other.re + self.im * other.im;
+        let im = self.im * other.re - self.re * other.im;
+        Self {
+            re: re / norm_sqr,
+            im: im / norm_sqr,
+        }
+    }
+}
+
+impl From<[Complex32; LANES]> for ComplexX8 {
+    fn from(zs: [Complex32; LANES]) -> Self {
+        Self {
+            re: f32x8::new(zs.map(|z| z.re)),
+            im: f32x8::new(zs.map(|z| z.im)),
+        }
+    }
+}
+
+impl From<ComplexX8> for [Complex32; LANES] {
+    fn from(z: ComplexX8) -> Self {
+        let (re, im) = (z.re.to_array(), z.im.to_array());
+        std::array::from_fn(|lane| Complex32::new(re[lane], im[lane]))
+    }
+}
+
+///////////////////////////////////////////////////////////////////
+
+impl SimdIterRoot for NewtonsMethod {
+    fn iter_root_simd(fz: &Polynomial, f0: ComplexX8, z: ComplexX8, _params: &[f32]) -> ComplexX8 {
+        let f1 = eval_terms(fz.derivative_terms(1), z);
+        z - f0 / f1
+    }
+}
+
+impl SimdIterRoot for HalleysMethod {
+    fn iter_root_simd(fz: &Polynomial, f0: ComplexX8, z: ComplexX8, _params: &[f32]) -> ComplexX8 {
+        let f1 = eval_terms(fz.derivative_terms(1), z);
+        let f2 = eval_terms(fz.derivative_terms(2), z);
+        z - f0.scale(2.) * f1 / (f1.scale(2.) * f1 - f0 * f2)
+    }
+}
+
+///////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{calculate::calculate_row, Roots};
+
+    fn assert_matches_scalar<I: SimdIterRoot>(formula: &str, params: &[f32]) {
+        let fz = Polynomial::parse(formula).unwrap();
+        let roots = Roots::new(&fz).unwrap().roots;
+        let z = Complex32::new(-1.5, 0.9);
+        let step = Complex32::new(0.013, -0.004);
+
+        // Not a multiple of LANES, so the last batch is partly masked out from the start
+        let mut scalar = vec![PixelData::default(); 203];
+        let mut simd = scalar.clone();
+        calculate_row::<f32, I>(&fz, &roots, z, step, params, &mut scalar);
+        calculate_row_simd::<I>(&fz, &roots, z, step, params, &mut simd);
+        assert_eq!(simd, scalar);
+    }
+
+    #[test]
+    fn test_newton_matches_scalar() {
+        assert_matches_scalar::<NewtonsMethod>("z^5 + 3z^3 + z + 3", &[]);
+        assert_matches_scalar::<NewtonsMethod>("z^3 - 2z + 2", &[]);
+        // A double root at 1, which Newton's method only creeps up on
+        assert_matches_scalar::<NewtonsMethod>("z^3 - 3z + 2", &[]);
+    }
+
+    #[test]
+    fn test_halley_matches_scalar() {
+        assert_matches_scalar::<HalleysMethod>("z^5 + 3z^3 + z + 3", &[]);
+        assert_matches_scalar::<HalleysMethod>("z^8 + 15z^4 - 16", &[]);
+    }
+
+    #[test]
+    fn test_powi() {
+        let z = Complex32::new(0.7, -1.3);
+        for exp in -3..=9 {
+            let zs: [Complex32; LANES] = ComplexX8::splat(z).powi(exp).into();
+            assert_eq!(zs[0], z.powi(exp));
+        }
+    }
+}
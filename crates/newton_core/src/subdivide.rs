@@ -0,0 +1,305 @@
+use num_complex::Complex32;
+
+use crate::{
+    frame::render, IterMethod, Lerp, PackedPixel, PixelDataDetail, Polynomial, Viewport,
+    MAX_NEWTON_COUNT,
+};
+
+///////////////////////////////////////////////////////////////////
+
+/// Tuning for `render_frame_subdivided`
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SubdivideSpec {
+    /// Rectangles this many pixels across or smaller are always calculated in full
+    pub min_size: usize,
+    /// Border pixels only count as matching when their iteration counts fall in the same band
+    /// of this many iterations
+    pub band_iterations: f32,
+}
+
+/// How a subdivided render compares to calculating every pixel
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SubdivideReport {
+    /// Percentage of pixels with a different root or iteration band to the brute force render
+    pub mismatch_percent: f32,
+    /// Percentage of pixels that were actually iterated, rather than filled in
+    pub computed_percent: f32,
+}
+
+struct Subdivider<'a, P> {
+    fz: &'a Polynomial,
+    roots: &'a [Complex32],
+    method: &'a IterMethod,
+    params: &'a [f32],
+    viewport: &'a Viewport,
+    spec: &'a SubdivideSpec,
+    pixel_data: Vec<P>,
+    is_known: Vec<bool>,
+    computed: usize,
+}
+
+/// Half-open pixel bounds, x0..x1 by y0..y1
+#[derive(Clone, Copy)]
+struct Rect {
+    x0: usize,
+    y0: usize,
+    x1: usize,
+    y1: usize,
+}
+
+///////////////////////////////////////////////////////////////////
+
+impl Default for SubdivideSpec {
+    fn default() -> Self {
+        Self {
+            min_size: 8,
+            band_iterations: 1.,
+        }
+    }
+}
+
+///////////////////////////////////////////////////////////////////
+
+/// Renders the same frame as `render_frame`, using Mariani–Silver subdivision. Rectangles
+/// whose whole border converges to the same root, within the same iteration band, are filled
+/// in without iterating their insides. Anything else is split in half until it either matches
+/// or gets down to `min_size`. Works in either packed pixel layout.
+pub fn render_frame_subdivided<P: PackedPixel>(
+    fz: &Polynomial,
+    roots: &[Complex32],
+    method: &IterMethod,
+    params: &[f32],
+    viewport: &Viewport,
+    spec: &SubdivideSpec,
+) -> Vec<P> {
+    Subdivider::new(fz, roots, method, params, viewport, spec)
+        .render()
+        .pixel_data
+}
+
+/// Renders the frame both ways, to check how much accuracy subdivision gives up
+pub fn compare_subdivided<P: PackedPixel>(
+    fz: &Polynomial,
+    roots: &[Complex32],
+    method: &IterMethod,
+    params: &[f32],
+    viewport: &Viewport,
+    spec: &SubdivideSpec,
+) -> SubdivideReport {
+    let subdivided = Subdivider::<P>::new(fz, roots, method, params, viewport, spec).render();
+    let brute_force: Vec<P> = render(fz, roots, method, params, viewport);
+
+    let total = brute_force.len().max(1) as f32;
+    let mismatched = subdivided
+        .pixel_data
+        .iter()
+        .zip(&brute_force)
+        .filter(|&(&a, &b)| band_of(a, spec) != band_of(b, spec))
+        .count();
+
+    SubdivideReport {
+        mismatch_percent: 100. * mismatched as f32 / total,
+        computed_percent: 100. * subdivided.computed as f32 / total,
+    }
+}
+
+/// The root a pixel went to, and which iteration band it got there in
+fn band_of<P: PackedPixel>(pixel: P, spec: &SubdivideSpec) -> (usize, u32) {
+    let PixelDataDetail { root_index, frac } = pixel.into();
+    let band = (frac * MAX_NEWTON_COUNT / spec.band_iterations) as u32;
+    (root_index, band)
+}
+
+///////////////////////////////////////////////////////////////////
+
+impl<'a, P: PackedPixel> Subdivider<'a, P> {
+    fn new(
+        fz: &'a Polynomial,
+        roots: &'a [Complex32],
+        method: &'a IterMethod,
+        params: &'a [f32],
+        viewport: &'a Viewport,
+        spec: &'a SubdivideSpec,
+    ) -> Self {
+        let len = viewport.width * viewport.height;
+        Self {
+            fz,
+            roots,
+            method,
+            params,
+            viewport,
+            spec,
+            pixel_data: vec![P::default(); len],
+            is_known: vec![false; len],
+            computed: 0,
+        }
+    }
+
+    fn render(mut self) -> Self {
+        if !self.pixel_data.is_empty() {
+            self.subdivide(Rect {
+                x0: 0,
+                y0: 0,
+                x1: self.viewport.width,
+                y1: self.viewport.height,
+            });
+        }
+        self
+    }
+
+    fn subdivide(&mut self, rect: Rect) {
+        let Rect { x0, y0, x1, y1 } = rect;
+        let (width, height) = (x1 - x0, y1 - y0);
+
+        // Anything 2 pixels across is all border, and can't be split any further
+        let min_size = self.spec.min_size.max(2);
+        if width <= min_size || height <= min_size {
+            (y0..y1).for_each(|y| self.calculate_run(x0, y, width, false));
+            return;
+        }
+
+        self.calculate_run(x0, y0, width, false);
+        self.calculate_run(x0, y1 - 1, width, false);
+        self.calculate_run(x0, y0, height, true);
+        self.calculate_run(x1 - 1, y0, height, true);
+
+        if self.is_border_uniform(rect) {
+            self.fill(rect);
+        } else if width >= height {
+            let mid = x0 + width / 2;
+            self.subdivide(Rect {
+                x1: mid + 1,
+                ..rect
+            });
+            self.subdivide(Rect { x0: mid, ..rect });
+        } else {
+            let mid = y0 + height / 2;
+            self.subdivide(Rect {
+                y1: mid + 1,
+                ..rect
+            });
+            self.subdivide(Rect { y0: mid, ..rect });
+        }
+    }
+
+    /// Iterates `len` pixels from (x, y), along a row or down a column. Pixels that are already
+    /// known keep their values, so shared borders come out the same whichever rectangle got
+    /// there first.
+    fn calculate_run(&mut self, x: usize, y: usize, len: usize, is_column: bool) {
+        let (stride, step) = match is_column {
+            true => (self.viewport.width, self.viewport.affine().dy),
+            false => (1, self.viewport.affine().dx),
+        };
+        let start = y * self.viewport.width + x;
+        let indices = (0..len).map(|i| start + i * stride);
+        if indices.clone().all(|index| self.is_known[index]) {
+            return;
+        }
+
+        let z = self.viewport.pixel_to_complex(x as f32, y as f32);
+        let mut run = vec![P::default(); len];
+        P::calculate_row(
+            self.method,
+            self.fz,
+            self.roots,
+            z,
+            step,
+            self.params,
+            &mut run,
+        );
+
+        for (index, pixel) in indices.zip(run) {
+            if !self.is_known[index] {
+                self.pixel_data[index] = pixel;
+                self.is_known[index] = true;
+                self.computed += 1;
+            }
+        }
+    }
+
+    fn is_border_uniform(&self, rect: Rect) -> bool {
+        let Rect { x0, y0, x1, y1 } = rect;
+        let width = self.viewport.width;
+        let band = band_of(self.pixel_data[y0 * width + x0], self.spec);
+
+        let rows = [y0, y1 - 1]
+            .into_iter()
+            .flat_map(|y| (x0..x1).map(move |x| (x, y)));
+        let columns = [x0, x1 - 1]
+            .into_iter()
+            .flat_map(|x| (y0..y1).map(move |y| (x, y)));
+        rows.chain(columns)
+            .all(|(x, y)| band_of(self.pixel_data[y * width + x], self.spec) == band)
+    }
+
+    /// Fills the inside of the rectangle, blending each row from its left border pixel to its
+    /// right one, so smooth shading carries on across the filled area
+    fn fill(&mut self, rect: Rect) {
+        let Rect { x0, y0, x1, y1 } = rect;
+        let width = self.viewport.width;
+
+        for y in y0 + 1..y1 - 1 {
+            let row = &mut self.pixel_data[y * width + x0..y * width + x1];
+            let left: PixelDataDetail = row[0].into();
+            let right: PixelDataDetail = row[row.len() - 1].into();
+
+            let span = (row.len() - 1) as f32;
+            for (i, pixel) in row.iter_mut().enumerate().skip(1).take(x1 - x0 - 2) {
+                let frac = (i as f32 / span).lerp(left.frac, right.frac);
+                *pixel = (left.root_index, frac).into();
+            }
+            self.is_known[y * width + x0..y * width + x1].fill(true);
+        }
+    }
+}
+
+///////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{render_frame, PixelData, PixelData32, Roots, ITER_METHODS};
+
+    #[test]
+    fn test_close_to_brute_force() -> anyhow::Result<()> {
+        let fz = Polynomial::parse("z^3 - 1")?;
+        let roots = Roots::new(&fz).unwrap().roots;
+        let viewport = Viewport::new(320, 240);
+
+        for method in &ITER_METHODS {
+            let params = method.default_params();
+            let spec = SubdivideSpec::default();
+            let report =
+                compare_subdivided::<PixelData>(&fz, &roots, method, &params, &viewport, &spec);
+            // Islands that never touch a border get missed, and some methods have plenty
+            assert!(report.mismatch_percent < 5., "{}: {report:?}", method.name);
+            assert!(report.computed_percent < 100.);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_small_rects_are_exact() -> anyhow::Result<()> {
+        // With nothing ever filled in, every pixel is iterated and nothing can mismatch
+        let fz = Polynomial::parse("z^5 + 3z^3 + z + 3")?;
+        let roots = Roots::new(&fz).unwrap().roots;
+        let method = IterMethod::by_id(0)?;
+        let params = method.default_params();
+        let viewport = Viewport::new(37, 23);
+        let spec = SubdivideSpec {
+            min_size: 64,
+            ..Default::default()
+        };
+
+        let subdivided: Vec<PixelData> =
+            render_frame_subdivided(&fz, &roots, method, &params, &viewport, &spec);
+        let brute_force = render_frame(&fz, &roots, method, &params, &viewport);
+        assert_eq!(subdivided, brute_force);
+
+        let report =
+            compare_subdivided::<PixelData32>(&fz, &roots, method, &params, &viewport, &spec);
+        assert_eq!(report.computed_percent, 100.);
+        Ok(())
+    }
+}
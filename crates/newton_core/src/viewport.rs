@@ -0,0 +1,156 @@
+use num_complex::Complex32;
+
+use crate::{CANVAS_SIZE, COMPLEX_WINDOW};
+
+///////////////////////////////////////////////////////////////////
+
+/// Maps the pixels of an image onto the complex plane. Pixel rows run in the direction of
+/// increasing imaginary part, and columns in the direction of increasing real part, before
+/// rotation is applied.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Viewport {
+    pub width: usize,
+    pub height: usize,
+    /// Complex coordinate at the middle of the image
+    pub center: Complex32,
+    /// Complex distance from the center to the nearest edge, so the shorter side of the image
+    /// always spans `2 * scale`, whatever the aspect ratio
+    pub scale: f32,
+    /// Rotation of the view about its center, in radians
+    pub rotation: f32,
+    /// Horizontal shear, applied before rotation. Each row is offset by `shear` times its
+    /// distance from the center row, so 0 leaves pixels square.
+    pub shear: f32,
+}
+
+/// A general affine map from pixel coordinates onto the complex plane:
+/// z = origin + x * dx + y * dy
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Affine {
+    pub origin: Complex32,
+    /// How far z moves from one pixel to the next along a row
+    pub dx: Complex32,
+    /// How far z moves from one row to the next
+    pub dy: Complex32,
+}
+
+///////////////////////////////////////////////////////////////////
+
+impl Viewport {
+    /// An unrotated view of the default window, centered on the origin
+    pub fn new(width: usize, height: usize) -> Self {
+        Self {
+            width,
+            height,
+            center: Complex32::default(),
+            scale: COMPLEX_WINDOW,
+            rotation: 0.,
+            shear: 0.,
+        }
+    }
+
+    pub fn units_per_pixel(&self) -> f32 {
+        2. * self.scale / self.width.min(self.height) as f32
+    }
+
+    pub fn affine(&self) -> Affine {
+        let rotation = Complex32::from_polar(self.units_per_pixel(), self.rotation);
+        let dx = rotation;
+        let dy = rotation * Complex32::new(self.shear, 1.);
+        let half_size = Complex32::new(self.width as f32 / 2., self.height as f32 / 2.);
+        Affine {
+            origin: self.center - half_size.re * dx - half_size.im * dy,
+            dx,
+            dy,
+        }
+    }
+
+    /// How far z moves from one pixel to the next along a row
+    pub fn pixel_step(&self) -> Complex32 {
+        self.affine().dx
+    }
+
+    /// Pixel coordinates may be fractional, eg. to find where a mouse cursor is pointing
+    pub fn pixel_to_complex(&self, x: f32, y: f32) -> Complex32 {
+        self.affine().to_complex(x, y)
+    }
+
+    pub fn complex_to_pixel(&self, z: Complex32) -> (f32, f32) {
+        self.affine().to_pixel(z)
+    }
+
+    /// The z of the first pixel in `row`
+    pub fn row_start(&self, row: usize) -> Complex32 {
+        self.pixel_to_complex(0., row as f32)
+    }
+}
+
+impl Default for Viewport {
+    fn default() -> Self {
+        Self::new(CANVAS_SIZE, CANVAS_SIZE)
+    }
+}
+
+///////////////////////////////////////////////////////////////////
+
+impl Affine {
+    pub fn to_complex(&self, x: f32, y: f32) -> Complex32 {
+        self.origin + x * self.dx + y * self.dy
+    }
+
+    /// Inverts `to_complex`, by solving the 2x2 real system for x and y
+    pub fn to_pixel(&self, z: Complex32) -> (f32, f32) {
+        let w = z - self.origin;
+        let (a, b) = (self.dx, self.dy);
+        let det = a.re * b.im - a.im * b.re;
+        let x = (w.re * b.im - w.im * b.re) / det;
+        let y = (a.re * w.im - a.im * w.re) / det;
+        (x, y)
+    }
+}
+
+///////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_default_window() {
+        let viewport = Viewport::default();
+        let corner = Complex32::new(-COMPLEX_WINDOW, -COMPLEX_WINDOW);
+        assert_eq!(viewport.row_start(0), corner);
+        assert_eq!(
+            viewport.pixel_to_complex(CANVAS_SIZE as f32, CANVAS_SIZE as f32),
+            -corner
+        );
+    }
+
+    #[test]
+    fn test_aspect_ratio() {
+        // The shorter side spans the window, and the longer side extends past it
+        let viewport = Viewport::new(200, 100);
+        assert_eq!(viewport.pixel_to_complex(100., 0.).im, -COMPLEX_WINDOW);
+        assert_eq!(viewport.row_start(50).re, -2. * COMPLEX_WINDOW);
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let viewport = Viewport {
+            center: Complex32::new(0.3, -1.2),
+            scale: 0.25,
+            rotation: 1.1,
+            shear: 0.4,
+            ..Viewport::new(640, 480)
+        };
+
+        // Rotation and shear happen about the center, so it stays put
+        let center = viewport.pixel_to_complex(320., 240.);
+        assert!((center - viewport.center).norm() < 1e-6);
+
+        for (x, y) in [(0., 0.), (639., 17.5), (100., 479.)] {
+            let (px, py) = viewport.complex_to_pixel(viewport.pixel_to_complex(x, y));
+            assert!((px - x).abs() < 1e-2 && (py - y).abs() < 1e-2);
+        }
+    }
+}
@@ -1,12 +1,16 @@
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 use wasm_bindgen::prelude::*;
 
 #[wasm_bindgen(typescript_custom_section)]
 const TS_APPEND: &'static str = r#"
 
-import { Complex, OklchColor, Transform } from '@/app/(wasm-wrapper)/structs';
+import { Complex, CriticalOrbit, IterMethod, OklchColor, OrbitStep, Point, Scene, Viewport } from '@/app/(wasm-wrapper)/structs';
 
+type ComplexArray = Complex[];
 type OklchColorArray = OklchColor[];
+type IterMethodArray = IterMethod[];
+type OrbitStepArray = OrbitStep[];
+type CriticalOrbitArray = CriticalOrbit[];
 
 "#;
 
@@ -15,23 +19,41 @@ extern "C" {
     #[wasm_bindgen(typescript_type = "Complex")]
     pub type JsComplex;
 
+    #[wasm_bindgen(typescript_type = "ComplexArray")]
+    pub type JsComplexArray;
+
     #[wasm_bindgen(typescript_type = "OklchColor")]
     pub type JsOklchColor;
 
     #[wasm_bindgen(typescript_type = "OklchColorArray")]
     pub type JsOklchColorArray;
 
-    #[wasm_bindgen(typescript_type = "Transform")]
-    pub type JsTransform;
+    #[wasm_bindgen(typescript_type = "Viewport")]
+    pub type JsViewport;
+
+    #[wasm_bindgen(typescript_type = "Point")]
+    pub type JsPoint;
+
+    #[wasm_bindgen(typescript_type = "Scene")]
+    pub type JsScene;
+
+    #[wasm_bindgen(typescript_type = "IterMethodArray")]
+    pub type JsIterMethodArray;
+
+    #[wasm_bindgen(typescript_type = "OrbitStepArray")]
+    pub type JsOrbitStepArray;
+
+    #[wasm_bindgen(typescript_type = "CriticalOrbitArray")]
+    pub type JsCriticalOrbitArray;
 
     #[wasm_bindgen(js_name = "__newComplex")]
     pub fn new_complex(re: f32, im: f32) -> JsComplex;
 
+    #[wasm_bindgen(js_name = "__newPoint")]
+    pub fn new_point(x: f32, y: f32) -> JsPoint;
+
     #[wasm_bindgen(js_name = "__newOklchColor")]
     pub fn new_oklch_color(h: f32, c: f32) -> JsOklchColor;
-
-    #[wasm_bindgen(js_name = "__newTransform")]
-    pub fn new_transform(scale: f32, x: f32, y: f32) -> JsTransform;
 }
 
 ///////////////////////////////////////////////////////////////////
@@ -54,6 +76,27 @@ impl From<num_complex::Complex32> for JsComplex {
     }
 }
 
+#[derive(Deserialize, Serialize, Clone, Copy)]
+pub struct Complex {
+    pub re: f32,
+    pub im: f32,
+}
+
+impl From<Complex> for num_complex::Complex32 {
+    fn from(value: Complex) -> Self {
+        Self::new(value.re, value.im)
+    }
+}
+
+impl From<num_complex::Complex32> for Complex {
+    fn from(value: num_complex::Complex32) -> Self {
+        Self {
+            re: value.re,
+            im: value.im,
+        }
+    }
+}
+
 ///////////////////////////////////////////////////////////////////
 
 impl From<newton_core::OklchColor> for JsOklchColor {
@@ -65,21 +108,26 @@ impl From<newton_core::OklchColor> for JsOklchColor {
 ///////////////////////////////////////////////////////////////////
 
 #[derive(Deserialize)]
-pub struct Transform {
+pub struct Viewport {
+    pub width: usize,
+    pub height: usize,
+    pub center: Complex,
     pub scale: f32,
-    pub translate: Point,
-}
-
-#[derive(Deserialize, Clone, Copy)]
-pub struct Point {
-    pub x: f32,
-    pub y: f32,
+    #[serde(default)]
+    pub rotation: f32,
+    #[serde(default)]
+    pub shear: f32,
 }
 
-///////////////////////////////////////////////////////////////////
-
-impl From<Transform> for JsTransform {
-    fn from(value: Transform) -> Self {
-        new_transform(value.scale, value.translate.x, value.translate.y)
+impl From<Viewport> for newton_core::Viewport {
+    fn from(value: Viewport) -> Self {
+        Self {
+            width: value.width,
+            height: value.height,
+            center: value.center.into(),
+            scale: value.scale,
+            rotation: value.rotation,
+            shear: value.shear,
+        }
     }
 }
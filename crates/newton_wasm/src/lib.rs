@@ -1,16 +1,25 @@
 mod js_imports;
+mod methods;
 mod pixel_data_buffer;
 mod polynomial;
 mod render;
 mod roots;
 mod scale_row;
+mod scene;
+mod session;
+mod threads;
+mod viewport;
 
 use wasm_bindgen::prelude::*;
 
+#[cfg(all(feature = "threads", target_arch = "wasm32"))]
+pub use threads::init_thread_pool;
+
 ///////////////////////////////////////////////////////////////////
 
-// SAFETY: This application is single threaded, so using AssumeSingleThreaded is allowed.
-#[cfg(target_arch = "wasm32")]
+// SAFETY: Without the `threads` feature this application is single threaded, so using
+// AssumeSingleThreaded is allowed. The threaded build keeps the default, thread safe allocator.
+#[cfg(all(target_arch = "wasm32", not(feature = "threads")))]
 #[global_allocator]
 static ALLOCATOR: lol_alloc::AssumeSingleThreaded<lol_alloc::FreeListAllocator> =
     unsafe { lol_alloc::AssumeSingleThreaded::new(lol_alloc::FreeListAllocator::new()) };
@@ -34,9 +43,5 @@ pub fn canvas_size() -> usize {
 
 #[wasm_bindgen(js_name = __unitsPerPixelBase)]
 pub fn units_per_pixel_base() -> f32 {
-    units_per_pixel_scaled(newton_core::CANVAS_SIZE)
-}
-
-pub fn units_per_pixel_scaled(num_pixels: usize) -> f32 {
-    2.0 * newton_core::COMPLEX_WINDOW / num_pixels as f32
+    newton_core::Viewport::default().units_per_pixel()
 }
@@ -0,0 +1,150 @@
+use newton_core::{CriticalFate, CriticalOrbit, IterMethod, MethodParam, ITER_METHODS};
+use num_complex::Complex32;
+use serde::Serialize;
+use wasm_bindgen::prelude::*;
+
+use crate::{
+    js_imports::{
+        Complex, JsComplex, JsCriticalOrbitArray, JsIterMethodArray, JsOrbitStepArray, JsTryInto,
+    },
+    polynomial::Polynomial,
+    roots::Roots,
+};
+
+///////////////////////////////////////////////////////////////////
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct IterMethodInfo {
+    id: u32,
+    name: &'static str,
+    formula: &'static str,
+    derivative_order: u32,
+    params: Vec<MethodParamInfo>,
+}
+
+#[derive(Serialize)]
+struct MethodParamInfo {
+    name: &'static str,
+    default: f32,
+    min: f32,
+    max: f32,
+}
+
+#[derive(Serialize)]
+struct OrbitStep {
+    z: Complex,
+    residual: f32,
+}
+
+#[derive(Serialize)]
+struct CriticalOrbitInfo {
+    point: Complex,
+    #[serde(flatten)]
+    fate: CriticalFateInfo,
+}
+
+#[derive(Serialize)]
+#[serde(tag = "fate", rename_all = "camelCase")]
+enum CriticalFateInfo {
+    Root { root: usize },
+    Cycle { period: usize },
+    Undetermined,
+}
+
+///////////////////////////////////////////////////////////////////
+
+#[wasm_bindgen(js_name = __iterMethods)]
+pub fn iter_methods() -> Result<JsIterMethodArray, JsError> {
+    let methods = ITER_METHODS
+        .iter()
+        .map(IterMethodInfo::from)
+        .collect::<Vec<_>>();
+    Ok(serde_wasm_bindgen::to_value(&methods)?.unchecked_into())
+}
+
+/// The iterates a starting point walks through, along with |f(z)| at each of them
+#[wasm_bindgen(js_name = __orbit)]
+pub fn orbit(
+    fz: &Polynomial,
+    method: u32,
+    params: &[f32],
+    z0: JsComplex,
+) -> Result<JsOrbitStepArray, JsError> {
+    let method = IterMethod::by_id(method).map_err(|err| JsError::new(&err.to_string()))?;
+    let params = method
+        .resolve_params(params)
+        .map_err(|err| JsError::new(&err.to_string()))?;
+    let z0: Complex = z0.js_try_into()?;
+
+    let fz = &fz.poly;
+    let steps = newton_core::orbit(fz, method, z0.into(), &params)
+        .into_iter()
+        .map(|z: Complex32| OrbitStep {
+            z: z.into(),
+            residual: fz.f0(z).norm(),
+        })
+        .collect::<Vec<_>>();
+    Ok(serde_wasm_bindgen::to_value(&steps)?.unchecked_into())
+}
+
+/// Where each free critical point of the method ends up. Any that land in a cycle, rather
+/// than at a root, mean the polynomial has an attracting cycle.
+#[wasm_bindgen(js_name = __criticalOrbits)]
+pub fn critical_orbits(
+    fz: &Polynomial,
+    roots: &Roots,
+    method: u32,
+    params: &[f32],
+) -> Result<JsCriticalOrbitArray, JsError> {
+    let method = IterMethod::by_id(method).map_err(|err| JsError::new(&err.to_string()))?;
+    let params = method
+        .resolve_params(params)
+        .map_err(|err| JsError::new(&err.to_string()))?;
+
+    let orbits = newton_core::critical_orbits(&fz.poly, &roots.0.roots, method, &params)
+        .map_err(|err| JsError::new(&err.to_string()))?
+        .into_iter()
+        .map(CriticalOrbitInfo::from)
+        .collect::<Vec<_>>();
+    Ok(serde_wasm_bindgen::to_value(&orbits)?.unchecked_into())
+}
+
+///////////////////////////////////////////////////////////////////
+
+impl From<&IterMethod> for IterMethodInfo {
+    fn from(method: &IterMethod) -> Self {
+        Self {
+            id: method.id,
+            name: method.name,
+            formula: method.formula,
+            derivative_order: method.derivative_order,
+            params: method.params.iter().map(From::from).collect(),
+        }
+    }
+}
+
+impl From<&MethodParam> for MethodParamInfo {
+    fn from(param: &MethodParam) -> Self {
+        Self {
+            name: param.name,
+            default: param.default,
+            min: param.min,
+            max: param.max,
+        }
+    }
+}
+
+impl From<CriticalOrbit> for CriticalOrbitInfo {
+    fn from(orbit: CriticalOrbit) -> Self {
+        let fate = match orbit.fate {
+            CriticalFate::Root(root) => CriticalFateInfo::Root { root },
+            CriticalFate::Cycle { period } => CriticalFateInfo::Cycle { period },
+            CriticalFate::Undetermined => CriticalFateInfo::Undetermined,
+        };
+        Self {
+            point: orbit.point.into(),
+            fate,
+        }
+    }
+}
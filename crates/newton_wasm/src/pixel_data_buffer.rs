@@ -1,17 +1,150 @@
-use newton_core::PixelData;
+use std::sync::{Arc, Mutex};
+
+use newton_core::{
+    IterMethod, PassRow, PixelData, PixelData32, PixelRecord, Polynomial, ProgressiveRender,
+};
+use num_complex::Complex32;
 use wasm_bindgen::prelude::*;
 
 ///////////////////////////////////////////////////////////////////
 
 #[wasm_bindgen]
 pub struct PixelDataBuffer {
-    pub(crate) pixel_data: Vec<PixelData>,
+    pub(crate) pixels: BufferPixels,
+    pub(crate) width: usize,
+}
+
+/// Packed pixels, or full records for the shading modes that need them
+pub(crate) enum BufferPixels {
+    Packed(Vec<PixelData>),
+    Records(Vec<PixelRecord>),
+}
+
+/// A batch of a session's rows, and the pixel data calculated for them, in memory that the
+/// threaded build's calculation worker shares with the main thread. The session keeps one
+/// reference and the worker gets another, passed along as a plain number by `handle`. The
+/// main thread never waits on the lock, as browsers don't let it block.
+#[derive(Clone)]
+pub struct SharedPixelDataBuffer(Arc<SharedRows>);
+
+/// Everything needed to calculate a batch of rows, away from the session
+pub struct RowBatch {
+    pub fz: Polynomial,
+    pub roots: Vec<Complex32>,
+    pub method: &'static IterMethod,
+    pub params: Vec<f32>,
+    /// The session's progressive render, as of when it picked the rows
+    pub progressive: ProgressiveRender,
+    pub rows: Vec<usize>,
+    pub kind: PixelKind,
+}
+
+/// Which of the session's pixel layouts a batch is calculated in
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum PixelKind {
+    Packed,
+    Wide,
+    Records,
+}
+
+/// Samples for each row of a batch, packed in either width or as full records
+pub enum RowSamples {
+    Packed(Vec<PassRow<PixelData>>),
+    Wide(Vec<PassRow<PixelData32>>),
+    Records(Vec<PassRow<PixelRecord>>),
+}
+
+struct SharedRows {
+    batch: RowBatch,
+    samples: Mutex<Option<RowSamples>>,
 }
 
 ///////////////////////////////////////////////////////////////////
 
 impl PixelDataBuffer {
-    pub fn new(pixel_data: Vec<PixelData>) -> Self {
-        Self { pixel_data }
+    pub fn new(pixel_data: Vec<PixelData>, width: usize) -> Self {
+        Self {
+            pixels: BufferPixels::Packed(pixel_data),
+            width,
+        }
+    }
+
+    pub fn with_records(records: Vec<PixelRecord>, width: usize) -> Self {
+        Self {
+            pixels: BufferPixels::Records(records),
+            width,
+        }
+    }
+
+    pub fn height(&self) -> usize {
+        let len = match &self.pixels {
+            BufferPixels::Packed(pixel_data) => pixel_data.len(),
+            BufferPixels::Records(records) => records.len(),
+        };
+        len / self.width
+    }
+}
+
+impl SharedPixelDataBuffer {
+    pub fn new(batch: RowBatch) -> Self {
+        Self(Arc::new(SharedRows {
+            batch,
+            samples: Mutex::new(None),
+        }))
+    }
+
+    /// Another reference to the buffer, as a number that can be posted to a worker sharing
+    /// this module's memory. Each handle must be picked up by `from_handle` exactly once, or
+    /// the buffer is never freed.
+    pub fn handle(&self) -> usize {
+        Arc::into_raw(Arc::clone(&self.0)) as usize
+    }
+
+    /// # Safety
+    ///
+    /// `handle` must have come from `handle`, and not been picked up before
+    pub unsafe fn from_handle(handle: usize) -> Self {
+        Self(Arc::from_raw(handle as *const SharedRows))
+    }
+
+    /// Iterates the batch's samples. With the `threads` feature the rows are spread across the
+    /// worker pool, which blocks, so this mustn't be called from the browser's main thread.
+    pub fn calculate(&self) {
+        let RowBatch {
+            fz,
+            roots,
+            method,
+            params,
+            progressive,
+            rows,
+            kind,
+        } = &self.0.batch;
+        let samples = match kind {
+            PixelKind::Packed => {
+                RowSamples::Packed(progressive.calculate_rows(fz, roots, method, params, rows))
+            }
+            PixelKind::Wide => {
+                RowSamples::Wide(progressive.calculate_rows(fz, roots, method, params, rows))
+            }
+            PixelKind::Records => {
+                RowSamples::Records(progressive.calculate_rows(fz, roots, method, params, rows))
+            }
+        };
+        if let Ok(mut shared) = self.0.samples.lock() {
+            *shared = Some(samples);
+        }
+    }
+
+    /// Whether the samples are in, without waiting on the lock
+    pub fn is_calculated(&self) -> bool {
+        self.0
+            .samples
+            .try_lock()
+            .is_ok_and(|samples| samples.is_some())
+    }
+
+    /// Takes the samples once they're in, without waiting on the lock
+    pub fn take_samples(&self) -> Option<RowSamples> {
+        self.0.samples.try_lock().ok()?.take()
     }
 }
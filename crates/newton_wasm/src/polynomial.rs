@@ -1,6 +1,9 @@
 use wasm_bindgen::prelude::*;
 
-use newton_core::Polynomial as P;
+use newton_core::{
+    random_polynomial, superattracting_cycle, CoefficientKind, CycleSpec, Polynomial as P,
+    RandomSpec, RootConstraint,
+};
 
 use crate::{js_imports::JsComplex, js_imports::JsTryInto};
 
@@ -22,6 +25,57 @@ impl Polynomial {
         })
     }
 
+    /// Builds a polynomial whose Newton map has a superattracting cycle through `points`, in order.
+    /// `leading` holds the `degree - points.length` highest coefficients, highest power first.
+    #[wasm_bindgen(js_name = withSuperattractingCycle)]
+    pub fn with_superattracting_cycle(
+        points: &[f32],
+        degree: usize,
+        leading: &[f32],
+    ) -> Result<Polynomial, JsError> {
+        let spec = CycleSpec {
+            points: points.to_vec(),
+            degree,
+            leading: leading.to_vec(),
+        };
+        Ok(Self {
+            poly: superattracting_cycle(&spec).map_err(|err| JsError::new(&err.to_string()))?,
+        })
+    }
+
+    /// Draws a random polynomial with integer coefficients. The same seed always gives the
+    /// same polynomial, so interesting finds can be reproduced.
+    pub fn random(seed: u32) -> Result<Polynomial, JsError> {
+        Self::from_spec(&RandomSpec::default(), seed)
+    }
+
+    /// Like `random`, with the degree range, coefficient kind (0 for integers, 1 for floats, 2
+    /// for complex, which can't be rendered), coefficient magnitude, density of the middle terms,
+    /// and whether every root must lie inside the unit disk
+    #[wasm_bindgen(js_name = randomWithSpec)]
+    pub fn random_with_spec(
+        seed: u32,
+        min_degree: usize,
+        max_degree: usize,
+        kind: u32,
+        magnitude: f32,
+        density: f32,
+        inside_unit_disk: bool,
+    ) -> Result<Polynomial, JsError> {
+        let spec = RandomSpec {
+            degree: min_degree..=max_degree,
+            kind: CoefficientKind::try_from(kind).map_err(|err| JsError::new(&err.to_string()))?,
+            magnitude,
+            density,
+            constraint: inside_unit_disk.then_some(RootConstraint::InsideUnitDisk),
+        };
+        Self::from_spec(&spec, seed)
+    }
+
+    pub fn formula(&self) -> String {
+        self.poly.to_string()
+    }
+
     pub fn eval_f0(&self, z: JsComplex) -> Result<JsComplex, JsError> {
         Ok(self.poly.f0(z.js_try_into()?).into())
     }
@@ -30,3 +84,13 @@ impl Polynomial {
         Ok(self.poly.f1(z.js_try_into()?).into())
     }
 }
+
+impl Polynomial {
+    fn from_spec(spec: &RandomSpec, seed: u32) -> Result<Polynomial, JsError> {
+        let random =
+            random_polynomial(spec, seed as u64).map_err(|err| JsError::new(&err.to_string()))?;
+        Ok(Self {
+            poly: P::try_from(&random.fz).map_err(|err| JsError::new(&err.to_string()))?,
+        })
+    }
+}
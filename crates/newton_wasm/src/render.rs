@@ -1,100 +1,172 @@
-use newton_core::{calc_luminance_max, pixel_color, PixelData};
+use newton_core::{
+    calc_luminance_max, pixel_color, record_color, CalculatedPixel, IterMethod, LightnessCurve,
+    LightnessMode, OklchColor, PixelData, PixelRecord, ShadingMode,
+};
 use num_complex::Complex32;
 use wasm_bindgen::prelude::*;
 
 use crate::{
-    canvas_size, complex_window,
-    js_imports::{JsTransform, JsTryInto, Transform},
-    pixel_data_buffer::PixelDataBuffer,
+    js_imports::{JsTryInto, JsViewport, Viewport},
+    pixel_data_buffer::{BufferPixels, PixelDataBuffer},
     polynomial::Polynomial,
     roots::Roots,
     scale_row::write_scaled_block,
-    units_per_pixel_base, units_per_pixel_scaled,
+    threads::for_each_chunk,
 };
 
 ///////////////////////////////////////////////////////////////////
 
 #[wasm_bindgen(js_name = __newImagePixelDataBuffer)]
-pub fn new_image_pixel_data_buffer() -> PixelDataBuffer {
-    let pixel_data = vec![PixelData::default(); canvas_size() * canvas_size()];
-    PixelDataBuffer::new(pixel_data)
+pub fn new_image_pixel_data_buffer(width: usize, height: usize) -> PixelDataBuffer {
+    let pixel_data = vec![PixelData::default(); width * height];
+    PixelDataBuffer::new(pixel_data, width)
+}
+
+/// Like `__newImagePixelDataBuffer`, holding full records for rows from `__calculateRecordRows`
+#[wasm_bindgen(js_name = __newImageRecordBuffer)]
+pub fn new_image_record_buffer(width: usize, height: usize) -> PixelDataBuffer {
+    let records = vec![PixelRecord::default(); width * height];
+    PixelDataBuffer::with_records(records, width)
 }
 
+/// Calculates every `render_scale`th pixel of the row, for fast, low resolution previews
 #[wasm_bindgen(js_name = __calculateRow)]
 pub fn calculate_row(
     fz: &Polynomial,
     roots: &Roots,
-    affine_transform: JsTransform,
+    viewport: JsViewport,
     method: u32,
+    params: &[f32],
     render_scale: usize,
     row: usize,
 ) -> Result<PixelDataBuffer, JsError> {
-    let num_pixels = canvas_size() / render_scale;
-    let mut pixel_data = vec![PixelData::default(); num_pixels];
-
-    let affine_transform: Transform = affine_transform.js_try_into()?;
-    let (z, units_per_pixel_scaled) = calculate_z_start(affine_transform.scale, row, num_pixels);
-
-    let fz = &fz.poly;
-    let roots = &roots.0.roots;
-    let z = z + Complex32::new(affine_transform.translate.x, affine_transform.translate.y);
+    calculate_rows(fz, roots, viewport, method, params, render_scale, row, 1)
+}
 
-    calculate_row_method(
-        method,
+/// Like `__calculateRow`, for `row_count` rows spaced `render_scale` apart, starting at `row`.
+/// In the threaded build the rows are calculated in parallel, one per worker.
+#[wasm_bindgen(js_name = __calculateRows)]
+#[allow(clippy::too_many_arguments)]
+pub fn calculate_rows(
+    fz: &Polynomial,
+    roots: &Roots,
+    viewport: JsViewport,
+    method: u32,
+    params: &[f32],
+    render_scale: usize,
+    row: usize,
+    row_count: usize,
+) -> Result<PixelDataBuffer, JsError> {
+    let (pixel_data, width) = calculate_buffer_rows(
         fz,
         roots,
-        z,
-        units_per_pixel_scaled,
-        &mut pixel_data,
+        viewport,
+        method,
+        params,
+        render_scale,
+        row,
+        row_count,
     )?;
+    Ok(PixelDataBuffer::new(pixel_data, width))
+}
 
-    Ok(PixelDataBuffer::new(pixel_data))
+/// Like `__calculateRows`, keeping a full record per pixel for the shading modes that need one
+#[wasm_bindgen(js_name = __calculateRecordRows)]
+#[allow(clippy::too_many_arguments)]
+pub fn calculate_record_rows(
+    fz: &Polynomial,
+    roots: &Roots,
+    viewport: JsViewport,
+    method: u32,
+    params: &[f32],
+    render_scale: usize,
+    row: usize,
+    row_count: usize,
+) -> Result<PixelDataBuffer, JsError> {
+    let (records, width) = calculate_buffer_rows(
+        fz,
+        roots,
+        viewport,
+        method,
+        params,
+        render_scale,
+        row,
+        row_count,
+    )?;
+    Ok(PixelDataBuffer::with_records(records, width))
 }
 
-fn calculate_row_method(
+/// The pixels of the rows, and how many there are per row
+#[allow(clippy::too_many_arguments)]
+fn calculate_buffer_rows<P: CalculatedPixel>(
+    fz: &Polynomial,
+    roots: &Roots,
+    viewport: JsViewport,
     method: u32,
-    fz: &newton_core::Polynomial,
-    roots: &[Complex32],
-    z: Complex32,
-    units_per_pixel_scaled: f32,
-    pixel_data: &mut [PixelData],
-) -> Result<(), JsError> {
-    macro_rules! calc_row {
-        ($iter:ident, $fz:ident, $roots:ident, $z: ident, $upps: ident, $pdata: ident) => {
-            newton_core::calculate_row::<_, newton_core::calculate::$iter>(
-                $fz, $roots, $z, $upps, $pdata,
-            )
-        };
-    }
+    params: &[f32],
+    render_scale: usize,
+    row: usize,
+    row_count: usize,
+) -> Result<(Vec<P>, usize), JsError> {
+    let method = IterMethod::by_id(method).map_err(|err| JsError::new(&err.to_string()))?;
+    let params = method
+        .resolve_params(params)
+        .map_err(|err| JsError::new(&err.to_string()))?;
 
-    let upps = units_per_pixel_scaled;
-    match method {
-        0 => calc_row!(NewtonsMethod, fz, roots, z, upps, pixel_data),
-        1 => calc_row!(SchroedersMethod, fz, roots, z, upps, pixel_data),
-        2 => calc_row!(SchroedersMethod2, fz, roots, z, upps, pixel_data),
-        3 => calc_row!(HalleysMethod, fz, roots, z, upps, pixel_data),
-        4 => calc_row!(SteffensensMethod, fz, roots, z, upps, pixel_data),
-        _ => {
-            return Err(JsError::new(&format!(
-                "Invalid root iter method type: {method}"
-            )))
-        }
+    if roots.0.roots.len() > PixelData::MAX_ROOTS {
+        return Err(JsError::new(&format!(
+            "Rows can only be calculated for up to {} roots, use a session for more",
+            PixelData::MAX_ROOTS
+        )));
     }
 
-    Ok(())
+    let viewport: Viewport = viewport.js_try_into()?;
+    let viewport: newton_core::Viewport = viewport.into();
+
+    let num_pixels = viewport.width.div_ceil(render_scale);
+    let pixels = calculate_rows_into(
+        &fz.poly,
+        &roots.0.roots,
+        &viewport,
+        method,
+        &params,
+        render_scale,
+        row,
+        row_count,
+    );
+
+    Ok((pixels, num_pixels))
 }
 
-fn calculate_z_start(zoom: f32, row: usize, num_pixels: usize) -> (Complex32, f32) {
-    let upp_full = units_per_pixel_base();
-    let upp_local = units_per_pixel_scaled(num_pixels);
-    let upp_local = upp_local * zoom;
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn calculate_rows_into<P: CalculatedPixel>(
+    fz: &newton_core::Polynomial,
+    roots: &[Complex32],
+    viewport: &newton_core::Viewport,
+    method: &IterMethod,
+    params: &[f32],
+    render_scale: usize,
+    row: usize,
+    row_count: usize,
+) -> Vec<P> {
+    let num_pixels = viewport.width.div_ceil(render_scale);
+    let mut pixels = vec![P::default(); num_pixels * row_count];
+    if pixels.is_empty() {
+        return pixels;
+    }
 
-    let z_im = -complex_window() + upp_full * (row as f32);
-    let z_re = -complex_window();
+    let step = viewport.pixel_step() * render_scale as f32;
+    for_each_chunk(&mut pixels, num_pixels, |index, pixel_row| {
+        let z = viewport.row_start(row + index * render_scale);
+        P::calculate_row(method, fz, roots, z, step, params, pixel_row);
+    });
 
-    (Complex32::new(z_re * zoom, z_im * zoom), upp_local)
+    pixels
 }
 
+/// Writes a batch of rows from `__calculateRows` or `__calculateRecordRows` into the image's
+/// buffer, which has to hold the same kind of pixels, and draws them. `shading` needs records
+/// for anything but shading by iterations.
 #[wasm_bindgen(js_name = __renderRow)]
 #[allow(clippy::too_many_arguments)]
 pub fn render_row(
@@ -106,50 +178,52 @@ pub fn render_row(
     row: usize,
     dropoff: f32,
     lightness_mode: u32,
+    shading: u32,
     is_dark_non_convergence: bool,
 ) -> Result<(), JsValue> {
-    let pdb_row_offset = row * canvas_size();
-    let pdb_block_len = canvas_size() * render_scale;
-    let pdb_slice = &mut pdb.pixel_data[pdb_row_offset..pdb_row_offset + pdb_block_len];
+    check_row(pdb, row)?;
+    let width = pdb.width;
+    let block_rows = (render_scale * row_pdb.height()).min(pdb.height() - row);
+    let pdb_range = row * width..(row + block_rows) * width;
 
-    let mut canvas_bytes = vec![0; 4 * pdb_block_len];
-    let canvas_pixels = unsafe { canvas_bytes.align_to_mut::<[u8; 4]>().1 };
-
-    let luminance_max = calc_luminance_max(dropoff);
-    write_scaled_block(
-        canvas_pixels,
-        &row_pdb.pixel_data,
-        render_scale,
-        |input| {
-            pixel_color(
-                *input,
-                &roots.0.colors,
-                luminance_max,
-                dropoff,
-                lightness_mode.into(),
-                is_dark_non_convergence,
-            )
-        },
-        |output, pixel| output.copy_from_slice(pixel),
-    );
-
-    write_scaled_block(
-        pdb_slice,
-        &row_pdb.pixel_data,
-        render_scale,
-        |input| *input,
-        |output, input| *output = *input,
-    );
-
-    let image_data = web_sys::ImageData::new_with_u8_clamped_array(
-        wasm_bindgen::Clamped(&canvas_bytes),
-        canvas_size() as u32,
+    let coloring = RowColoring::new(
+        roots,
+        dropoff,
+        lightness_mode,
+        shading,
+        is_dark_non_convergence,
     )?;
-    ctx.put_image_data(&image_data, 0.0, row as f64)?;
-    Ok(())
+    let canvas_bytes = match (&mut pdb.pixels, &row_pdb.pixels) {
+        (BufferPixels::Packed(pixel_data), BufferPixels::Packed(input)) => {
+            coloring.check_packed()?;
+            scale_rows(
+                &mut pixel_data[pdb_range],
+                width,
+                input,
+                row_pdb.width,
+                render_scale,
+                |&pixel| coloring.pixel_color(pixel),
+            )
+        }
+        (BufferPixels::Records(records), BufferPixels::Records(input)) => scale_rows(
+            &mut records[pdb_range],
+            width,
+            input,
+            row_pdb.width,
+            render_scale,
+            |record| coloring.record_color(record),
+        ),
+        _ => {
+            return Err(JsError::new("The rows and the image need the same kind of pixels").into())
+        }
+    };
+    put_rows(ctx, &canvas_bytes, width, row)
 }
 
+/// Redraws a row of the image from its buffer, without recalculating. With records, any
+/// shading works.
 #[wasm_bindgen(js_name = __recolorRow)]
+#[allow(clippy::too_many_arguments)]
 pub fn recolor_row(
     ctx: &web_sys::CanvasRenderingContext2d,
     roots: &Roots,
@@ -157,37 +231,176 @@ pub fn recolor_row(
     row: usize,
     dropoff: f32,
     lightness_mode: u32,
+    shading: u32,
     is_dark_non_convergence: bool,
 ) -> Result<(), JsValue> {
-    let pdb_row_offset = row * canvas_size();
-    let pdb_block_len = canvas_size();
-    let pdb_slice = &pdb.pixel_data[pdb_row_offset..pdb_row_offset + pdb_block_len];
+    check_row(pdb, row)?;
+    let width = pdb.width;
+    let pdb_range = row * width..(row + 1) * width;
 
-    let mut canvas_bytes = vec![0; 4 * pdb_block_len];
+    let coloring = RowColoring::new(
+        roots,
+        dropoff,
+        lightness_mode,
+        shading,
+        is_dark_non_convergence,
+    )?;
+    let canvas_bytes: Vec<u8> = match &pdb.pixels {
+        BufferPixels::Packed(pixel_data) => {
+            coloring.check_packed()?;
+            pixel_data[pdb_range]
+                .iter()
+                .flat_map(|&pixel| coloring.pixel_color(pixel))
+                .collect()
+        }
+        BufferPixels::Records(records) => records[pdb_range]
+            .iter()
+            .flat_map(|record| coloring.record_color(record))
+            .collect(),
+    };
+    put_rows(ctx, &canvas_bytes, width, row)
+}
+
+/// `count` evenly spaced samples of a curve in `LightnessCurve`'s text format, from a
+/// convergence speed of 0 up to 1, for drawing it. Throws if the curve is invalid.
+#[wasm_bindgen(js_name = __sampleLightnessCurve)]
+pub fn sample_lightness_curve(curve: &str, count: usize) -> Result<Vec<f32>, JsError> {
+    let curve = LightnessCurve::parse(curve).map_err(|err| JsError::new(&err.to_string()))?;
+    let last = count.saturating_sub(1).max(1) as f32;
+    Ok((0..count)
+        .map(|i| curve.evaluate(i as f32 / last))
+        .collect())
+}
+
+/// Writes the input rows into `pdb_slice`, each blown up into a block of `render_scale` rows,
+/// the last one cropped, and returns them colored in as RGBA bytes
+fn scale_rows<P: Copy>(
+    pdb_slice: &mut [P],
+    width: usize,
+    input: &[P],
+    input_width: usize,
+    render_scale: usize,
+    color: impl Fn(&P) -> [u8; 4],
+) -> Vec<u8> {
+    let mut canvas_bytes = vec![0; 4 * pdb_slice.len()];
     let canvas_pixels = unsafe { canvas_bytes.align_to_mut::<[u8; 4]>().1 };
 
-    let luminance_max = calc_luminance_max(dropoff);
-    write_scaled_block(
-        canvas_pixels,
-        pdb_slice,
-        1,
-        |input| {
-            pixel_color(
-                *input,
-                &roots.0.colors,
-                luminance_max,
-                dropoff,
-                lightness_mode.into(),
-                is_dark_non_convergence,
-            )
-        },
-        |output, pixel| output.copy_from_slice(pixel),
-    );
+    let block_len = width * render_scale;
+    let input_rows = input.chunks(input_width);
+    let canvas_blocks = canvas_pixels.chunks_mut(block_len);
+    let pdb_blocks = pdb_slice.chunks_mut(block_len);
+    for ((input_row, canvas_block), pdb_block) in input_rows.zip(canvas_blocks).zip(pdb_blocks) {
+        write_scaled_block(
+            canvas_block,
+            width,
+            input_row,
+            render_scale,
+            &color,
+            |output, pixel| output.copy_from_slice(pixel),
+        );
 
+        write_scaled_block(
+            pdb_block,
+            width,
+            input_row,
+            render_scale,
+            |input| *input,
+            |output, input| *output = *input,
+        );
+    }
+    canvas_bytes
+}
+
+fn put_rows(
+    ctx: &web_sys::CanvasRenderingContext2d,
+    canvas_bytes: &[u8],
+    width: usize,
+    row: usize,
+) -> Result<(), JsValue> {
     let image_data = web_sys::ImageData::new_with_u8_clamped_array(
-        wasm_bindgen::Clamped(&canvas_bytes),
-        canvas_size() as u32,
+        wasm_bindgen::Clamped(canvas_bytes),
+        width as u32,
     )?;
-    ctx.put_image_data(&image_data, 0.0, row as f64)?;
-    Ok(())
+    ctx.put_image_data(&image_data, 0.0, row as f64)
+}
+
+/// Everything `__renderRow` and `__recolorRow` color pixels with
+struct RowColoring<'a> {
+    colors: &'a [OklchColor],
+    luminance_max: f32,
+    dropoff: f32,
+    lightness: LightnessCurve,
+    shading: ShadingMode,
+    is_dark_non_convergence: bool,
+}
+
+impl<'a> RowColoring<'a> {
+    fn new(
+        roots: &'a Roots,
+        dropoff: f32,
+        lightness_mode: u32,
+        shading: u32,
+        is_dark_non_convergence: bool,
+    ) -> Result<Self, JsError> {
+        let shading =
+            ShadingMode::try_from(shading).map_err(|err| JsError::new(&err.to_string()))?;
+        Ok(Self {
+            colors: &roots.0.colors,
+            luminance_max: calc_luminance_max(dropoff),
+            dropoff,
+            lightness: lightness_curve(lightness_mode)?,
+            shading,
+            is_dark_non_convergence,
+        })
+    }
+
+    /// Packed pixels only have the iteration count to shade by
+    fn check_packed(&self) -> Result<(), JsError> {
+        match self.shading.needs_records() {
+            true => Err(JsError::new(&format!(
+                "{:?} shading needs records",
+                self.shading
+            ))),
+            false => Ok(()),
+        }
+    }
+
+    fn pixel_color(&self, pixel: PixelData) -> [u8; 4] {
+        pixel_color(
+            pixel,
+            self.colors,
+            self.luminance_max,
+            self.dropoff,
+            &self.lightness,
+            self.is_dark_non_convergence,
+        )
+    }
+
+    fn record_color(&self, record: &PixelRecord) -> [u8; 4] {
+        record_color(
+            record,
+            self.colors,
+            self.luminance_max,
+            self.dropoff,
+            &self.lightness,
+            self.shading,
+            self.is_dark_non_convergence,
+        )
+    }
+}
+
+fn check_row(pdb: &PixelDataBuffer, row: usize) -> Result<(), JsError> {
+    let height = pdb.height();
+    match row < height {
+        true => Ok(()),
+        false => Err(JsError::new(&format!(
+            "Row {row} is outside the image, which is {height} rows high"
+        ))),
+    }
+}
+
+fn lightness_curve(lightness_mode: u32) -> Result<LightnessCurve, JsError> {
+    LightnessMode::try_from(lightness_mode)
+        .map(LightnessCurve::from)
+        .map_err(|err| JsError::new(&err.to_string()))
 }
@@ -1,9 +1,9 @@
-use newton_core::Roots as R;
+use newton_core::{ColorScheme, Roots as R, SchemeOptions};
 use wasm_bindgen::prelude::*;
 use web_sys::js_sys::Array;
 
 use crate::{
-    js_imports::JsComplex,
+    js_imports::{Complex, JsComplex, JsComplexArray},
     js_imports::{JsOklchColor, JsOklchColorArray, JsTryInto},
     polynomial::Polynomial,
 };
@@ -19,10 +19,9 @@ pub struct Roots(pub(crate) R);
 impl Roots {
     #[wasm_bindgen(constructor)]
     pub fn new(fz: &Polynomial) -> Result<Roots, JsError> {
-        match R::new(&fz.poly) {
-            Some(r) => Ok(Roots(r)),
-            None => Err(JsError::new("No roots found for polynomial")),
-        }
+        R::new(&fz.poly)
+            .map(Roots)
+            .map_err(|err| JsError::new(&err.to_string()))
     }
 
     #[wasm_bindgen]
@@ -53,3 +52,34 @@ impl Roots {
         Ok(())
     }
 }
+
+///////////////////////////////////////////////////////////////////
+
+/// Colors for the roots from a `ColorScheme` id, the same as the CLI's `--scheme` picks
+#[wasm_bindgen(js_name = __schemeColors)]
+pub fn scheme_colors(
+    roots: JsComplexArray,
+    scheme: u32,
+    hue_offset: f32,
+    chromaticity: f32,
+    static_hues: bool,
+) -> Result<Vec<JsOklchColor>, JsError> {
+    let scheme = ColorScheme::try_from(scheme).map_err(|err| JsError::new(&err.to_string()))?;
+    let roots: JsValue = roots.into();
+    let roots: Array = roots.into();
+    let roots = roots
+        .iter()
+        .map(|z| z.js_try_into().map(|z: Complex| z.into()))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let options = SchemeOptions {
+        hue_offset,
+        chromaticity,
+        static_hues,
+    };
+    Ok(scheme
+        .colors(&roots, &options)
+        .into_iter()
+        .map(Into::into)
+        .collect())
+}
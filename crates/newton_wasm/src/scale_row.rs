@@ -1,5 +1,8 @@
+/// Blows each input pixel up into a `scale` x `scale` square of the output. The output is
+/// `output_width` wide, and any squares hanging off its right or bottom edge are cropped.
 pub fn write_scaled_block<Out, In, TransIn, Trans, Write>(
     output: &mut [Out],
+    output_width: usize,
     input: &[In],
     scale: usize,
     transform: Trans,
@@ -8,19 +11,17 @@ pub fn write_scaled_block<Out, In, TransIn, Trans, Write>(
     Trans: Fn(&In) -> TransIn,
     Write: Fn(&mut Out, &TransIn),
 {
-    output
-        .chunks_mut(input.len() * scale)
-        .for_each(|output_row| {
-            output_row
-                .chunks_mut(scale)
-                .zip(input)
-                .for_each(|(output_chunk, in_unit)| {
-                    let transformed_input = transform(in_unit);
-                    output_chunk
-                        .iter_mut()
-                        .for_each(|out_pixel| write(out_pixel, &transformed_input));
-                });
-        });
+    output.chunks_mut(output_width).for_each(|output_row| {
+        output_row
+            .chunks_mut(scale)
+            .zip(input)
+            .for_each(|(output_chunk, in_unit)| {
+                let transformed_input = transform(in_unit);
+                output_chunk
+                    .iter_mut()
+                    .for_each(|out_pixel| write(out_pixel, &transformed_input));
+            });
+    });
 }
 
 ///////////////////////////////////////////////////////////////////
@@ -41,6 +42,7 @@ mod tests {
 
         write_scaled_block(
             &mut output,
+            input.len() * scale,
             &input,
             scale,
             |input| *input,
@@ -65,6 +67,7 @@ mod tests {
 
         write_scaled_block(
             output_slice,
+            input.len() * scale,
             input,
             scale,
             |input| *input,
@@ -93,6 +96,7 @@ mod tests {
 
         write_scaled_block(
             output_slice,
+            input.len() * scale,
             &input,
             scale,
             |input| [*input, *input, *input, *input],
@@ -107,4 +111,21 @@ mod tests {
             ]
         );
     }
+
+    #[test]
+    fn cropped_scale() {
+        let input = [0, 1, 2];
+        let mut output = vec![9; 5];
+
+        write_scaled_block(
+            &mut output,
+            5,
+            &input,
+            2,
+            |input| *input,
+            |output, input| *output = *input,
+        );
+
+        assert_eq!(output, [0, 0, 1, 1, 2]);
+    }
 }
@@ -0,0 +1,42 @@
+use newton_core::Scene;
+use wasm_bindgen::prelude::*;
+
+use crate::js_imports::{JsScene, JsTryInto};
+
+///////////////////////////////////////////////////////////////////
+
+/// Reads a scene file, checking that it can be rendered
+#[wasm_bindgen(js_name = __parseScene)]
+pub fn parse_scene(json: &str) -> Result<JsScene, JsError> {
+    let scene = Scene::from_json(json).map_err(|err| JsError::new(&err.to_string()))?;
+    Ok(serde_wasm_bindgen::to_value(&scene)?.unchecked_into())
+}
+
+/// Writes a scene file, in the same format the CLI reads
+#[wasm_bindgen(js_name = __sceneToJson)]
+pub fn scene_to_json(scene: JsScene) -> Result<String, JsError> {
+    let scene: Scene = scene.js_try_into()?;
+    scene
+        .to_json()
+        .map_err(|err| JsError::new(&err.to_string()))
+}
+
+/// A short, URL-safe code for the scene, for share links
+#[wasm_bindgen(js_name = __encodeScene)]
+pub fn encode_scene(scene: JsScene) -> Result<String, JsError> {
+    let scene: Scene = scene.js_try_into()?;
+    scene.encode().map_err(|err| JsError::new(&err.to_string()))
+}
+
+#[wasm_bindgen(js_name = __decodeScene)]
+pub fn decode_scene(code: &str) -> Result<JsScene, JsError> {
+    let scene = Scene::decode(code).map_err(|err| JsError::new(&err.to_string()))?;
+    Ok(serde_wasm_bindgen::to_value(&scene)?.unchecked_into())
+}
+
+/// The scene embedded in a PNG from `RenderSession.exportPng`
+#[wasm_bindgen(js_name = __pngScene)]
+pub fn png_scene(bytes: &[u8]) -> Result<JsScene, JsError> {
+    let scene = newton_core::png_scene(bytes).map_err(|err| JsError::new(&err.to_string()))?;
+    Ok(serde_wasm_bindgen::to_value(&scene)?.unchecked_into())
+}
@@ -0,0 +1,1081 @@
+use anyhow::{anyhow, bail, Result};
+use newton_core::{
+    calc_luminance_max, encode_png, pixel_color, record_color, ChaosColor, IterMethod,
+    LightnessCurve, LightnessMode, OklchColor, PackedPixel, PixelData, PixelData32,
+    PixelDataDetail, PixelRecord, Polynomial as P, ProgressiveRender, RawData, RawFormat,
+    Roots as R, SavedFrame, Scene, SceneColoring, SceneView, ShadingMode, Viewport as V,
+    SCENE_VERSION,
+};
+use wasm_bindgen::prelude::*;
+use web_sys::js_sys::Array;
+
+use crate::{
+    js_imports::{JsComplex, JsOklchColor, JsOklchColorArray, JsTryInto, JsViewport, Viewport},
+    pixel_data_buffer::{PixelKind, RowBatch, RowSamples, SharedPixelDataBuffer},
+};
+
+///////////////////////////////////////////////////////////////////
+
+/// Owns everything a render needs: the polynomial and its roots, the view, the method, the
+/// coloring, the pixel data and the colored image. Setters work out for themselves whether a
+/// change means recalculating, or only recoloring what's already been calculated.
+#[wasm_bindgen]
+pub struct RenderSession {
+    formula: String,
+    fz: P,
+    roots: R,
+    viewport: V,
+    method: &'static IterMethod,
+    params: Vec<f32>,
+    coloring: Coloring,
+    pixels: Pixels,
+    /// RGBA bytes, kept in step with `pixels` by `calculate_rows` and `recolor`
+    image: Vec<u8>,
+    progressive: ProgressiveRender,
+    /// Rows from `start_rows` that haven't been written back yet
+    pending: Option<SharedPixelDataBuffer>,
+    needs_recalculation: bool,
+    needs_recolor: bool,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+struct Coloring {
+    /// On the slider scale
+    dropoff: f32,
+    lightness_mode: LightnessMode,
+    /// Takes over from the mode while it's set
+    lightness_curve: Option<LightnessCurve>,
+    shading: ShadingMode,
+    is_dark_non_convergence: bool,
+}
+
+/// Packed pixels by default, twice as wide for polynomials with more roots than 16 bits can
+/// tell apart, or full records for shading modes that need them
+enum Pixels {
+    Packed(Vec<PixelData>),
+    Wide(Vec<PixelData32>),
+    Records(Vec<PixelRecord>),
+}
+
+// The first pass samples every 64th pixel in each direction
+const COARSEST_SCALE_FACTOR: u32 = 6;
+
+///////////////////////////////////////////////////////////////////
+
+#[wasm_bindgen]
+impl RenderSession {
+    #[wasm_bindgen(constructor)]
+    pub fn new(formula: &str, viewport: JsViewport) -> Result<RenderSession, JsError> {
+        let viewport: Viewport = viewport.js_try_into()?;
+        Self::with_formula(formula, viewport.into()).map_err(|err| JsError::new(&err.to_string()))
+    }
+
+    /// Picks up a frame from `save`, with the coloring it was saved with, ready to recolor
+    /// without calculating anything
+    pub fn load(bytes: &[u8]) -> Result<RenderSession, JsError> {
+        Self::from_saved(bytes).map_err(|err| JsError::new(&err.to_string()))
+    }
+
+    /// The finished frame, with its polynomial, roots, colors, view and method, as a compressed
+    /// binary file
+    #[wasm_bindgen(js_name = save)]
+    pub fn js_save(&self) -> Result<Vec<u8>, JsError> {
+        self.save().map_err(|err| JsError::new(&err.to_string()))
+    }
+
+    /// The finished image as a PNG, with the scene that made it embedded
+    #[wasm_bindgen(js_name = exportPng)]
+    pub fn js_export_png(&self) -> Result<Vec<u8>, JsError> {
+        self.export_png()
+            .map_err(|err| JsError::new(&err.to_string()))
+    }
+
+    /// The root index and smooth iteration count of every pixel, as "npz" or "csv" bytes. The
+    /// final z can only be included while records are turned on.
+    #[wasm_bindgen(js_name = exportRaw)]
+    pub fn js_export_raw(&self, format: &str, include_z: bool) -> Result<Vec<u8>, JsError> {
+        RawFormat::from_extension(format)
+            .and_then(|format| self.export_raw(format, include_z))
+            .map_err(|err| JsError::new(&err.to_string()))
+    }
+
+    pub fn formula(&self) -> String {
+        self.formula.clone()
+    }
+
+    #[wasm_bindgen(js_name = setFormula)]
+    pub fn js_set_formula(&mut self, formula: &str) -> Result<(), JsError> {
+        self.set_formula(formula)
+            .map_err(|err| JsError::new(&err.to_string()))
+    }
+
+    #[wasm_bindgen(js_name = setViewport)]
+    pub fn js_set_viewport(&mut self, viewport: JsViewport) -> Result<(), JsError> {
+        let viewport: Viewport = viewport.js_try_into()?;
+        self.set_viewport(viewport.into());
+        Ok(())
+    }
+
+    /// An empty parameter list selects the method's defaults
+    #[wasm_bindgen(js_name = setMethod)]
+    pub fn js_set_method(&mut self, method: u32, params: &[f32]) -> Result<(), JsError> {
+        self.set_method(method, params)
+            .map_err(|err| JsError::new(&err.to_string()))
+    }
+
+    #[wasm_bindgen(js_name = setRootColors)]
+    pub fn js_set_root_colors(&mut self, colors: JsOklchColorArray) -> Result<(), JsError> {
+        let colors: JsValue = colors.into();
+        let colors: Array = colors.into();
+        let colors = colors
+            .iter()
+            .map(|color| color.js_try_into())
+            .collect::<Result<Vec<OklchColor>, _>>()?;
+        self.set_root_colors(colors)
+            .map_err(|err| JsError::new(&err.to_string()))
+    }
+
+    /// `dropoff` is on the slider's 0 to 1 scale, the same as in scenes and the CLI
+    #[wasm_bindgen(js_name = setColoring)]
+    pub fn js_set_coloring(
+        &mut self,
+        dropoff: f32,
+        lightness_mode: u32,
+        is_dark_non_convergence: bool,
+    ) -> Result<(), JsError> {
+        let lightness_mode = LightnessMode::try_from(lightness_mode)
+            .map_err(|err| JsError::new(&err.to_string()))?;
+        self.set_coloring(dropoff, lightness_mode, is_dark_non_convergence)
+            .map_err(|err| JsError::new(&err.to_string()))
+    }
+
+    /// A curve in `LightnessCurve`'s text format, or nothing to go back to the lightness mode
+    #[wasm_bindgen(js_name = setLightnessCurve)]
+    pub fn js_set_lightness_curve(&mut self, curve: Option<String>) -> Result<(), JsError> {
+        let curve = curve
+            .map(|curve| LightnessCurve::parse(&curve))
+            .transpose()
+            .map_err(|err| JsError::new(&err.to_string()))?;
+        self.set_lightness_curve(curve);
+        Ok(())
+    }
+
+    /// Keeps a full `PixelRecord` per pixel rather than packed `PixelData`, which the
+    /// shading modes other than iterations need
+    #[wasm_bindgen(js_name = setRecords)]
+    pub fn js_set_records(&mut self, is_enabled: bool) {
+        self.set_records(is_enabled);
+    }
+
+    #[wasm_bindgen(js_name = setShading)]
+    pub fn js_set_shading(&mut self, shading: u32) -> Result<(), JsError> {
+        ShadingMode::try_from(shading)
+            .and_then(|shading| self.set_shading(shading))
+            .map_err(|err| JsError::new(&err.to_string()))
+    }
+
+    #[wasm_bindgen(js_name = roots)]
+    pub fn js_roots(&self) -> Vec<JsComplex> {
+        self.roots.roots.iter().cloned().map(Into::into).collect()
+    }
+
+    #[wasm_bindgen(js_name = rootColors)]
+    pub fn js_root_colors(&self) -> Vec<JsOklchColor> {
+        self.roots.colors.iter().cloned().map(Into::into).collect()
+    }
+
+    #[wasm_bindgen(js_name = needsRecalculation)]
+    pub fn needs_recalculation(&self) -> bool {
+        self.needs_recalculation
+    }
+
+    #[wasm_bindgen(js_name = needsRecolor)]
+    pub fn needs_recolor(&self) -> bool {
+        self.needs_recolor
+    }
+
+    /// Whether every pixel has been calculated, and nothing has changed since
+    #[wasm_bindgen(js_name = isDone)]
+    pub fn is_done(&self) -> bool {
+        !self.needs_recalculation
+            && !self.needs_recolor
+            && self.pending.is_none()
+            && self.progressive.is_done()
+    }
+
+    /// One step of the render, after which the changed rows only need copying onto the canvas.
+    /// Recolors the whole image if only the coloring changed, otherwise calculates up to
+    /// `row_count` more rows. Returns the `[start, end)` rows of the image that changed, or
+    /// nothing once the render is finished.
+    #[wasm_bindgen(js_name = nextPass)]
+    pub fn js_next_pass(&mut self, row_count: usize) -> Result<Option<Vec<usize>>, JsError> {
+        self.next_pass(row_count)
+            .map_err(|err| JsError::new(&err.to_string()))
+    }
+
+    /// Calculates up to `row_count` more rows of samples, starting over first if anything
+    /// that affects the calculation has changed. Returns the `[start, end)` rows of the image
+    /// that changed, or nothing once the whole frame has been calculated. Throws if rows from
+    /// `startRows` haven't been finished yet.
+    #[wasm_bindgen(js_name = calculateRows)]
+    pub fn js_calculate_rows(&mut self, row_count: usize) -> Result<Option<Vec<usize>>, JsError> {
+        self.calculate_rows(row_count)
+            .map_err(|err| JsError::new(&err.to_string()))
+    }
+
+    /// Like `calculateRows`, but leaves the calculating to whoever gets the returned handle,
+    /// which is the threaded build's calculation worker, through `__calculateShared`. Returns
+    /// nothing once the whole frame has been calculated.
+    #[wasm_bindgen(js_name = startRows)]
+    pub fn js_start_rows(&mut self, row_count: usize) -> Result<Option<usize>, JsError> {
+        let buffer = self
+            .start_rows(row_count)
+            .map_err(|err| JsError::new(&err.to_string()))?;
+        Ok(buffer.map(|buffer| buffer.handle()))
+    }
+
+    /// Whether the rows from `startRows` are still being calculated
+    #[wasm_bindgen(js_name = isCalculating)]
+    pub fn is_calculating(&self) -> bool {
+        self.pending
+            .as_ref()
+            .is_some_and(|buffer| !buffer.is_calculated())
+    }
+
+    /// Writes back and colors the rows from `startRows` once they've been calculated. Returns
+    /// the `[start, end)` rows of the image that changed, or nothing if there aren't any rows
+    /// ready.
+    #[wasm_bindgen(js_name = finishRows)]
+    pub fn js_finish_rows(&mut self) -> Option<Vec<usize>> {
+        let rows = self.finish_rows()?;
+        Some(vec![rows.start, rows.end])
+    }
+
+    /// Recolors the whole image from the pixel data calculated so far
+    pub fn recolor(&mut self) {
+        self.color_pixels(0..self.pixels.len());
+        self.needs_recolor = false;
+    }
+
+    /// Copies rows `start..end` of the image onto the canvas
+    pub fn draw(
+        &self,
+        ctx: &web_sys::CanvasRenderingContext2d,
+        start: usize,
+        end: usize,
+    ) -> Result<(), JsValue> {
+        let width = self.viewport.width;
+        let end = end.min(self.viewport.height);
+        if start >= end {
+            return Ok(());
+        }
+
+        let image_data = web_sys::ImageData::new_with_u8_clamped_array(
+            wasm_bindgen::Clamped(&self.image[4 * start * width..4 * end * width]),
+            width as u32,
+        )?;
+        ctx.put_image_data(&image_data, 0.0, start as f64)
+    }
+}
+
+///////////////////////////////////////////////////////////////////
+
+impl RenderSession {
+    pub fn with_formula(formula: &str, viewport: V) -> Result<Self> {
+        let (fz, roots) = parse_formula(formula)?;
+        let method = IterMethod::by_id(0)?;
+        let len = viewport.width * viewport.height;
+        let pixels = Pixels::new(PixelKind::packed_for(&roots), len);
+        Ok(Self {
+            formula: formula.to_string(),
+            fz,
+            roots,
+            viewport,
+            method,
+            params: method.default_params(),
+            coloring: Coloring::default(),
+            pixels,
+            image: vec![0; 4 * len],
+            progressive: ProgressiveRender::new(viewport, COARSEST_SCALE_FACTOR),
+            pending: None,
+            needs_recalculation: true,
+            needs_recolor: false,
+        })
+    }
+
+    pub fn from_saved(bytes: &[u8]) -> Result<Self> {
+        let SavedFrame {
+            fz,
+            roots,
+            viewport,
+            method,
+            params,
+            coloring,
+            pixel_data,
+        } = SavedFrame::load(bytes)?;
+        let pixels = match PixelKind::packed_for(&roots) {
+            PixelKind::Wide => Pixels::Wide(pixel_data),
+            _ => Pixels::Packed(
+                pixel_data
+                    .into_iter()
+                    .map(|pixel| {
+                        let PixelDataDetail { root_index, frac } = pixel.into();
+                        (root_index, frac).into()
+                    })
+                    .collect(),
+            ),
+        };
+        Ok(Self {
+            formula: fz.to_string(),
+            fz,
+            roots,
+            viewport,
+            method: IterMethod::by_id(method)?,
+            params,
+            coloring: Coloring::from_scene(coloring),
+            pixels,
+            image: vec![0; 4 * viewport.width * viewport.height],
+            progressive: ProgressiveRender::finished(viewport),
+            pending: None,
+            needs_recalculation: false,
+            needs_recolor: true,
+        })
+    }
+
+    /// Records are saved as the packed pixels they'd make. The shading isn't saved, as it
+    /// needs records.
+    pub fn save(&self) -> Result<Vec<u8>> {
+        if self.needs_recalculation || !self.progressive.is_done() {
+            bail!("The frame can only be saved once it's finished rendering");
+        }
+
+        let pixel_data = match &self.pixels {
+            Pixels::Packed(pixel_data) => pixel_data.iter().map(|&pixel| pixel.into()).collect(),
+            Pixels::Wide(pixel_data) => pixel_data.clone(),
+            Pixels::Records(records) => records
+                .iter()
+                .map(|record| (record.root_index, record.frac).into())
+                .collect(),
+        };
+        SavedFrame {
+            fz: self.fz.clone(),
+            roots: self.roots.clone(),
+            viewport: self.viewport,
+            method: self.method.id,
+            params: self.params.clone(),
+            coloring: self.coloring.to_scene(),
+            pixel_data,
+        }
+        .save()
+    }
+
+    /// Everything but the shading, which scenes don't cover
+    pub fn scene(&self) -> Scene {
+        Scene {
+            version: SCENE_VERSION,
+            formula: self.formula.clone(),
+            method: self.method.id,
+            params: self.params.clone(),
+            view: SceneView::from_viewport(&self.viewport),
+            coloring: self.coloring.to_scene(),
+            root_colors: Some(self.roots.colors.clone()),
+        }
+    }
+
+    pub fn export_png(&self) -> Result<Vec<u8>> {
+        if self.needs_recalculation || self.needs_recolor || !self.progressive.is_done() {
+            bail!("The image can only be exported once it's finished rendering");
+        }
+        let V { width, height, .. } = self.viewport;
+        encode_png(width, height, &self.image, &self.scene())
+    }
+
+    pub fn export_raw(&self, format: RawFormat, include_z: bool) -> Result<Vec<u8>> {
+        if self.needs_recalculation || !self.progressive.is_done() {
+            bail!("Raw data can only be exported once the frame's finished rendering");
+        }
+        let raw_data = match (&self.pixels, include_z) {
+            (Pixels::Records(records), true) => RawData::from_records(records, &self.viewport),
+            (Pixels::Records(records), false) => RawData::from_pixels(records, &self.viewport),
+            (Pixels::Packed(_) | Pixels::Wide(_), true) => {
+                bail!("The final z needs records, which aren't turned on")
+            }
+            (Pixels::Packed(pixel_data), false) => RawData::from_pixels(pixel_data, &self.viewport),
+            (Pixels::Wide(pixel_data), false) => RawData::from_pixels(pixel_data, &self.viewport),
+        };
+        raw_data.encode(format)
+    }
+
+    /// Recalculates. The roots get their default colors, until `set_root_colors` is called.
+    /// Packed pixels switch width if the new roots need it.
+    pub fn set_formula(&mut self, formula: &str) -> Result<()> {
+        if formula == self.formula {
+            return Ok(());
+        }
+
+        (self.fz, self.roots) = parse_formula(formula)?;
+        let kind = match self.pixels.kind() {
+            PixelKind::Records => PixelKind::Records,
+            _ => PixelKind::packed_for(&self.roots),
+        };
+        if kind != self.pixels.kind() {
+            self.pixels = Pixels::new(kind, self.pixels.len());
+        }
+        self.formula = formula.to_string();
+        self.needs_recalculation = true;
+        Ok(())
+    }
+
+    /// Recalculates if the view moved, and resizes the buffers if its size changed
+    pub fn set_viewport(&mut self, viewport: V) {
+        if viewport == self.viewport {
+            return;
+        }
+
+        let len = viewport.width * viewport.height;
+        if len != self.pixels.len() {
+            self.pixels = self.pixels.resized(len);
+            self.image = vec![0; 4 * len];
+        }
+        self.viewport = viewport;
+        self.needs_recalculation = true;
+    }
+
+    /// Recalculates if the method or its params changed
+    pub fn set_method(&mut self, method: u32, params: &[f32]) -> Result<()> {
+        let method = IterMethod::by_id(method)?;
+        let params = method.resolve_params(params)?;
+        if method.id == self.method.id && params == self.params {
+            return Ok(());
+        }
+
+        self.method = method;
+        self.params = params;
+        self.needs_recalculation = true;
+        Ok(())
+    }
+
+    /// Only recolors, as the pixel data stays the same
+    pub fn set_root_colors(&mut self, colors: Vec<OklchColor>) -> Result<()> {
+        if colors.len() != self.roots.colors.len() {
+            return Err(anyhow!(
+                "There should be {} colors for the roots",
+                self.roots.colors.len()
+            ));
+        }
+
+        if colors != self.roots.colors {
+            self.roots.colors = colors;
+            self.needs_recolor = true;
+        }
+        Ok(())
+    }
+
+    /// Only recolors, as the pixel data stays the same. `dropoff` is on the slider's 0 to 1
+    /// scale, like `SceneColoring`'s.
+    pub fn set_coloring(
+        &mut self,
+        dropoff: f32,
+        lightness_mode: LightnessMode,
+        is_dark_non_convergence: bool,
+    ) -> Result<()> {
+        if !(0. ..=1.).contains(&dropoff) {
+            bail!("Dropoff must be within 0 to 1, but was {dropoff}");
+        }
+
+        let coloring = Coloring {
+            dropoff,
+            lightness_mode,
+            is_dark_non_convergence,
+            ..self.coloring.clone()
+        };
+        if coloring != self.coloring {
+            self.coloring = coloring;
+            self.needs_recolor = true;
+        }
+        Ok(())
+    }
+
+    /// Only recolors. While a curve is set, it's used instead of the lightness mode.
+    pub fn set_lightness_curve(&mut self, curve: Option<LightnessCurve>) {
+        if curve != self.coloring.lightness_curve {
+            self.coloring.lightness_curve = curve;
+            self.needs_recolor = true;
+        }
+    }
+
+    /// Recalculates when switching between packed pixels and records. Turning records off also
+    /// goes back to shading by iterations.
+    pub fn set_records(&mut self, is_enabled: bool) {
+        if is_enabled == matches!(self.pixels, Pixels::Records(_)) {
+            return;
+        }
+
+        let kind = match is_enabled {
+            true => PixelKind::Records,
+            false => PixelKind::packed_for(&self.roots),
+        };
+        self.pixels = Pixels::new(kind, self.pixels.len());
+        self.coloring.shading = ShadingMode::Iterations;
+        self.needs_recalculation = true;
+    }
+
+    /// Only recolors. Anything but shading by iterations needs records turned on first.
+    pub fn set_shading(&mut self, shading: ShadingMode) -> Result<()> {
+        if shading.needs_records() && !matches!(self.pixels, Pixels::Records(_)) {
+            return Err(anyhow!(
+                "{shading:?} shading needs records, which aren't turned on"
+            ));
+        }
+
+        if shading != self.coloring.shading {
+            self.coloring.shading = shading;
+            self.needs_recolor = true;
+        }
+        Ok(())
+    }
+
+    pub fn next_pass(&mut self, row_count: usize) -> Result<Option<Vec<usize>>> {
+        if self.needs_recolor {
+            self.recolor();
+            return Ok(Some(vec![0, self.viewport.height]));
+        }
+        self.calculate_rows(row_count)
+    }
+
+    pub fn calculate_rows(&mut self, row_count: usize) -> Result<Option<Vec<usize>>> {
+        let Some(buffer) = self.start_rows(row_count)? else {
+            return Ok(None);
+        };
+        buffer.calculate();
+        Ok(self.finish_rows().map(|rows| vec![rows.start, rows.end]))
+    }
+
+    /// Picks the next rows to calculate, starting over first if anything that affects the
+    /// calculation has changed. Only one batch can be out at a time.
+    pub fn start_rows(&mut self, row_count: usize) -> Result<Option<SharedPixelDataBuffer>> {
+        if self.pending.is_some() {
+            bail!("The last rows have to be finished before starting more");
+        }
+
+        if self.needs_recalculation {
+            self.progressive = ProgressiveRender::new(self.viewport, COARSEST_SCALE_FACTOR);
+            self.needs_recalculation = false;
+        }
+
+        let Some(rows) = self.progressive.next_rows(row_count) else {
+            return Ok(None);
+        };
+        let buffer = SharedPixelDataBuffer::new(RowBatch {
+            fz: self.fz.clone(),
+            roots: self.roots.roots.clone(),
+            method: self.method,
+            params: self.params.clone(),
+            progressive: self.progressive.clone(),
+            rows,
+            kind: self.pixels.kind(),
+        });
+        self.pending = Some(buffer.clone());
+        Ok(Some(buffer))
+    }
+
+    /// Writes back and colors the rows from `start_rows`, or returns `None` if they're still
+    /// being calculated. Rows that something has since changed out from under are thrown away,
+    /// which leaves an empty range.
+    pub fn finish_rows(&mut self) -> Option<std::ops::Range<usize>> {
+        let samples = self.pending.as_ref()?.take_samples()?;
+        self.pending = None;
+        if self.needs_recalculation {
+            return Some(0..0);
+        }
+
+        let rows = match (samples, &mut self.pixels) {
+            (RowSamples::Packed(samples), Pixels::Packed(pixel_data)) => {
+                self.progressive.write_rows(samples, pixel_data)
+            }
+            (RowSamples::Wide(samples), Pixels::Wide(pixel_data)) => {
+                self.progressive.write_rows(samples, pixel_data)
+            }
+            (RowSamples::Records(samples), Pixels::Records(records)) => {
+                self.progressive.write_rows(samples, records)
+            }
+            // Switching between layouts always recalculates
+            _ => return Some(0..0),
+        };
+
+        let width = self.viewport.width;
+        self.color_pixels(rows.start * width..rows.end * width);
+        Some(rows)
+    }
+
+    /// Only the 16 bit packed pixels, which are empty while records are turned on or the
+    /// pixels are wide
+    pub fn pixel_data(&self) -> &[PixelData] {
+        match &self.pixels {
+            Pixels::Packed(pixel_data) => pixel_data,
+            Pixels::Wide(_) | Pixels::Records(_) => &[],
+        }
+    }
+
+    /// Only the 32 bit packed pixels, which are empty unless there are more than 16 roots
+    pub fn wide_pixel_data(&self) -> &[PixelData32] {
+        match &self.pixels {
+            Pixels::Wide(pixel_data) => pixel_data,
+            Pixels::Packed(_) | Pixels::Records(_) => &[],
+        }
+    }
+
+    /// Only the records, which are empty unless turned on
+    pub fn records(&self) -> &[PixelRecord] {
+        match &self.pixels {
+            Pixels::Packed(_) | Pixels::Wide(_) => &[],
+            Pixels::Records(records) => records,
+        }
+    }
+
+    pub fn image(&self) -> &[u8] {
+        &self.image
+    }
+
+    fn color_pixels(&mut self, range: std::ops::Range<usize>) {
+        let Coloring {
+            dropoff,
+            shading,
+            is_dark_non_convergence,
+            ..
+        } = self.coloring;
+        let lightness = self.coloring.lightness();
+        let dropoff = SceneColoring::pixel_dropoff_from_slider(dropoff);
+        let luminance_max = calc_luminance_max(dropoff);
+
+        let colors = &self.roots.colors;
+        let image = self.image[4 * range.start..4 * range.end].chunks_exact_mut(4);
+        match &self.pixels {
+            Pixels::Packed(pixel_data) => color_packed(
+                &pixel_data[range],
+                image,
+                colors,
+                luminance_max,
+                dropoff,
+                &lightness,
+                is_dark_non_convergence,
+            ),
+            Pixels::Wide(pixel_data) => color_packed(
+                &pixel_data[range],
+                image,
+                colors,
+                luminance_max,
+                dropoff,
+                &lightness,
+                is_dark_non_convergence,
+            ),
+            Pixels::Records(records) => {
+                for (record, rgba) in records[range].iter().zip(image) {
+                    rgba.copy_from_slice(&record_color(
+                        record,
+                        colors,
+                        luminance_max,
+                        dropoff,
+                        &lightness,
+                        shading,
+                        is_dark_non_convergence,
+                    ));
+                }
+            }
+        }
+    }
+}
+
+impl Default for Coloring {
+    fn default() -> Self {
+        Self {
+            dropoff: 0.5,
+            lightness_mode: LightnessMode::Normal,
+            lightness_curve: None,
+            shading: ShadingMode::Iterations,
+            is_dark_non_convergence: true,
+        }
+    }
+}
+
+impl Coloring {
+    /// Shading by iterations, as scenes don't cover the shading
+    fn from_scene(coloring: SceneColoring) -> Self {
+        let (lightness_mode, lightness_curve) = match coloring.lightness {
+            LightnessCurve::Mode(mode) => (mode, None),
+            curve => (LightnessMode::default(), Some(curve)),
+        };
+        Self {
+            dropoff: coloring.dropoff,
+            lightness_mode,
+            lightness_curve,
+            shading: ShadingMode::Iterations,
+            is_dark_non_convergence: coloring.chaos == ChaosColor::Black,
+        }
+    }
+
+    fn to_scene(&self) -> SceneColoring {
+        SceneColoring {
+            lightness: self.lightness(),
+            dropoff: self.dropoff,
+            chaos: match self.is_dark_non_convergence {
+                true => ChaosColor::Black,
+                false => ChaosColor::White,
+            },
+            // Sessions color by hue, never with gradients
+            palette: None,
+        }
+    }
+
+    fn lightness(&self) -> LightnessCurve {
+        match &self.lightness_curve {
+            Some(curve) => curve.clone(),
+            None => self.lightness_mode.into(),
+        }
+    }
+}
+
+impl Pixels {
+    fn new(kind: PixelKind, len: usize) -> Self {
+        match kind {
+            PixelKind::Packed => Pixels::Packed(vec![PixelData::default(); len]),
+            PixelKind::Wide => Pixels::Wide(vec![PixelData32::default(); len]),
+            PixelKind::Records => Pixels::Records(vec![PixelRecord::default(); len]),
+        }
+    }
+
+    fn kind(&self) -> PixelKind {
+        match self {
+            Pixels::Packed(_) => PixelKind::Packed,
+            Pixels::Wide(_) => PixelKind::Wide,
+            Pixels::Records(_) => PixelKind::Records,
+        }
+    }
+
+    fn len(&self) -> usize {
+        match self {
+            Pixels::Packed(pixel_data) => pixel_data.len(),
+            Pixels::Wide(pixel_data) => pixel_data.len(),
+            Pixels::Records(records) => records.len(),
+        }
+    }
+
+    fn resized(&self, len: usize) -> Self {
+        Pixels::new(self.kind(), len)
+    }
+}
+
+impl PixelKind {
+    /// The narrowest packed layout that can tell all the roots apart
+    fn packed_for(roots: &R) -> Self {
+        match roots.roots.len() > PixelData::MAX_ROOTS {
+            true => PixelKind::Wide,
+            false => PixelKind::Packed,
+        }
+    }
+}
+
+fn color_packed<Px: PackedPixel>(
+    pixel_data: &[Px],
+    image: std::slice::ChunksExactMut<u8>,
+    colors: &[OklchColor],
+    luminance_max: f32,
+    dropoff: f32,
+    lightness: &LightnessCurve,
+    is_dark_non_convergence: bool,
+) {
+    for (&pixel, rgba) in pixel_data.iter().zip(image) {
+        rgba.copy_from_slice(&pixel_color(
+            pixel,
+            colors,
+            luminance_max,
+            dropoff,
+            lightness,
+            is_dark_non_convergence,
+        ));
+    }
+}
+
+fn parse_formula(formula: &str) -> Result<(P, R)> {
+    let fz = P::parse(formula)?;
+    let roots = R::new(&fz)?;
+    Ok((fz, roots))
+}
+
+///////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn calculate_all(session: &mut RenderSession) {
+        while session.calculate_rows(4).unwrap().is_some() {}
+    }
+
+    fn new_session() -> RenderSession {
+        let mut session = RenderSession::with_formula("z^3 - 1", V::new(40, 30)).unwrap();
+        calculate_all(&mut session);
+        session
+    }
+
+    #[test]
+    fn test_calculates_whole_frame() {
+        let mut session = RenderSession::with_formula("z^3 - 1", V::new(40, 30)).unwrap();
+        assert!(session.needs_recalculation());
+        assert_eq!(session.calculate_rows(1).unwrap(), Some(vec![0, 30]));
+
+        calculate_all(&mut session);
+        assert!(session.is_done());
+
+        // Every pixel got colored in, and non-convergence is black rather than transparent
+        assert!(session.image().chunks(4).all(|rgba| rgba[3] == 255));
+    }
+
+    #[test]
+    fn test_setters_mark_recalculation() {
+        let mut session = new_session();
+        let viewport = V::new(40, 30);
+
+        // Setting things to what they already are doesn't throw away any work
+        session.set_formula("z^3 - 1").unwrap();
+        session.set_viewport(viewport);
+        session.set_method(0, &[]).unwrap();
+        assert!(session.is_done());
+
+        session.set_viewport(V {
+            rotation: 0.5,
+            ..viewport
+        });
+        assert!(session.needs_recalculation() && !session.needs_recolor());
+
+        calculate_all(&mut session);
+        session.set_method(3, &[]).unwrap();
+        assert!(session.needs_recalculation());
+
+        calculate_all(&mut session);
+        session.set_formula("z^4 - 1").unwrap();
+        assert!(session.needs_recalculation());
+        assert_eq!(session.roots.roots.len(), 4);
+
+        // Bad input leaves the session as it was
+        calculate_all(&mut session);
+        assert!(session.set_formula("z^").is_err());
+        assert!(session.set_method(0, &[5.]).is_err());
+        assert!(session.is_done());
+    }
+
+    #[test]
+    fn test_setters_mark_recolor() {
+        let mut session = new_session();
+        let pixel_data = session.pixel_data().to_vec();
+        let image = session.image().to_vec();
+
+        session
+            .set_coloring(0.6, LightnessMode::Inverted, false)
+            .unwrap();
+        assert!(session.needs_recolor() && !session.needs_recalculation());
+        session.recolor();
+        assert!(session.is_done());
+        assert_eq!(session.pixel_data(), pixel_data);
+        assert_ne!(session.image(), image);
+
+        let curve = LightnessCurve::parse("gamma 0.5").unwrap();
+        session.set_lightness_curve(Some(curve.clone()));
+        assert!(session.needs_recolor());
+        session.recolor();
+        session.set_lightness_curve(Some(curve.clone()));
+        session
+            .set_coloring(0.6, LightnessMode::Parabola, false)
+            .unwrap();
+        assert!(session.needs_recolor());
+        session.recolor();
+        // The curve still takes over from the mode
+        assert_eq!(session.scene().coloring.lightness, curve);
+        session.set_lightness_curve(None);
+        session.recolor();
+        assert_eq!(
+            session.scene().coloring.lightness,
+            LightnessMode::Parabola.into()
+        );
+
+        let mut colors = session.roots.colors.clone();
+        colors[0].h += 90.;
+        session.set_root_colors(colors.clone()).unwrap();
+        assert!(session.needs_recolor());
+        session.recolor();
+        session.set_root_colors(colors).unwrap();
+        assert!(session.is_done());
+
+        assert!(session.set_root_colors(Vec::new()).is_err());
+        assert!(session
+            .set_coloring(1.5, LightnessMode::Normal, false)
+            .is_err());
+    }
+
+    #[test]
+    fn test_next_pass() {
+        let mut session = RenderSession::with_formula("z^3 - 1", V::new(40, 30)).unwrap();
+        let mut expected = RenderSession::with_formula("z^3 - 1", V::new(40, 30)).unwrap();
+        while let Some(rows) = session.next_pass(4).unwrap() {
+            assert_eq!(Some(rows), expected.calculate_rows(4).unwrap());
+        }
+        assert!(session.is_done());
+
+        // Recoloring redraws everything in one go
+        session
+            .set_coloring(0.2, LightnessMode::Normal, true)
+            .unwrap();
+        assert_eq!(session.next_pass(4).unwrap(), Some(vec![0, 30]));
+        assert_eq!(session.next_pass(4).unwrap(), None);
+    }
+
+    #[test]
+    fn test_calculate_rows_while_started() {
+        let mut session = RenderSession::with_formula("z^3 - 1", V::new(40, 30)).unwrap();
+        let buffer = session.start_rows(4).unwrap().unwrap();
+
+        // Rather than looking finished, which would stop the render
+        assert!(session.calculate_rows(4).is_err());
+        assert!(session.next_pass(4).is_err());
+
+        buffer.calculate();
+        assert_eq!(session.finish_rows(), Some(0..30));
+        assert!(session.calculate_rows(4).unwrap().is_some());
+    }
+
+    #[test]
+    fn test_records() {
+        let mut session = new_session();
+        assert!(session.set_shading(ShadingMode::Residual).is_err());
+
+        session.set_records(true);
+        assert!(session.needs_recalculation());
+        calculate_all(&mut session);
+        assert_eq!(session.records().len(), 40 * 30);
+        assert!(session.pixel_data().is_empty());
+
+        // Shading by iterations looks the same either way, give or take the precision packing
+        // loses, and switching shading only recolors
+        let image = session.image().to_vec();
+        let max_difference = image
+            .iter()
+            .zip(new_session().image())
+            .map(|(&a, &b)| a.abs_diff(b))
+            .max();
+        assert!(max_difference <= Some(2), "{max_difference:?}");
+        session.set_shading(ShadingMode::StepSize).unwrap();
+        assert!(session.needs_recolor() && !session.needs_recalculation());
+        session.recolor();
+        assert_ne!(session.image(), image);
+
+        session.set_records(false);
+        assert!(session.needs_recalculation());
+        calculate_all(&mut session);
+        assert_eq!(session.image(), new_session().image());
+    }
+
+    #[test]
+    fn test_wide_pixels() {
+        let mut session = new_session();
+        session.set_formula("z^20 - 1").unwrap();
+        calculate_all(&mut session);
+        assert!(session.pixel_data().is_empty());
+        let root_indices: std::collections::HashSet<_> = session
+            .wide_pixel_data()
+            .iter()
+            .map(|&pixel| PixelDataDetail::from(pixel).root_index)
+            .collect();
+        assert!(
+            root_indices.len() > PixelData::MAX_ROOTS,
+            "{root_indices:?}"
+        );
+
+        // Records don't care, and turning them off picks the width again
+        session.set_records(true);
+        session.set_records(false);
+        assert_eq!(session.wide_pixel_data().len(), 40 * 30);
+
+        session.set_formula("z^3 - 1").unwrap();
+        calculate_all(&mut session);
+        assert!(session.wide_pixel_data().is_empty());
+        assert_eq!(session.image(), new_session().image());
+    }
+
+    #[test]
+    fn test_save_and_load() {
+        let mut session = RenderSession::with_formula("z^3 - 1", V::new(40, 30)).unwrap();
+        assert!(session.save().is_err());
+        calculate_all(&mut session);
+        let mut colors = session.roots.colors.clone();
+        colors[1].c = 0.05;
+        session.set_root_colors(colors).unwrap();
+        session
+            .set_coloring(0.7, LightnessMode::Normal, false)
+            .unwrap();
+        session.set_lightness_curve(Some(LightnessCurve::parse("gamma 2").unwrap()));
+        session.recolor();
+
+        // Only needs coloring in, with the same colors and coloring as before
+        let mut loaded = RenderSession::from_saved(&session.save().unwrap()).unwrap();
+        assert!(loaded.needs_recolor() && !loaded.needs_recalculation());
+        assert_eq!(loaded.calculate_rows(1).unwrap(), None);
+        loaded.recolor();
+        assert!(loaded.is_done());
+        assert_eq!(loaded.image(), session.image());
+        assert_eq!(loaded.formula(), "z^3 - 1");
+        assert_eq!(loaded.coloring, session.coloring);
+
+        // Wide pixels come back wide
+        session.set_formula("z^20 - 1").unwrap();
+        calculate_all(&mut session);
+        let mut loaded = RenderSession::from_saved(&session.save().unwrap()).unwrap();
+        assert_eq!(loaded.wide_pixel_data(), session.wide_pixel_data());
+        loaded.recolor();
+        assert_eq!(loaded.image(), session.image());
+
+        assert!(RenderSession::from_saved(&[]).is_err());
+    }
+
+    #[test]
+    fn test_export_png() {
+        let mut session = RenderSession::with_formula("z^3 - 1", V::new(40, 30)).unwrap();
+        session
+            .set_coloring(0.8, LightnessMode::Parabola, false)
+            .unwrap();
+        assert!(session.export_png().is_err());
+        calculate_all(&mut session);
+        session.recolor();
+
+        let scene = newton_core::png_scene(&session.export_png().unwrap()).unwrap();
+        assert_eq!(scene.formula, "z^3 - 1");
+        assert_eq!(scene.root_colors.as_ref(), Some(&session.roots.colors));
+        assert_eq!(scene.viewport(40, 30), session.viewport);
+        assert_eq!(scene.coloring.lightness, LightnessMode::Parabola.into());
+        assert_eq!(scene.coloring.dropoff, 0.8);
+    }
+
+    #[test]
+    fn test_export_raw() {
+        let mut session = new_session();
+        assert!(session.export_raw(RawFormat::Npz, true).is_err());
+        let csv = String::from_utf8(session.export_raw(RawFormat::Csv, false).unwrap()).unwrap();
+        assert_eq!(
+            csv.lines().filter(|line| !line.starts_with('#')).count(),
+            1 + 40 * 30
+        );
+
+        session.set_records(true);
+        assert!(session.export_raw(RawFormat::Npz, true).is_err());
+        calculate_all(&mut session);
+        assert!(session.export_raw(RawFormat::Npz, true).is_ok());
+    }
+
+    #[test]
+    fn test_resizes_buffers() {
+        let mut session = new_session();
+        session.set_viewport(V::new(25, 10));
+        calculate_all(&mut session);
+        assert_eq!(session.pixel_data().len(), 25 * 10);
+        assert_eq!(session.image().len(), 4 * 25 * 10);
+    }
+}
@@ -0,0 +1,162 @@
+use wasm_bindgen::prelude::*;
+
+use crate::pixel_data_buffer::SharedPixelDataBuffer;
+
+// JS has to await `initThreadPool(navigator.hardwareConcurrency)` before rendering
+#[cfg(all(feature = "threads", target_arch = "wasm32"))]
+pub use wasm_bindgen_rayon::init_thread_pool;
+
+///////////////////////////////////////////////////////////////////
+
+/// How many rows are worth handing to `__calculateRows` at once
+#[wasm_bindgen(js_name = __threadCount)]
+pub fn thread_count() -> usize {
+    #[cfg(feature = "threads")]
+    return rayon::current_num_threads();
+
+    #[cfg(not(feature = "threads"))]
+    1
+}
+
+/// The module and its memory, for starting the calculation worker on. Sharing the memory is what
+/// lets the worker fill in buffers the main thread reads straight back.
+#[cfg(all(feature = "threads", target_arch = "wasm32"))]
+#[wasm_bindgen(js_name = __sharedModule)]
+pub fn shared_module() -> web_sys::js_sys::Array {
+    web_sys::js_sys::Array::of2(&wasm_bindgen::module(), &wasm_bindgen::memory())
+}
+
+/// Calculates the rows behind a handle from `RenderSession.startRows`. In the threaded build
+/// this waits on the worker pool, which browsers only allow off the main thread, so it's
+/// called from the calculation worker.
+#[wasm_bindgen(js_name = __calculateShared)]
+pub fn calculate_shared(handle: usize) {
+    // SAFETY: Handles only come from `startRows`, which the frontend sends here once each
+    let buffer = unsafe { SharedPixelDataBuffer::from_handle(handle) };
+    buffer.calculate();
+}
+
+/// Calls `f` with the index and contents of each `chunk_len` sized chunk. In the threaded build
+/// the chunks are spread across the worker pool, which blocks, so it has to be called off the
+/// browser's main thread. Otherwise they're run in order.
+pub fn for_each_chunk<T, F>(data: &mut [T], chunk_len: usize, f: F)
+where
+    T: Send,
+    F: Fn(usize, &mut [T]) + Send + Sync,
+{
+    #[cfg(feature = "threads")]
+    {
+        use rayon::prelude::*;
+        data.par_chunks_mut(chunk_len)
+            .enumerate()
+            .for_each(|(index, chunk)| f(index, chunk));
+    }
+
+    #[cfg(not(feature = "threads"))]
+    data.chunks_mut(chunk_len)
+        .enumerate()
+        .for_each(|(index, chunk)| f(index, chunk));
+}
+
+///////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use newton_core::{PixelData, Polynomial, Roots, Viewport, ITER_METHODS};
+    use wasm_bindgen_test::wasm_bindgen_test;
+
+    use crate::{render::calculate_rows_into, session::RenderSession};
+
+    // Runs under Node with `wasm-pack test --node`, as well as natively with `cargo test`
+    #[wasm_bindgen_test]
+    #[cfg_attr(not(target_arch = "wasm32"), test)]
+    fn test_for_each_chunk() {
+        let mut data = vec![0; 10];
+        for_each_chunk(&mut data, 3, |index, chunk| chunk.fill(index));
+        assert_eq!(data, [0, 0, 0, 1, 1, 1, 2, 2, 2, 3]);
+    }
+
+    #[wasm_bindgen_test]
+    #[cfg_attr(not(target_arch = "wasm32"), test)]
+    fn test_rows_match_row_by_row() {
+        let fz = Polynomial::parse("z^5 + 3z^3 + z + 3").unwrap();
+        let roots = Roots::new(&fz).unwrap().roots;
+        let viewport = Viewport {
+            rotation: 0.4,
+            ..Viewport::new(37, 29)
+        };
+
+        for method in &ITER_METHODS {
+            let params = method.default_params();
+            for render_scale in [1, 4] {
+                let rows: Vec<PixelData> = calculate_rows_into(
+                    &fz,
+                    &roots,
+                    &viewport,
+                    method,
+                    &params,
+                    render_scale,
+                    3,
+                    5,
+                );
+                let row_len = viewport.width.div_ceil(render_scale);
+                assert_eq!(rows.len(), 5 * row_len);
+
+                for (index, pixel_row) in rows.chunks(row_len).enumerate() {
+                    let row = 3 + index * render_scale;
+                    let expected: Vec<PixelData> = calculate_rows_into(
+                        &fz,
+                        &roots,
+                        &viewport,
+                        method,
+                        &params,
+                        render_scale,
+                        row,
+                        1,
+                    );
+                    assert_eq!(pixel_row, expected);
+                }
+            }
+        }
+    }
+
+    #[wasm_bindgen_test]
+    #[cfg_attr(not(target_arch = "wasm32"), test)]
+    fn test_shared_rows_match_session_rows() {
+        let viewport = Viewport::new(37, 29);
+        let mut session = RenderSession::with_formula("z^5 + 3z^3 + z + 3", viewport).unwrap();
+        let mut shared = RenderSession::with_formula("z^5 + 3z^3 + z + 3", viewport).unwrap();
+
+        while let Some(rows) = session.calculate_rows(3).unwrap() {
+            // The handle goes through the same hand off as a post to the calculation worker
+            let handle = shared.start_rows(3).unwrap().unwrap().handle();
+            assert!(shared.is_calculating());
+            assert_eq!(shared.finish_rows(), None);
+            assert!(shared.start_rows(3).is_err());
+
+            calculate_shared(handle);
+            assert!(!shared.is_calculating());
+            let shared_rows = shared.finish_rows().unwrap();
+            assert_eq!(vec![shared_rows.start, shared_rows.end], rows);
+        }
+        assert!(shared.start_rows(3).unwrap().is_none());
+        assert!(shared.is_done());
+        assert_eq!(shared.image(), session.image());
+    }
+
+    #[wasm_bindgen_test]
+    #[cfg_attr(not(target_arch = "wasm32"), test)]
+    fn test_shared_rows_thrown_away_after_changes() {
+        let mut session = RenderSession::with_formula("z^3 - 1", Viewport::new(20, 10)).unwrap();
+        let buffer = session.start_rows(1).unwrap().unwrap();
+        session.set_viewport(Viewport::new(30, 10));
+        buffer.calculate();
+        assert_eq!(session.finish_rows(), Some(0..0));
+
+        // Starting again picks the new view up from the coarsest pass
+        let buffer = session.start_rows(100).unwrap().unwrap();
+        buffer.calculate();
+        assert_eq!(session.finish_rows(), Some(0..10));
+    }
+}
@@ -0,0 +1,23 @@
+use wasm_bindgen::prelude::*;
+
+use crate::js_imports::{new_point, Complex, JsComplex, JsPoint, JsTryInto, JsViewport, Viewport};
+
+///////////////////////////////////////////////////////////////////
+
+/// Maps a (possibly fractional) canvas pixel onto the complex plane
+#[wasm_bindgen(js_name = __pixelToComplex)]
+pub fn pixel_to_complex(viewport: JsViewport, x: f32, y: f32) -> Result<JsComplex, JsError> {
+    let viewport: Viewport = viewport.js_try_into()?;
+    let viewport: newton_core::Viewport = viewport.into();
+    Ok(viewport.pixel_to_complex(x, y).into())
+}
+
+/// Maps a point on the complex plane onto canvas pixel coordinates
+#[wasm_bindgen(js_name = __complexToPixel)]
+pub fn complex_to_pixel(viewport: JsViewport, z: JsComplex) -> Result<JsPoint, JsError> {
+    let viewport: Viewport = viewport.js_try_into()?;
+    let viewport: newton_core::Viewport = viewport.into();
+    let z: Complex = z.js_try_into()?;
+    let (x, y) = viewport.complex_to_pixel(z.into());
+    Ok(new_point(x, y))
+}
@@ -0,0 +1,5 @@
+// Stand-in for the frontend's structs.ts, so `wasm-pack test --node` can load the crate without
+// the Next.js path aliases. Keep in step with the constructors there.
+exports.__newPoint = (x, y) => ({ x, y });
+exports.__newComplex = (re, im) => ({ re, im });
+exports.__newOklchColor = (h, c) => ({ h, c });
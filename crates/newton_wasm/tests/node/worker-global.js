@@ -0,0 +1,4 @@
+// Preloaded for the threaded build's Node tests. wasm-bindgen-rayon's worker script waits on
+// messages to `self` as soon as it's imported, which Node doesn't have. No workers are started,
+// so rayon runs everything on the calling thread.
+globalThis.self ??= new EventTarget();
@@ -15,6 +15,29 @@ install_wasm_pack() { curl https://rustwasm.github.io/wasm-pack/installer/init.s
 # There's issues with the official twiggy cargo package, so you have to build it from source from github...
 install_twiggy() { :; }
 build_wasm() { wasm-pack build $OPTS newton_wasm; }
+# Workers need shared memory, so std has to be rebuilt with atomics, which needs nightly. The
+# workers instantiate the module themselves, which needs the web target.
+build_wasm_threads() {
+    RUSTFLAGS="-C target-feature=+atomics,+bulk-memory,+mutable-globals" \
+        rustup run nightly wasm-pack build $OPTS --target web newton_wasm -- \
+        --features threads -Z build-std=panic_abort,std
+}
+# The SIMD row paths only pay off when the whole crate is built with simd128
+build_wasm_simd() {
+    RUSTFLAGS="-C target-feature=+simd128" wasm-pack build $OPTS newton_wasm -- --features simd
+}
+# render_frame only goes through rayon with the feature on, so cover both paths
+test_core() { cargo test --manifest-path newton_core/Cargo.toml --features rayon; }
+# The shim stands in for the frontend module the crate imports, see tests/node
+test_wasm() { NODE_PATH="$PWD/newton_wasm/tests/node" wasm-pack test --node newton_wasm; }
+# The threaded build under Node, which covers handing rows to the calculation worker
+test_wasm_threads() {
+    RUSTFLAGS="-C target-feature=+atomics,+bulk-memory,+mutable-globals" \
+        NODE_PATH="$PWD/newton_wasm/tests/node" \
+        NODE_OPTIONS="--require $PWD/newton_wasm/tests/node/worker-global.js" \
+        rustup run nightly wasm-pack test --node newton_wasm -- \
+        --features threads -Z build-std=panic_abort,std
+}
 twiggy_debug() {
     # Just some debugging stuff locally, for making smaller WASM files
     WASM=$PKG/newton_wasm_bg.wasm
@@ -30,6 +53,9 @@ if [ "$CMD" = "install-wasm" ]; then install_wasm_pack; fi
 
 OPTS="-d ../$PKG $OPT_LEVEL $EXTRA_OPTS"
 
-build_wasm
+if [ "$CMD" = "threads" ]; then build_wasm_threads
+elif [ "$CMD" = "simd" ]; then build_wasm_simd
+else build_wasm; fi
 
+if [ "$CMD" = "test" ]; then test_core && test_wasm && test_wasm_threads; fi
 if [ "$CMD" = "twiggy" ]; then twiggy_debug; fi
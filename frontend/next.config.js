@@ -2,12 +2,22 @@
 const nextConfig = {
     reactStrictMode: false,
     output: 'standalone',
+    // The threaded wasm build shares memory between workers, which browsers only allow on
+    // cross-origin isolated pages
+    headers: async () => process.env.NEWTON_THREADS ? [{
+        source: '/:path*',
+        headers: [
+            { key: 'Cross-Origin-Opener-Policy', value: 'same-origin' },
+            { key: 'Cross-Origin-Embedder-Policy', value: 'require-corp' },
+        ],
+    }] : [],
     webpack: config => {
         config.module ??= {};
         config.module.rules ??= [];
+        // The threaded build fetches and instantiates its own module, so it's only a file to serve
         config.module.rules.push({
             test: /\.wasm$/,
-            type: 'webassembly/async',
+            type: process.env.NEWTON_THREADS ? 'asset/resource' : 'webassembly/async',
         });
 
         config.experiments ??= {};
@@ -1,6 +1,7 @@
 import { transformIdent } from "../(util)/transform";
 import { FromValued, devalue, useValue } from "../(util)/valued";
-import { IterRootMethod, LightnessMode, NonConvergence } from "../(wasm-wrapper)/structs";
+import { LightnessMode, NonConvergence, OklchColor, Shading } from "../(wasm-wrapper)/structs";
+import { getIterMethodsSync } from "../(wasm-wrapper)/consts";
 import { defaultPolynomials } from "./settings";
 import { RenderFnToBool, StateMachineProps, useStateMachine } from "../(state-machine)/state-machine";
 import { useEffect, useRef } from "react";
@@ -14,10 +15,14 @@ export type AppGeneralPropsRaw = FromValued<AppGeneralProps>;
 export const useAppGeneralProps = () => {
     const isRendering = useValue(false);
     const formula = useValue(defaultPolynomials[0]);
-    const iterMethod = useValue(IterRootMethod.NewtonsMethod);
+    const iterMethod = useValue(0);
     const curPoint = useValue("");
     const transform = useValue(transformIdent());
+    const rotation = useValue(0);
     const lightnessMode = useValue(LightnessMode.Normal);
+    // Takes over from the mode while it's a valid curve, such as "gamma 2.2"
+    const lightnessCurve = useValue("");
+    const shading = useValue(Shading.Iterations);
     const nonConvergence = useValue(NonConvergence.Black);
     const colorScheme = useValue(ColorScheme.CONTRASTING_HUES);
     const hueOffset = useValue(0.0);
@@ -25,15 +30,13 @@ export const useAppGeneralProps = () => {
     const dropoff = useValue(0.5);
     const renderRoots = useValue(false);
     const staticHues = useValue(false);
-
-    useEffect(() => {
-        transform.value = transformIdent();
-        // eslint-disable-next-line react-hooks/exhaustive-deps
-    }, [formula.value]);
+    const showOrbit = useValue(false);
+    // Set by opening a scene, and cleared by anything that changes the color scheme or the roots
+    const rootColors = useValue<OklchColor[]>();
 
     return {
-        isRendering, formula, iterMethod, curPoint, transform, lightnessMode, nonConvergence,
-        colorScheme, hueOffset, chromaticity, dropoff, renderRoots, staticHues,
+        isRendering, formula, iterMethod, curPoint, transform, rotation, lightnessMode, lightnessCurve, shading,
+        nonConvergence, colorScheme, hueOffset, chromaticity, dropoff, renderRoots, staticHues, showOrbit, rootColors,
     };
 }
 
@@ -60,14 +63,14 @@ const useGeneralPropTriggers = (props: AppGeneralProps, stateMachine: StateMachi
 
     // Recolor the existing PDB
     useEffect(() => { triggerFn(stateMachine.initFns.recolorPassFn); }, [
-        props.lightnessMode.value, props.nonConvergence.value,
+        props.lightnessMode.value, props.lightnessCurve.value, props.shading.value, props.nonConvergence.value,
         props.colorScheme.value, props.hueOffset.value, props.chromaticity.value,
-        props.dropoff.value, props.renderRoots.value, props.staticHues.value,
+        props.dropoff.value, props.renderRoots.value, props.staticHues.value, props.rootColors.value,
     ]);
 
     // Recalculate the existing formula / roots
     useEffect(() => { triggerFn(stateMachine.initFns.recalculatePassFn); }, [
-        props.transform.value.scale, props.transform.value.translate,
+        props.transform.value.scale, props.transform.value.translate, props.rotation.value,
     ]);
 
     // Calculate from the start, with fresh formula / roots
@@ -78,21 +81,22 @@ const useGeneralPropTriggers = (props: AppGeneralProps, stateMachine: StateMachi
 /* eslint-enable react-hooks/exhaustive-deps */
 
 export const useAppOnKeyDown = (props: AppGeneralProps) => {
-    const { formula, iterMethod, curPoint, transform } = props;
+    const { formula, iterMethod, curPoint, transform, rootColors } = props;
 
     /* eslint-disable @typescript-eslint/no-non-null-assertion */
     const onKeyDown = (e: KeyboardEvent) => {
         // Don't trigger this if we're typing in an input or something
         if (document.activeElement && document.activeElement != document.body) return;
 
-        const iterRootMethods = Object.values(IterRootMethod);
+        const iterRootMethods = getIterMethodsSync().map(method => method.id);
 
         const indexPolynomial = defaultPolynomials.indexOf(formula.value);
-        const indexIterMethod = iterRootMethods.indexOf(iterMethod.value as IterRootMethod);
+        const indexIterMethod = iterRootMethods.indexOf(iterMethod.value);
 
         const setIndexPolynomial = (nextIndex: number) => {
             curPoint.value = "";
             transform.value = transformIdent();
+            rootColors.value = undefined;
             formula.value = defaultPolynomials[nextIndex];
         }
 
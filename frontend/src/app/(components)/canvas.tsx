@@ -1,9 +1,11 @@
 import styles from './page.module.css';
 import { MouseEvent, RefObject, useCallback, useEffect, useRef } from 'react';
-import { getCanvasSize, getNewtonSync } from '../(wasm-wrapper)/consts';
-import { canvasToUnitTransform, toCanvasCenterOrigin } from '../(wasm-wrapper)/transform';
-import { applyTransforms, scale } from '../(util)/transform';
-import { AppGeneralProps } from './app-props';
+import { getNewtonSync } from '../(wasm-wrapper)/consts';
+import { toViewport } from '../(wasm-wrapper)/transform';
+import { Complex, newPolynomial } from '../(wasm-wrapper)/structs';
+import { complexToPixel, orbit, pixelToComplex } from '../(wasm-wrapper)/wrapper';
+import { AppGeneralProps, AppGeneralPropsRaw } from './app-props';
+import { devalue } from '../(util)/valued';
 
 export type CanvasDrawFn = (context: CanvasRenderingContext2D, animationFrameId?: number) => void;
 
@@ -15,7 +17,8 @@ export interface CanvasProps extends React.ComponentPropsWithoutRef<"canvas"> {
 export const Canvas = (allProps: CanvasProps) => {
     const { props, drawFn, ...remProps } = allProps;
     const canvasRef = useAnimatedCanvas(drawFn);
-    const { onMouseMove, onMouseLeave } = useOnChanges(props, canvasRef);
+    const orbitCanvasRef = useRef<HTMLCanvasElement>(null);
+    const { onMouseMove, onMouseLeave } = useOnChanges(props, canvasRef, orbitCanvasRef);
 
     return (
         <div className={styles.canvasContainer}>
@@ -28,11 +31,17 @@ export const Canvas = (allProps: CanvasProps) => {
                 height={1024}
                 {...remProps}
             />
+            <canvas
+                className={styles.orbitCanvas}
+                ref={orbitCanvasRef}
+                width={1024}
+                height={1024}
+            />
         </div>
     );
 }
 
-const useOnChanges = (props: AppGeneralProps, canvasRef: RefObject<HTMLCanvasElement>) => {
+const useOnChanges = (props: AppGeneralProps, canvasRef: RefObject<HTMLCanvasElement>, orbitCanvasRef: RefObject<HTMLCanvasElement>) => {
     const onWheel = useCallback((e: WheelEvent) => {
         const { transform, curPoint } = props;
         const zoomAdjust = e.deltaY / 1000;
@@ -51,34 +60,66 @@ const useOnChanges = (props: AppGeneralProps, canvasRef: RefObject<HTMLCanvasEle
 
     // TODO: I've gotta figure out a way to properly throttle this so that it doesn't cause problems
     const onMouseMove = (e: MouseEvent<HTMLCanvasElement>) => {
-        const { transform, curPoint } = props;
+        const { transform, rotation, curPoint } = props;
         e.preventDefault();
 
         const newton = getNewtonSync();
         if (!newton) return;
 
-        const _transform = canvasToUnitTransform(transform.value);
-        const curPt = applyTransforms(e.nativeEvent.offsetX, e.nativeEvent.offsetY,
-            scale(getCanvasSize() / e.currentTarget.clientWidth),   // Scale up to the internal canvas size
-            toCanvasCenterOrigin(),
-            _transform
-        );
+        const { width, height, clientWidth } = e.currentTarget;
+        const viewport = toViewport(transform.value, rotation.value, width, height);
+        const pixelScale = width / clientWidth;   // Scale up to the internal canvas size
+        const curPt = pixelToComplex(viewport, e.nativeEvent.offsetX * pixelScale, e.nativeEvent.offsetY * pixelScale);
 
-        const curPtStr = `${curPt.x.toFixed(5)} ${curPt.y < 0 ? '-' : '+'} ${Math.abs(curPt.y).toFixed(5)}i`;
+        const curPtStr = `${curPt.re.toFixed(5)} ${curPt.im < 0 ? '-' : '+'} ${Math.abs(curPt.im).toFixed(5)}i`;
         curPoint.value = curPtStr;
+        drawOrbit(orbitCanvasRef.current, devalue(props), curPt);
 
         if (!(e.buttons & 1)) return;
 
-        transform.value.translate = applyTransforms(-e.movementX, -e.movementY, _transform);
+        // Dragging moves the center against the mouse, in whatever direction the view is rotated to
+        const center = { x: width / 2, y: height / 2 };
+        const newCenter = pixelToComplex(viewport, center.x - e.movementX * pixelScale, center.y - e.movementY * pixelScale);
+        transform.value.translate = { x: newCenter.re, y: newCenter.im };
     }
 
     const onMouseLeave = (_e: MouseEvent<HTMLCanvasElement>) => {
         props.curPoint.value = "";
+        drawOrbit(orbitCanvasRef.current, devalue(props));
     }
 
     return { onMouseMove, onMouseLeave };
 }
 
+const drawOrbit = (canvas: HTMLCanvasElement | null, props: AppGeneralPropsRaw, z0?: Complex) => {
+    const context = canvas?.getContext('2d');
+    if (!context) return;
+
+    context.clearRect(0, 0, context.canvas.width, context.canvas.height);
+    if (!z0 || !props.showOrbit) return;
+
+    const fz = newPolynomial(props.formula);
+    if (!fz) return;
+    const steps = orbit(fz, props.iterMethod, z0);
+    fz.free();
+
+    const viewport = toViewport(props.transform, props.rotation, context.canvas.width, context.canvas.height);
+    const points = steps.map(({ z }) => complexToPixel(viewport, z));
+
+    context.lineWidth = 2;
+    context.strokeStyle = 'white';
+    context.beginPath();
+    points.forEach(({ x, y }) => { context.lineTo(x, y); });
+    context.stroke();
+
+    context.fillStyle = 'white';
+    points.forEach(({ x, y }) => {
+        context.beginPath();
+        context.arc(x, y, 3, 0, 2 * Math.PI);
+        context.fill();
+    });
+}
+
 export function useAnimatedCanvas(drawFn: CanvasDrawFn) {
     const canvasRef = useRef<HTMLCanvasElement>(null);
 
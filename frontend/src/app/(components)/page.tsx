@@ -8,6 +8,7 @@ import { Canvas } from './canvas';
 import { AppGeneralProps, useAppOnKeyDown, useAppProps } from './app-props';
 import { StateMachineProps } from '../(state-machine)/state-machine';
 import { devalue } from '../(util)/valued';
+import { applySceneFromUrl } from './scene';
 
 export default function Home() {
     const { generalProps, stateMachine } = useAppProps();
@@ -23,7 +24,7 @@ export default function Home() {
         <main className={styles.main}>
             <div className={styles.appContainer}>
                 <div className={styles.settingsStatusContainer}>
-                    <Settings {...generalProps} />
+                    <Settings {...generalProps} getSession={() => stateMachine.data.current?.session} />
                     <Status {...generalProps} />
                 </div>
                 <Canvas props={generalProps} drawFn={stateMachine.stepFn} />
@@ -37,6 +38,7 @@ const useInitializePage = (generalProps: AppGeneralProps, stateMachine: StateMac
         console.clear();
         await initWasmNewtonAsync();
         // console.log('WASM has initialized');
+        applySceneFromUrl(generalProps);
         stateMachine.initFns.calculateNewPassFn(devalue(generalProps), stateMachine);
         generalProps.isRendering.value = true;
     });
@@ -0,0 +1,165 @@
+import { AppGeneralProps, AppGeneralPropsRaw } from "./app-props";
+import { newTransform } from "../(util)/transform";
+import { LightnessMode, NonConvergence, Scene, SceneChaos, SceneLightness } from "../(wasm-wrapper)/structs";
+import {
+    decodeScene, encodeScene, isValidLightnessCurve, parseScene, pngScene, polynomialRoots, sceneToJson,
+} from "../(wasm-wrapper)/wrapper";
+import { RenderSession } from "@/pkg/newton_wasm";
+import { calcRootColors } from "../(state-machine)/render";
+import { getIterMethodsSync } from "../(wasm-wrapper)/consts";
+
+///////////////////////////////////////////////////////////////////
+
+// Scenes are the files the CLI renders, so a picture can be set up here and rendered at any size there, or
+// the other way around
+
+const SCENE_VERSION = 1;
+
+export const sceneFromProps = (props: AppGeneralPropsRaw): Scene => {
+    const { formula, iterMethod, transform, rotation, lightnessMode, lightnessCurve, dropoff, nonConvergence } = props;
+
+    return {
+        version: SCENE_VERSION,
+        formula,
+        method: iterMethod,
+        params: [],
+        view: {
+            center: [transform.translate.x, transform.translate.y],
+            scale: transform.scale,
+            rotation,
+            shear: 0,
+        },
+        coloring: {
+            lightness: isValidLightnessCurve(lightnessCurve) ? lightnessCurve : toSceneLightness(lightnessMode),
+            dropoff,
+            chaos: toSceneChaos(nonConvergence),
+        },
+        // The color scheme lives here, not in the scene, so the roots' colors are written out as they are
+        rootColors: calcRootColors(props, polynomialRoots(formula)),
+    };
+}
+
+// Throws for scenes the web app can't show as they are, rather than showing something else
+export const applyScene = (props: AppGeneralProps, scene: Scene) => {
+    checkSceneSupported(scene);
+
+    const {
+        formula, iterMethod, curPoint, transform, rotation, lightnessMode, lightnessCurve, dropoff, nonConvergence,
+        rootColors,
+    } = props;
+    const { view, coloring } = scene;
+
+    curPoint.value = "";
+    formula.value = scene.formula;
+    iterMethod.value = scene.method;
+    transform.value = newTransform(view.scale, view.center[0], view.center[1]);
+    rotation.value = view.rotation;
+    // Anything but a mode's name is a curve, which leaves the mode as it is
+    const mode = fromSceneLightness(coloring.lightness);
+    lightnessMode.value = mode ?? lightnessMode.value;
+    lightnessCurve.value = mode ? "" : coloring.lightness;
+    dropoff.value = coloring.dropoff;
+    nonConvergence.value = fromSceneChaos(coloring.chaos);
+    rootColors.value = scene.rootColors;
+}
+
+export const saveSceneFile = (props: AppGeneralPropsRaw) => {
+    const json = sceneToJson(sceneFromProps(props));
+    downloadBlob(new Blob([json], { type: "application/json" }), "newton-scene.json");
+}
+
+// The PNG carries its scene, so opening it again restores the view it was exported from
+export const exportPngFile = (session: RenderSession) => {
+    const png = session.exportPng();
+    downloadBlob(new Blob([png], { type: "image/png" }), "newton.png");
+}
+
+// Takes scene files, and PNGs exported from here or rendered by the CLI
+export const openSceneFile = async (props: AppGeneralProps, file: File) => {
+    const scene = file.type == "image/png" || file.name.toLowerCase().endsWith(".png")
+        ? pngScene(new Uint8Array(await file.arrayBuffer()))
+        : parseScene(await file.text());
+    applyScene(props, scene);
+}
+
+const downloadBlob = (blob: Blob, fileName: string) => {
+    const url = URL.createObjectURL(blob);
+    const link = document.createElement("a");
+    link.href = url;
+    link.download = fileName;
+    link.click();
+    URL.revokeObjectURL(url);
+}
+
+// Share links carry the whole scene in the URL's fragment, so they never reach a server
+const SCENE_HASH_KEY = "scene";
+
+export const sceneUrl = (props: AppGeneralPropsRaw): string => {
+    const url = new URL(window.location.href);
+    url.hash = `${SCENE_HASH_KEY}=${encodeScene(sceneFromProps(props))}`;
+    return url.toString();
+}
+
+// Returns false if the page's URL has no scene in it, or one that can't be rendered
+export const applySceneFromUrl = (props: AppGeneralProps): boolean => {
+    const code = new URLSearchParams(window.location.hash.slice(1)).get(SCENE_HASH_KEY);
+    if (!code) return false;
+
+    try {
+        applyScene(props, decodeScene(code));
+        return true;
+    } catch (err) {
+        console.error(err);
+        return false;
+    }
+}
+
+///////////////////////////////////////////////////////////////////
+
+// The web app has no shear, and always uses the method's default parameters
+const checkSceneSupported = (scene: Scene) => {
+    if (scene.view.shear != 0) {
+        throw new Error(`The scene has a shear of ${scene.view.shear}, which only the CLI can render`);
+    }
+    if (scene.coloring.palette) {
+        throw new Error("The scene colors roots with a gradient palette, which only the CLI can render");
+    }
+
+    const method = getIterMethodsSync().find(method => method.id == scene.method);
+    const defaults = method?.params.map(param => param.default) ?? [];
+    const isDefault = scene.params.length == 0
+        || (scene.params.length == defaults.length && scene.params.every((param, i) => param == defaults[i]));
+    if (!isDefault) {
+        throw new Error(`The scene sets ${method?.name ?? "the method"}'s parameters, which only the CLI can render`);
+    }
+}
+
+const toSceneLightness = (lightnessMode: LightnessMode): SceneLightness => {
+    switch (lightnessMode) {
+        case LightnessMode.Normal: return "normal";
+        case LightnessMode.Inverted: return "inverted";
+        case LightnessMode.Parabola: return "parabola";
+        case LightnessMode.InvertedParabola: return "inverted-parabola";
+        default:
+            const mode: never = lightnessMode;
+            throw new Error(`lightnessMode ${mode} not accounted for`);
+    }
+}
+
+const fromSceneLightness = (lightness: SceneLightness): LightnessMode | undefined => {
+    switch (lightness) {
+        case "normal": return LightnessMode.Normal;
+        case "inverted": return LightnessMode.Inverted;
+        case "parabola": return LightnessMode.Parabola;
+        case "inverted-parabola": return LightnessMode.InvertedParabola;
+        default: return undefined;
+    }
+}
+
+const toSceneChaos = (nonConvergence: NonConvergence): SceneChaos => {
+    return nonConvergence == NonConvergence.Black ? "black" : "white";
+}
+
+const fromSceneChaos = (chaos: SceneChaos): NonConvergence => {
+    return chaos == "black" ? NonConvergence.Black : NonConvergence.White;
+}
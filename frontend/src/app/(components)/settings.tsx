@@ -6,9 +6,14 @@ import { transformIdent } from '../(util)/transform';
 import { useValue } from '../(util)/valued';
 import { usePeriodicFn } from '../(util)/periodic-fn';
 import { randomCycle2, randomFormula } from './random-formulas';
-import { IterRootMethod, LightnessMode, NonConvergence } from '../(wasm-wrapper)/structs';
+import { LightnessMode, NonConvergence, Shading } from '../(wasm-wrapper)/structs';
+import { getIterMethodsSync } from '../(wasm-wrapper)/consts';
 import { AppGeneralProps } from './app-props';
 import { ColorScheme } from '../(state-machine)/render';
+import { exportPngFile, openSceneFile, saveSceneFile, sceneUrl } from './scene';
+import { isValidLightnessCurve } from '../(wasm-wrapper)/wrapper';
+import { RenderSession } from '@/pkg/newton_wasm';
+import { devalue } from '../(util)/valued';
 
 enum SettingsPanel {
     RENDERING = "Rendering",
@@ -16,7 +21,10 @@ enum SettingsPanel {
     DEBUG = "Debug",
 }
 
-export const Settings = (props: AppGeneralProps) => {
+// The session is only there while something's been rendered, so it's looked up when it's needed
+export type SettingsProps = AppGeneralProps & { getSession: () => RenderSession | undefined };
+
+export const Settings = (props: SettingsProps) => {
     const settingsPanel = useValue(SettingsPanel.RENDERING);
 
     const onChangeSettingsPanel = (panel: SettingsPanel) => (_e: MouseEvent<HTMLDivElement>) => { settingsPanel.value = panel; };
@@ -43,12 +51,14 @@ export const Settings = (props: AppGeneralProps) => {
 
 ///////////////////////////////////////////////////////////////////
 
-const RenderSettings = (props: AppGeneralProps) => {
+const RenderSettings = (props: SettingsProps) => {
     return (
         <div className={styles.renderSettingsContainer}>
             <FormulaSettings {...props} />
             <hr className={styles.settingsDivider} />
             <RenderPassSettings {...props} />
+            <hr className={styles.settingsDivider} />
+            <SceneSettings {...props} />
         </div>
     )
 }
@@ -56,13 +66,14 @@ const RenderSettings = (props: AppGeneralProps) => {
 ///////////////////////////////////////////////////////////////////
 
 const FormulaSettings = (props: AppGeneralProps) => {
-    const { formula, iterMethod, curPoint, transform } = props;
+    const { formula, iterMethod, curPoint, transform, rotation, rootColors } = props;
     const customRef = useRef<HTMLInputElement>(null);
     const isCustomFormula = useValue(false);
 
     const resetTransform = () => {
         curPoint.value = "";
         transform.value = transformIdent();
+        rootColors.value = undefined;
     }
 
     const onChangeFormula = (e: ChangeEvent<HTMLSelectElement | HTMLInputElement>) => {
@@ -71,7 +82,11 @@ const FormulaSettings = (props: AppGeneralProps) => {
     };
 
     const onChangeIterMethod = (e: ChangeEvent<HTMLSelectElement>) => {
-        iterMethod.value = e.target.value as IterRootMethod;
+        iterMethod.value = Number.parseInt(e.target.value);
+    };
+
+    const onChangeRotation = (e: ChangeEvent<HTMLInputElement>) => {
+        rotation.value = Number.parseFloat(e.target.value);
     };
 
     const onFocus = useCallback(() => {
@@ -149,8 +164,14 @@ const FormulaSettings = (props: AppGeneralProps) => {
 
             <label>Algorithm:</label>
             <select className={styles.iterMethod} value={iterMethod.value} title={desc.iterMethod} onChange={onChangeIterMethod}>
-                {Object.entries(IterRootMethod).map(([k, v]) => <option key={k} value={v}>{v}</option>)}
+                {getIterMethodsSync().map(m => <option key={m.id} value={m.id} title={m.formula}>{m.name}</option>)}
             </select>
+
+            <label>Rotation:</label>
+            <input className={styles.rotation} type="range" min="-180" max="180" step="1"
+                value={rotation.value}
+                title={desc.rotation}
+                onChange={onChangeRotation} />
         </div>
     );
 }
@@ -158,16 +179,20 @@ const FormulaSettings = (props: AppGeneralProps) => {
 ///////////////////////////////////////////////////////////////////
 
 const RenderPassSettings = (props: AppGeneralProps) => {
-    const { lightnessMode, nonConvergence, colorScheme, hueOffset, chromaticity, dropoff, renderRoots, staticHues } = props;
+    const { lightnessMode, lightnessCurve, shading, nonConvergence, colorScheme, hueOffset, chromaticity, dropoff, renderRoots, staticHues, showOrbit, rootColors } = props;
 
     const onChangeLightnessMode = (e: ChangeEvent<HTMLSelectElement>) => { lightnessMode.value = e.target.value as LightnessMode; }
+    const onChangeLightnessCurve = (e: ChangeEvent<HTMLInputElement>) => { lightnessCurve.value = e.target.value; }
+    const onChangeShading = (e: ChangeEvent<HTMLSelectElement>) => { shading.value = e.target.value as Shading; }
     const onChangeNonConvergence = (e: ChangeEvent<HTMLSelectElement>) => { nonConvergence.value = e.target.value as NonConvergence; }
-    const onChangeScheme = (e: ChangeEvent<HTMLSelectElement>) => { colorScheme.value = e.target.value as ColorScheme; }
-    const onChangeHueOffset = (e: ChangeEvent<HTMLInputElement>) => { hueOffset.value = Number.parseFloat(e.target.value); }
-    const onChangeChromaticity = (e: ChangeEvent<HTMLInputElement>) => { chromaticity.value = Number.parseFloat(e.target.value); }
+    // Any of the color scheme's controls takes over from colors a scene brought in
+    const onChangeScheme = (e: ChangeEvent<HTMLSelectElement>) => { colorScheme.value = e.target.value as ColorScheme; rootColors.value = undefined; }
+    const onChangeHueOffset = (e: ChangeEvent<HTMLInputElement>) => { hueOffset.value = Number.parseFloat(e.target.value); rootColors.value = undefined; }
+    const onChangeChromaticity = (e: ChangeEvent<HTMLInputElement>) => { chromaticity.value = Number.parseFloat(e.target.value); rootColors.value = undefined; }
     const onChangeDropoff = (e: ChangeEvent<HTMLInputElement>) => { dropoff.value = Number.parseFloat(e.target.value); }
     const onChangeDrawRoots = (e: ChangeEvent<HTMLInputElement>) => { renderRoots.value = e.target.checked; }
-    const onChangeStaticHues = (e: ChangeEvent<HTMLInputElement>) => { staticHues.value = e.target.checked; }
+    const onChangeStaticHues = (e: ChangeEvent<HTMLInputElement>) => { staticHues.value = e.target.checked; rootColors.value = undefined; }
+    const onChangeShowOrbit = (e: ChangeEvent<HTMLInputElement>) => { showOrbit.value = e.target.checked; }
 
     return (
         <div className={styles.renderPassSettings}>
@@ -175,6 +200,18 @@ const RenderPassSettings = (props: AppGeneralProps) => {
             <select value={lightnessMode.value} title={desc.lightnessMode} onChange={onChangeLightnessMode}>
                 {Object.entries(LightnessMode).map(([k, v]) => <option key={k} value={v}>{v}</option>)}
             </select>
+            <label>Lightness Curve:</label>
+            <input
+                className={lightnessCurve.value && !isValidLightnessCurve(lightnessCurve.value) ? styles.badLightnessCurve : ''}
+                type="text"
+                placeholder="gamma 2.2"
+                value={lightnessCurve.value}
+                title={desc.lightnessCurve}
+                onChange={onChangeLightnessCurve} />
+            <label>Shading:</label>
+            <select value={shading.value} title={desc.shading} onChange={onChangeShading}>
+                {Object.entries(Shading).map(([k, v]) => <option key={k} value={v}>{v}</option>)}
+            </select>
             <label>Chaos Color:</label>
             <select value={nonConvergence.value} title={desc.nonConvergence} onChange={onChangeNonConvergence}>
                 {Object.entries(NonConvergence).map(([k, v]) => <option key={k} value={v}>{v}</option>)}
@@ -208,6 +245,62 @@ const RenderPassSettings = (props: AppGeneralProps) => {
             <input type="checkbox" checked={renderRoots.value} title={desc.showRoots} onChange={onChangeDrawRoots} />
             <label>Static Hues:</label>
             <input type="checkbox" checked={staticHues.value} title={desc.staticHues} onChange={onChangeStaticHues} />
+            <label>Show Orbit:</label>
+            <input type="checkbox" checked={showOrbit.value} title={desc.showOrbit} onChange={onChangeShowOrbit} />
+        </div>
+    )
+}
+
+///////////////////////////////////////////////////////////////////
+
+const SceneSettings = (props: SettingsProps) => {
+    const { getSession, ...generalProps } = props;
+    const { isRendering } = generalProps;
+    const fileRef = useRef<HTMLInputElement>(null);
+
+    const onClickSave = () => {
+        try {
+            saveSceneFile(devalue(generalProps));
+        } catch (err) {
+            alert(`Couldn't save the scene: ${String(err)}`);
+        }
+    }
+
+    const onClickShare = () => {
+        try {
+            const url = sceneUrl(devalue(generalProps));
+            window.history.replaceState(null, "", url);
+            navigator.clipboard.writeText(url).catch(() => prompt("Copy this link:", url));
+        } catch (err) {
+            alert(`Couldn't share the scene: ${String(err)}`);
+        }
+    }
+
+    const onClickExportPng = () => {
+        const session = getSession();
+        if (!session) return;
+        try {
+            exportPngFile(session);
+        } catch (err) {
+            alert(`Couldn't export the image: ${String(err)}`);
+        }
+    }
+
+    const onChangeFile = (e: ChangeEvent<HTMLInputElement>) => {
+        const file = e.target.files?.[0];
+        e.target.value = "";
+        if (!file) return;
+        openSceneFile(generalProps, file).catch((err: unknown) => alert(`Couldn't open ${file.name}: ${String(err)}`));
+    }
+
+    return (
+        <div className={styles.sceneSettings}>
+            <label>Scene:</label>
+            <button title={desc.openScene} onClick={() => fileRef.current?.click()}>Open</button>
+            <button title={desc.saveScene} onClick={onClickSave}>Save</button>
+            <button title={desc.shareScene} onClick={onClickShare}>Share</button>
+            <button title={desc.exportPng} disabled={isRendering.value} onClick={onClickExportPng}>PNG</button>
+            <input ref={fileRef} type="file" accept=".json,.png,application/json,image/png" hidden onChange={onChangeFile} />
         </div>
     )
 }
@@ -260,7 +353,8 @@ const desc = {
     formula: 'Pre-screened interesting polynomial formulas',
     custom: 'Enter in any integer-coefficient polynomial',
     iterMethod: 'Change the root iteration algorithm (see README.md link in Info tab)',
-    randomCycle2: 'Attempt to generate a 2-Cycle super-attracting function',
+    rotation: 'Rotate the view about its center, eg. to line up a symmetry axis with the screen',
+    randomCycle2: 'Generate a 5th degree function with a super-attracting 2-cycle',
     randomFormula: 'Generate random coefficients',
     colorScheme: 'Change how the polynomial roots are colored',
     hueOffset: 'Shift the color scheme for polynomial roots along the color wheel',
@@ -268,8 +362,15 @@ const desc = {
     shadingCurve: 'Change the level of exponential falloff for the shading algorithm',
     showRoots: 'Draw a circle around the roots of the polynomial in the complex plane',
     staticHues: 'Color hues always start at 0°, instead of the first root\'s complex argument',
+    showOrbit: 'Trace the path the point under the mouse takes on its way to a root',
     lightnessMode: 'Changes the way chaotic & stable regions render lightness',
+    lightnessCurve: 'Replaces the lightness mode with a curve from convergence speed to lightness: "gamma 2.2", "points 0,0 0.5,0.8 1,1", "bezier 0.4 0 0.2 1" or "expr 1 - (1 - v)^3"',
+    shading: 'What sets the lightness: the iteration count, or how close to the root a point ended up. The last two keep more data per pixel',
     nonConvergence: 'Points that do not converge to any root, should be this color',
+    openScene: 'Load a scene file, or a PNG exported from here or rendered by the command line renderer',
+    exportPng: 'Download the finished image as a PNG, with its scene embedded so it can be opened again',
+    saveScene: 'Download the formula, view and colors as a scene file, which the command line renderer can also render',
+    shareScene: 'Copy a link that opens this exact view, with its formula, algorithm and colors',
 }
 
 export const defaultPolynomials = [
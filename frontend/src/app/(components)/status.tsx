@@ -1,14 +1,25 @@
 import styles from './page.module.css';
+import { useMemo } from 'react';
 import { AppGeneralProps } from './app-props';
+import { getNewtonSync } from '../(wasm-wrapper)/consts';
+import { isValidFormula } from '../(wasm-wrapper)/util';
+import { attractingCyclePeriods } from '../(wasm-wrapper)/wrapper';
 
 export const Status = (props: AppGeneralProps) => {
-    const { isRendering, curPoint } = props;
+    const { isRendering, curPoint, formula, iterMethod } = props;
 
     const renderStyle = isRendering.value ? styles.isRendering : styles.notRendering;
 
+    const isLoaded = !!getNewtonSync();
+    const cyclePeriods = useMemo(() => {
+        if (!isLoaded || !isValidFormula(formula.value)) return [];
+        return attractingCyclePeriods(formula.value, iterMethod.value);
+    }, [isLoaded, formula.value, iterMethod.value]);
+
     return (
         <div className={styles.status}>
             <label>{curPoint.value}&nbsp;</label>
+            {cyclePeriods.length > 0 && <label title={desc.attractingCycle}>Attracting cycle: period {cyclePeriods.join(", ")}</label>}
             <div className={styles.frameRate}>
                 {isRendering.value && <label>Rendering...</label>}
                 <div className={renderStyle} />
@@ -16,3 +27,7 @@ export const Status = (props: AppGeneralProps) => {
         </div>
     );
 }
+
+const desc = {
+    attractingCycle: 'A critical point of the iteration falls into a cycle instead of a root, so some regions never converge',
+}
@@ -1,14 +1,15 @@
-import { PixelDataBuffer, Polynomial, Roots } from "@/pkg/newton_wasm";
-import { newPolynomial, newRoots } from "../(wasm-wrapper)/structs";
+import { RenderSession } from "@/pkg/newton_wasm";
+import { Shading, getLightnessMode, getNonConvergence, getShading, newRenderSession } from "../(wasm-wrapper)/structs";
 import { setRootColors } from "./render";
-import { newImagePixelDataBuffer } from "../(wasm-wrapper)/wrapper";
+import { getCanvasSize } from "../(wasm-wrapper)/consts";
+import { toViewport } from "../(wasm-wrapper)/transform";
+import { isValidLightnessCurve } from "../(wasm-wrapper)/wrapper";
 import { AppGeneralPropsRaw } from "../(components)/app-props";
 
 ///////////////////////////////////////////////////////////////////
 
 export enum State {
     RENDER_PASS,
-    RECOLOR_PASS,
     DONE,
 }
 
@@ -18,8 +19,8 @@ export interface RenderStateData {
 
     state: State,
     generalProps: AppGeneralPropsRaw,
-    renderData?: RenderData,
-    fractalData?: FractalData,
+    // Owns the polynomial, roots, pixel data and image, and knows what a change of props needs redoing
+    session?: RenderSession,
 }
 
 export interface StateMachineFns {
@@ -28,18 +29,6 @@ export interface StateMachineFns {
     postPassFn: RenderPassFn,
 }
 
-export interface RenderData {
-    startTime: number,
-    row: number,
-    scaleFactor: number,
-}
-
-export interface FractalData {
-    fz: Polynomial,
-    roots: Roots,
-    pdb: PixelDataBuffer,
-}
-
 ///////////////////////////////////////////////////////////////////
 
 export const isRenderStateFinishedRendering = (data: RenderStateData): boolean => {
@@ -50,37 +39,33 @@ export const setRenderStateFinishedRendering = (data: RenderStateData) => {
     data.generalProps.isRendering = false;
 }
 
-export const newRenderData = (scaleFactor = 6): RenderData => ({
-    startTime: Date.now(),
-    row: 0,
-    scaleFactor,
-});
-
-export const resetFractalData = (
-    fractalData: FractalData | undefined, generalProps: AppGeneralPropsRaw, recalculate: boolean
-): FractalData | undefined => {
-    const { formula } = generalProps;
-
-    let fz = fractalData?.fz;
-    let roots = fractalData?.roots;
-    let pdb = fractalData?.pdb;
-
-    if (!fractalData || recalculate) {
-        fz?.free();
-        roots?.free();
-        pdb?.free();
-        fz = newPolynomial(formula) ?? undefined;
-        roots = newRoots(fz) ?? undefined;
-        pdb = newImagePixelDataBuffer();
-    }
-
-    if (!fz || !roots || !pdb) {
-        fz?.free();
-        roots?.free();
-        pdb?.free();
+// Brings the session in line with the props, creating it if there isn't one yet. Returns undefined,
+// freeing the old session, if the props can't be rendered.
+export const syncSession = (
+    session: RenderSession | undefined, generalProps: AppGeneralPropsRaw
+): RenderSession | undefined => {
+    const { formula, transform, rotation, iterMethod, dropoff, lightnessMode, lightnessCurve, shading, nonConvergence } = generalProps;
+    const viewport = toViewport(transform, rotation, getCanvasSize(), getCanvasSize());
+
+    try {
+        session ??= newRenderSession(formula, viewport) ?? undefined;
+        if (!session) return undefined;
+
+        session.setFormula(formula);
+        session.setViewport(viewport);
+        // An empty parameter list selects the method's defaults
+        session.setMethod(iterMethod, new Float32Array());
+        setRootColors(generalProps, session);
+        session.setColoring(dropoff, getLightnessMode(lightnessMode), getNonConvergence(nonConvergence));
+        // Half typed curves leave the mode in charge
+        session.setLightnessCurve(isValidLightnessCurve(lightnessCurve) ? lightnessCurve : undefined);
+        // Only worth the memory when the shading reads them
+        session.setRecords(shading != Shading.Iterations);
+        session.setShading(getShading(shading));
+        return session;
+    } catch (err) {
+        console.error(err);
+        session?.free();
         return undefined;
     }
-
-    setRootColors(generalProps, roots);
-    return { fz, roots, pdb }
 }
@@ -1,42 +1,49 @@
 import assert from "assert";
-import { applyTransforms, invert, transformMany } from "../(util)/transform";
-import { canvasToUnitTransform, toCanvasCenterOrigin } from "../(wasm-wrapper)/transform";
-import { OklchColor, } from "../(wasm-wrapper)/structs";
-import { calculateRow, recolorRow, renderRow } from "../(wasm-wrapper)/wrapper";
+import { toViewport } from "../(wasm-wrapper)/transform";
+import { Complex, OklchColor, } from "../(wasm-wrapper)/structs";
+import { complexToPixel, schemeColors } from "../(wasm-wrapper)/wrapper";
+import { getThreadCount } from "../(wasm-wrapper)/consts";
+import { calculateOnWorker, hasCalcWorker } from "../(wasm-wrapper)/threads";
 import { RenderStateData } from "./data";
-import { Roots } from "@/pkg/newton_wasm";
-import { lerpClamped, toSplitArray } from "../(util)/util";
+import { RenderSession } from "@/pkg/newton_wasm";
 import { AppGeneralPropsRaw } from "../(components)/app-props";
 
-export const renderToCanvasRow = (data: RenderStateData, context: CanvasRenderingContext2D) => {
-    assert(!!data.renderData && !!data.fractalData);
+// Recolors the whole canvas if only the coloring changed, otherwise calculates the session's next pass
+// and draws the rows it changed. Returns false once the render is done.
+export const renderNextPass = (data: RenderStateData, context: CanvasRenderingContext2D): boolean => {
+    assert(!!data.session);
+
+    const { session } = data;
+    // Rows the threaded build's calculation worker has finished
+    const finishedRows = session.finishRows();
+    if (finishedRows) {
+        session.draw(context, finishedRows[0], finishedRows[1]);
+        return true;
+    }
 
-    const { transform, iterMethod, dropoff, lightnessMode, nonConvergence } = data.generalProps;
-    const { row, scaleFactor } = data.renderData;
-    const { fz, roots, pdb } = data.fractalData;
-    const pdbRow = calculateRow(fz, roots, transform, iterMethod, 1 << scaleFactor, row);
-    renderRow(context, roots, pdb, pdbRow, 1 << scaleFactor, row, dropoff, lightnessMode, nonConvergence);
-    pdbRow.free();
-}
+    if (hasCalcWorker() && !session.needsRecolor()) {
+        const handle = session.startRows(getThreadCount());
+        if (handle === undefined) return false;
+        calculateOnWorker(handle);
+        return true;
+    }
 
-export const recolorCanvasRow = (data: RenderStateData, context: CanvasRenderingContext2D) => {
-    assert(!!data.renderData && !!data.fractalData);
+    const rows = session.nextPass(getThreadCount());
+    if (!rows) return false;
 
-    const { dropoff, lightnessMode, nonConvergence } = data.generalProps;
-    const { row } = data.renderData;
-    const { roots, pdb } = data.fractalData;
-    recolorRow(context, roots, pdb, row, dropoff, lightnessMode, nonConvergence);
+    session.draw(context, rows[0], rows[1]);
+    return true;
 }
 
 export const drawRoots = (data: RenderStateData, context: CanvasRenderingContext2D) => {
-    if (!data.fractalData) return;
+    if (!data.session) return;
 
-    const { transform } = data.generalProps;
-    const { roots } = data.fractalData;
+    const { transform, rotation } = data.generalProps;
+    const roots = data.session.roots();
 
-    const _transform = invert(transformMany(toCanvasCenterOrigin(), canvasToUnitTransform(transform)));
-    for (const root of roots.roots()) {
-        const { x, y } = applyTransforms(root.re, root.im, _transform);
+    const viewport = toViewport(transform, rotation, context.canvas.width, context.canvas.height);
+    for (const root of roots) {
+        const { x, y } = complexToPixel(viewport, root);
 
         context.strokeStyle = 'black';
         context.beginPath();
@@ -54,115 +61,42 @@ export const drawRoots = (data: RenderStateData, context: CanvasRenderingContext
     }
 }
 
-interface ColorInfo {
-    rootIndex: number,
-    color: OklchColor,
-    radius: number,
-}
-
 export enum ColorScheme {
     CONTRASTING_HUES = "Contrasting Hues",
     LINEAR_HUES = "Linear Hues",
-    MONOCHROMATIC = "Monochromatic"
+    MONOCHROMATIC = "Monochromatic",
+    GOLDEN_ANGLE = "Golden Angle",
+    EQUIDISTANT_HUES = "Equidistant Hues",
 }
 
-export const setRootColors = (generalProps: AppGeneralPropsRaw, roots?: Roots) => {
-    const { colorScheme, hueOffset, chromaticity, staticHues } = generalProps;
-    if (!roots) return;
-
-    const complexRoots = roots.roots();
-    const radii = complexRoots.map(c => Math.hypot(c.re, c.im));
-    const colorInfo: ColorInfo[] = complexRoots.map((c, i) => ({
-        rootIndex: i,
-        radius: radii[i],
-        color: {
-            h: Math.atan2(c.im, c.re) * 180 / Math.PI,
-            c: lerpClamped(radii[i] / 1.5, 0.01, 0.4 * chromaticity),
-        }
-    }));
-
-    let info: ColorInfo[];
+// Ids of `ColorScheme` in newton_core
+const getColorScheme = (colorScheme: ColorScheme): number | undefined => {
     switch (colorScheme) {
-        case ColorScheme.LINEAR_HUES:
-            info = linearHueColors(colorInfo, staticHues);
-            break;
-        case ColorScheme.CONTRASTING_HUES:
-            info = contrastingHueColors(colorInfo, staticHues);
-            break;
-        case ColorScheme.MONOCHROMATIC:
-            info = monochromaticColors(colorInfo, staticHues);
-            break;
+        case ColorScheme.CONTRASTING_HUES: return 0;
+        case ColorScheme.LINEAR_HUES: return 1;
+        case ColorScheme.MONOCHROMATIC: return 2;
+        case ColorScheme.GOLDEN_ANGLE: return 3;
+        case ColorScheme.EQUIDISTANT_HUES: return 4;
         default:
             const _colorScheme: never = colorScheme;
             console.error("Invalid colorScheme:", _colorScheme);
-            return;
+            return undefined;
     }
-
-    info.forEach(i => { i.color.h += hueOffset; });
-    info.sort((a, b) => a.rootIndex - b.rootIndex);
-
-    const colors = info.map(c => c.color);
-    roots.setColors(colors);
-}
-
-const normalizedAngle = (h: number, angleEpsilon?: number) => {
-    const epsilon = angleEpsilon ?? 0;
-    return (h + 360 + epsilon) % 360 - epsilon;
 }
 
-const normalizeSortColorInfo = (_colorInfo: ColorInfo[]): ColorInfo[] => {
-    const minAngleDelta = 5;
-
-    const sortFn = (a: ColorInfo, b: ColorInfo) => {
-        const ah = normalizedAngle(a.color.h, minAngleDelta);
-        const bh = normalizedAngle(b.color.h, minAngleDelta);
-        const angleDelta = ah - bh;
-        const radiusDelta = a.radius - b.radius;
-        return (Math.abs(angleDelta) > minAngleDelta) ? angleDelta : radiusDelta;
-    };
-
-    const [shortInfo, colorInfo] = toSplitArray(_colorInfo, c => c.radius < 0.1);
-    shortInfo.sort(sortFn);
-    colorInfo.sort(sortFn);
-
-    return colorInfo.concat(shortInfo);
+export const setRootColors = (generalProps: AppGeneralPropsRaw, session: RenderSession) => {
+    const colors = calcRootColors(generalProps, session.roots());
+    if (!colors) return;
+    session.setRootColors(colors);
 }
 
-const monochromaticColors = (_info: ColorInfo[], staticHues: boolean) => {
-    const info = normalizeSortColorInfo(_info);
-    info.forEach(c => c.color.h = info[0].color.h * (staticHues ? 0 : 1));
-    return info;
-}
+// Colors from a loaded scene win over the color scheme, as long as they're for the same number of roots.
+// The schemes themselves live in newton_core, so the CLI colors roots the same way.
+export const calcRootColors = (generalProps: AppGeneralPropsRaw, complexRoots: Complex[]): OklchColor[] | undefined => {
+    const { colorScheme, hueOffset, chromaticity, staticHues, rootColors } = generalProps;
+    if (rootColors?.length == complexRoots.length) return rootColors;
 
-const linearHueColors = (_info: ColorInfo[], staticHues: boolean) => {
-    const info = normalizeSortColorInfo(_info);
-    const initialAngle = info[0].color.h;
-    const angleAmount = 360 / info.length;
-    info.forEach((info, i) => { info.color.h = initialAngle * (staticHues ? 0 : 1) + i * angleAmount });
-    return info;
+    const scheme = getColorScheme(colorScheme);
+    if (scheme === undefined) return undefined;
+    return schemeColors(complexRoots, scheme, hueOffset, chromaticity, staticHues);
 }
-
-const contrastingHueColors = (_info: ColorInfo[], staticHues: boolean) => {
-    const info = normalizeSortColorInfo(_info);
-    const initialAngle = info[0].color.h;
-    const angleAmount = 360 / info.length;
-
-    // The goal of this algorithm is to spread out the colors as much as possible on the hue spectrum.
-    // We also want to maximize the distance between adjacent nodes, so this basically boils down to the
-    // following problem: Given an array [0..n-1], what permutation maximizes the closest-distance
-    // between adjacent elements, modulo-n? This algorithm permutes the array as needed to maximize
-    // the adjacent differences.
-    const n = info.length;
-    let allIndices: number[];
-    if (!(info.length & 1)) {
-        const indices = [...Array(info.length / 2).keys()].map(i => i * (n / 2 + 1));
-        allIndices = [...indices, ...indices.map(v => (n * n + 2 * n - 4) / 4 - v)];
-    } else {
-        allIndices = [...Array(info.length).keys()].map(i => i * (n - 1) / 2);
-    }
-    allIndices = allIndices.map(v => v % n);
-    info.forEach((info, i) => { info.color.h = initialAngle * (staticHues ? 0 : 1) + allIndices[i] * angleAmount });
-
-    return info;
-}
-
@@ -1,24 +1,24 @@
 import assert from "assert";
 import { AppGeneralPropsRaw } from "../(components)/app-props";
 import { getNewtonSync } from "../(wasm-wrapper)/consts";
-import { RenderPassFn, RenderStateData, State, StateMachineFns, resetFractalData, newRenderData, setRenderStateFinishedRendering } from "./data";
+import { RenderPassFn, RenderStateData, State, StateMachineFns, syncSession, setRenderStateFinishedRendering } from "./data";
 import { StateMachineProps } from "./state-machine";
-import { drawRoots, renderToCanvasRow } from "./render";
+import { drawRoots, renderNextPass } from "./render";
 import { isValidFormula } from "../(wasm-wrapper)/util";
 
-export const newCalculatePassFn = (postSetupFn: (data: RenderStateData) => void, recalculate: boolean) => {
+// Whether this recalculates or only recolors is up to the session, which compares the props against what it has
+export const newSessionPassFn = (postSetupFn: (data: RenderStateData) => void) => {
     return (generalProps: AppGeneralPropsRaw, stateMachine: StateMachineProps) => {
         if (!getNewtonSync()) return false;
         if (!isValidFormula(generalProps.formula)) return false;
 
         const { data } = stateMachine;
         const fns: StateMachineFns = { prePassFn, passFn, postPassFn };
-        const renderData = newRenderData();
-        const fractalData = resetFractalData(data.current?.fractalData, generalProps, recalculate);
+        const session = syncSession(data.current?.session, generalProps);
         const state = State.RENDER_PASS;
-        generalProps.isRendering = !!fractalData;
+        generalProps.isRendering = !!session;
 
-        data.current = { fns, generalProps, state, renderData, fractalData };
+        data.current = { fns, generalProps, state, session };
         postSetupFn(data.current);
         return true;
     }
@@ -26,27 +26,20 @@ export const newCalculatePassFn = (postSetupFn: (data: RenderStateData) => void,
 
 const prePassFn: RenderPassFn = (data: RenderStateData, _context: CanvasRenderingContext2D) => {
     if (data.state == State.DONE) return;
-    if (!data.renderData || !data.fractalData) { setRenderStateFinishedRendering(data); return; }
+    if (!data.session) { setRenderStateFinishedRendering(data); return; }
 }
 
 const passFn: RenderPassFn<boolean> = (data: RenderStateData, context: CanvasRenderingContext2D) => {
     if (data.state == State.DONE) return false;
-    assert(!!data.renderData && !!data.fractalData);
+    assert(!!data.session);
 
-    if (data.renderData.row >= context.canvas.height) {
-        if (data.renderData.scaleFactor == 0) {
-            data.state = State.DONE;
-            return false;
-        }
+    // The threaded build's calculation worker is still busy, so give the frame back until it's done
+    if (data.session.isCalculating()) return false;
 
-        // Let's reset the render with a lower scaling factor
-        data.renderData.row = 0;
-        data.renderData.scaleFactor -= 1;
+    if (!renderNextPass(data, context)) {
+        data.state = State.DONE;
+        return false;
     }
-
-    renderToCanvasRow(data, context);
-    data.renderData.row += 1 << data.renderData.scaleFactor;
-
     return true;
 }
 
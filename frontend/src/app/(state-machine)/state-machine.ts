@@ -4,8 +4,7 @@ import { getNewtonSync } from "../(wasm-wrapper)/consts";
 import { State, RenderStateData, isRenderStateFinishedRendering, setRenderStateFinishedRendering } from "./data";
 import { CanvasDrawFn } from "../(components)/canvas";
 import { AppGeneralPropsRaw } from "../(components)/app-props";
-import { newCalculatePassFn } from "./sm-calculate";
-import { newRecolorPassFn } from "./sm-recolor";
+import { newSessionPassFn } from "./sm-calculate";
 
 // Main render loop here!
 const renderFn = (context: CanvasRenderingContext2D, data: RenderStateData) => {
@@ -42,7 +41,7 @@ export const useStateMachine = () => {
     const stepFn = useCallback<CanvasDrawFn>((context: CanvasRenderingContext2D) => {
         if (!data.current) return;
         if (isRenderStateFinishedRendering(data.current)) return;
-        if (!data.current.renderData) return;
+        if (!data.current.session) return;
         if (!getNewtonSync()) return;
         renderFn(context, data.current);
     }, []);
@@ -73,18 +72,11 @@ const useStateMachineInitFns = () => {
         startTime.current = Date.now();
     };
 
-    const calculateNewPassFn = newCalculatePassFn(postSetupFn, true);
-    const recalculatePassFn = newCalculatePassFn(postSetupFn, false);
-
-    // Bit of a special case here, as we need to recalculate if we're not done the
-    // full calculations. This is because the PDB won't have been filled out completely
-    const _recolorPassFn = newRecolorPassFn(postSetupFn);
-    const recolorPassFn = (generalProps: AppGeneralPropsRaw, stateMachine: StateMachineProps) => {
-        const data = stateMachine.data.current;
-        if (!data) return false;
-        if (data.state == State.RENDER_PASS) { return recalculatePassFn(generalProps, stateMachine); }
-        return _recolorPassFn(generalProps, stateMachine);
-    };
+    // The session works out for itself whether a change of props needs a recalculation or only a
+    // recolor, so every kind of change starts the same way
+    const calculateNewPassFn = newSessionPassFn(postSetupFn);
+    const recalculatePassFn = calculateNewPassFn;
+    const recolorPassFn = calculateNewPassFn;
 
     const initFns: StateMachineInitFns = { calculateNewPassFn, recalculatePassFn, recolorPassFn, updateIsRenderingPassFn };
 
@@ -61,13 +61,5 @@ export const toSplitArray = <T,>(arr: T[], fn: (v: T) => boolean): [T[], T[]] =>
     return [tArray, fArray];
 }
 
-export const dotProduct = (a: number[], b: number[]): number => {
-    return vecMult(a, b).reduce((p, v) => p + v, 0);
-}
-
-export const vecMult = (a: number[], b: number[]): number[] => {
-    return a.map((v, i) => v * b[i]);
-}
-
 export const randRange = (a: number, b: number) => lerp(Math.random(), a, b);
 export const randRangeI = (a: number, b: number) => Math.floor(randRange(a, b + 1));
@@ -0,0 +1,26 @@
+import { CalcWorkerMessage } from "./threads";
+
+// The threaded build's calculation worker, see `startCalcWorker`. Rows are written into buffers in
+// the shared memory, so there's nothing to post back but that it's ready.
+
+type ThreadedNewton = typeof import('@/pkg/newton_wasm') & {
+    default: (init: { module_or_path: WebAssembly.Module, memory: WebAssembly.Memory }) => Promise<unknown>,
+};
+
+let newton: ThreadedNewton | undefined;
+
+self.onmessage = async ({ data }: MessageEvent<CalcWorkerMessage>) => {
+    switch (data.type) {
+        case 'init':
+            newton = await import('@/pkg/newton_wasm') as ThreadedNewton;
+            await newton.default({ module_or_path: data.module, memory: data.memory });
+            self.postMessage({ type: 'ready' });
+            break;
+        case 'calculate':
+            newton?.__calculateShared(data.handle);
+            break;
+        default:
+            const _data: never = data;
+            console.error("Invalid calculation worker message:", _data);
+    }
+}
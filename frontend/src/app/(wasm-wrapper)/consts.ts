@@ -1,3 +1,6 @@
+import { IterMethod } from "./structs";
+import { isThreaded, startCalcWorker } from "./threads";
+
 export type Newton = typeof import('@/pkg/newton_wasm');
 export type Wasm = typeof import('@/pkg/newton_wasm_bg.wasm');
 
@@ -15,8 +18,23 @@ export const getNewton = (): Newton => {
     return _newton;
 }
 
-export const getNewtonAsync = async (): Promise<Newton> => {
-    _newton = await import('@/pkg/newton_wasm');
+let _newtonPromise: Promise<Newton> | undefined;
+
+// Loads the module only once, as the threaded build can't start its workers twice
+export const getNewtonAsync = (): Promise<Newton> => {
+    _newtonPromise ??= loadNewtonAsync();
+    return _newtonPromise;
+}
+
+const loadNewtonAsync = async (): Promise<Newton> => {
+    const newton = await import('@/pkg/newton_wasm');
+    // The threaded build's workers must be running before any rendering
+    if (isThreaded(newton)) {
+        await newton.default();
+        await newton.initThreadPool(navigator.hardwareConcurrency);
+        await startCalcWorker(newton);
+    }
+    _newton = newton;
     return _newton;
 }
 
@@ -30,7 +48,11 @@ export const getWasmSync = (): Wasm | null => {
 }
 
 export const getWasmAsync = async (): Promise<Wasm> => {
-    _wasm = await import("@/pkg/newton_wasm_bg.wasm");
+    // The threaded build's module is instantiated by hand, so its memory has to come from there
+    const newton = await getNewtonAsync();
+    _wasm = isThreaded(newton)
+        ? { memory: newton.__sharedModule()[1] } as Wasm
+        : await import("@/pkg/newton_wasm_bg.wasm");
     return _wasm;
 }
 
@@ -43,6 +65,21 @@ export const initWasmNewtonAsync = async () => {
 
 ///////////////////////////////////////////////////////////////////
 
+let _iterMethods: IterMethod[] | undefined;
+
+export const getIterMethods = () => {
+    if (_iterMethods !== undefined) return _iterMethods;
+    _iterMethods = getNewton().__iterMethods();
+    return _iterMethods;
+}
+
+export const getIterMethodsSync = (): IterMethod[] => {
+    if (!getNewtonSync()) return [];
+    return getIterMethods();
+}
+
+///////////////////////////////////////////////////////////////////
+
 let _canvasSize: number | undefined;
 
 export const getCanvasSize = () => {
@@ -51,6 +88,16 @@ export const getCanvasSize = () => {
     return _canvasSize;
 }
 
+let _threadCount: number | undefined;
+
+// How many rows to calculate per pass, which is 1 unless the threaded build is in use, where each
+// worker gets a row of its own
+export const getThreadCount = () => {
+    if (_threadCount !== undefined) return _threadCount;
+    _threadCount = getNewton().__threadCount();
+    return _threadCount;
+}
+
 let _unitsPerPixel: number | undefined;
 
 export const getUnitsPerPixel = () => {
@@ -1,6 +1,6 @@
-import { Polynomial, Roots } from "@/pkg/newton_wasm";
+import { Polynomial, RenderSession, Roots } from "@/pkg/newton_wasm";
 import { getNewton } from "./consts";
-import { Transform as _Transform, newTransform } from "@/app/(util)/transform";
+import { Point as _Point } from "@/app/(util)/transform";
 
 export const newPolynomial = (formula: string): Polynomial | null => {
     try {
@@ -11,6 +11,47 @@ export const newPolynomial = (formula: string): Polynomial | null => {
     }
 }
 
+export const newCyclePolynomial = (points: number[], degree: number, leadingCoefficients: number[]): Polynomial | null => {
+    try {
+        return getNewton().Polynomial.withSuperattractingCycle(new Float32Array(points), degree, new Float32Array(leadingCoefficients));
+    } catch {
+        return null;
+    }
+}
+
+// Matches newton_core's RandomSpec. Complex coefficients can't be rendered, so aren't offered here.
+export enum CoefficientKind {
+    Integer = "Integer",
+    Float = "Float",
+}
+export interface RandomSpec {
+    minDegree: number, maxDegree: number, kind: CoefficientKind, magnitude: number, density: number,
+    insideUnitDisk: boolean,
+};
+
+export const getCoefficientKind = (kind: CoefficientKind) => {
+    switch (kind) {
+        case CoefficientKind.Integer: return 0;
+        case CoefficientKind.Float: return 1;
+        default:
+            const k: never = kind;
+            throw new Error(`coefficient kind ${k} not accounted for`);
+    }
+}
+
+// Without a spec, integer coefficients and a degree from 3 to 9
+export const newRandomPolynomial = (seed: number, spec?: RandomSpec): Polynomial | null => {
+    try {
+        if (!spec) return getNewton().Polynomial.random(seed);
+        const { minDegree, maxDegree, kind, magnitude, density, insideUnitDisk } = spec;
+        return getNewton().Polynomial.randomWithSpec(
+            seed, minDegree, maxDegree, getCoefficientKind(kind), magnitude, density, insideUnitDisk);
+    } catch (err) {
+        console.error(err);
+        return null;
+    }
+}
+
 export const newRoots = (fz?: Polynomial): Roots | null => {
     if (!fz) return null;
     try {
@@ -21,6 +62,15 @@ export const newRoots = (fz?: Polynomial): Roots | null => {
     }
 }
 
+export const newRenderSession = (formula: string, viewport: Viewport): RenderSession | null => {
+    try {
+        return new (getNewton().RenderSession)(formula, viewport);
+    } catch (err) {
+        console.error(err);
+        return null;
+    }
+}
+
 export enum LightnessMode {
     Normal = "Normal",
     Inverted = "Inverted",
@@ -40,6 +90,23 @@ export const getLightnessMode = (lightnessMode: LightnessMode) => {
     }
 }
 
+export enum Shading {
+    Iterations = "Iterations",
+    Residual = "Residual",
+    StepSize = "Step Size",
+}
+
+export const getShading = (shading: Shading) => {
+    switch (shading) {
+        case Shading.Iterations: return 0;
+        case Shading.Residual: return 1;
+        case Shading.StepSize: return 2;
+        default:
+            const mode: never = shading;
+            throw new Error(`shading ${mode} not accounted for`);
+    }
+}
+
 export enum NonConvergence {
     Black = "Black",
     White = "White",
@@ -55,12 +122,13 @@ export const getNonConvergence = (nonConvergence: NonConvergence): boolean => {
     }
 }
 
-export type Transform = _Transform;
-export const __newTransform = (scale: number, x: number, y: number): Transform => {
-    return newTransform(scale, x, y);
-}
-
 export interface Complex { re: number, im: number };
+export interface Viewport { width: number, height: number, center: Complex, scale: number, rotation: number, shear: number };
+
+export type Point = _Point;
+export const __newPoint = (x: number, y: number): Point => {
+    return { x, y };
+}
 export const __newComplex = (re: number, im: number): Complex => {
     return { re, im };
 }
@@ -70,23 +138,24 @@ export const __newOklchColor = (h: number, c: number): OklchColor => {
     return { h, c };
 }
 
-export enum IterRootMethod {
-    NewtonsMethod = "Newton's Method",
-    SchroedersMethod = "Schröder's Method for Multiple Roots",
-    SchroedersMethod2 = "Schröder's Method #2",
-    HalleysMethod = "Halley's Method",
-    SteffensonsMethod = "Steffenson's Method",
-}
+// Matches newton_core's Scene, as read and written by the CLI. Lightness is one of the modes by name, or
+// any other lightness curve as text.
+export type SceneLightness = string;
+export type SceneChaos = "black" | "white";
+export interface SceneView { center: [number, number], scale: number, rotation: number, shear: number };
+export interface SceneColoring {
+    lightness: SceneLightness, dropoff: number, chaos: SceneChaos,
+    // A gradient palette in its text format, which only the CLI renders
+    palette?: string,
+};
+export interface Scene {
+    version: number, formula: string, method: number, params: number[],
+    view: SceneView, coloring: SceneColoring, rootColors?: OklchColor[],
+};
 
-export const getIterMethod = (iterMethod: IterRootMethod) => {
-    switch (iterMethod) {
-        case IterRootMethod.NewtonsMethod: return 0;
-        case IterRootMethod.SchroedersMethod: return 1;
-        case IterRootMethod.SchroedersMethod2: return 2;
-        case IterRootMethod.HalleysMethod: return 3;
-        case IterRootMethod.SteffensonsMethod: return 4;
-        default:
-            const method: never = iterMethod;
-            throw new Error(`iterMethod ${method} not accounted for`);
-    }
-}
+export interface MethodParam { name: string, default: number, min: number, max: number };
+export interface IterMethod { id: number, name: string, formula: string, derivativeOrder: number, params: MethodParam[] };
+export interface OrbitStep { z: Complex, residual: number };
+export type CriticalOrbit = { point: Complex } & (
+    { fate: "root", root: number } | { fate: "cycle", period: number } | { fate: "undetermined" }
+);
@@ -0,0 +1,43 @@
+import { Newton } from "./consts";
+
+// What the threaded build exports on top of everything else. It's built for the web target, so it
+// has to be instantiated by hand.
+interface ThreadedNewton {
+    default: (init?: { module_or_path: WebAssembly.Module, memory: WebAssembly.Memory }) => Promise<unknown>,
+    initThreadPool: (threadCount: number) => Promise<void>,
+    __sharedModule: () => [WebAssembly.Module, WebAssembly.Memory],
+}
+
+export const isThreaded = (newton: Newton): newton is Newton & ThreadedNewton => {
+    return 'initThreadPool' in newton && typeof newton.initThreadPool == 'function';
+}
+
+export type CalcWorkerMessage =
+    | { type: 'init', module: WebAssembly.Module, memory: WebAssembly.Memory }
+    | { type: 'calculate', handle: number };
+
+///////////////////////////////////////////////////////////////////
+
+let _calcWorker: Worker | undefined;
+
+// Rayon blocks while it waits on its workers, which browsers only allow off the main thread, so the
+// threaded build calculates rows on a worker running the same module on the same memory
+export const startCalcWorker = async (newton: Newton & ThreadedNewton) => {
+    const [module, memory] = newton.__sharedModule();
+    const worker = new Worker(new URL('./calc-worker.ts', import.meta.url), { type: 'module' });
+    worker.onerror = err => console.error("Calculation worker failed:", err);
+
+    const ready = new Promise(resolve => worker.addEventListener('message', resolve, { once: true }));
+    worker.postMessage({ type: 'init', module, memory } satisfies CalcWorkerMessage);
+    await ready;
+    _calcWorker = worker;
+}
+
+export const hasCalcWorker = () => !!_calcWorker;
+
+// Hands over a handle from `RenderSession.startRows`. The session picks the rows back up with
+// `finishRows`, once `isCalculating` says they're done.
+export const calculateOnWorker = (handle: number) => {
+    if (!_calcWorker) throw new Error("The calculation worker isn't running");
+    _calcWorker.postMessage({ type: 'calculate', handle } satisfies CalcWorkerMessage);
+}
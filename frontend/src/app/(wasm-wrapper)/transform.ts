@@ -1,23 +1,16 @@
-/* eslint-disable @typescript-eslint/no-non-null-assertion */
-import { Transform, invert, translate } from "../(util)/transform";
+import { Transform } from "../(util)/transform";
 import { getCanvasSize, getUnitsPerPixel } from "./consts";
+import { Viewport } from "./structs";
 
-export const unitToPixelTransform = (transform: Transform) => {
-    return invert(canvasToUnitTransform(transform));
-}
-
-export const toCanvasCenterOrigin = () => {
-    const canvasSize = getCanvasSize();
-    return translate({ x: -canvasSize / 2, y: -canvasSize / 2 });
-}
-
-export const canvasToUnitTransform = (transform: Transform) => {
+// The transform's scale is relative to the default window, which spans the shorter side of the canvas.
+// Rotation is in degrees.
+export const toViewport = (transform: Transform, rotation: number, width: number, height: number): Viewport => {
     return {
-        ...transform,
-        scale: transform.scale * getUnitsPerPixel(),
+        width,
+        height,
+        center: { re: transform.translate.x, im: transform.translate.y },
+        scale: transform.scale * getUnitsPerPixel() * getCanvasSize() / 2,
+        rotation: rotation * Math.PI / 180,
+        shear: 0,
     };
 }
-
-export const unitToCanvasTransform = (transform: Transform) => {
-    return invert(canvasToUnitTransform(transform));
-}
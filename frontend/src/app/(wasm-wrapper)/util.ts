@@ -17,7 +17,7 @@ export const isValidFormula = (formula?: string) => {
         .map(terms => terms.split("-").filter(nonEmpty))
         .flat();
 
-    const re = new RegExp(/^(\d+)?z(\^\d+)?$|^(\d+)$/);
+    const re = new RegExp(/^(\d+(\.\d+)?)?z(\^\d+)?$|^(\d+(\.\d+)?)$/);
     return terms.length > 0 && terms.every(term => term.match(re));
 }
 
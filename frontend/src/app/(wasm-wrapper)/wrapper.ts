@@ -1,34 +1,135 @@
 import { PixelDataBuffer, Polynomial, Roots } from "@/pkg/newton_wasm";
 import { getNewton } from "./consts";
-import { Transform } from "@/app/(util)/transform";
-import { IterRootMethod, LightnessMode, NonConvergence, getIterMethod, getLightnessMode, getNonConvergence } from "./structs";
+import { Complex, LightnessMode, NonConvergence, OklchColor, OrbitStep, Shading, getLightnessMode, getNonConvergence, getShading, newPolynomial, newRoots, Point, Scene, Viewport } from "./structs";
 import { calcDropoff } from "./util";
 
-export const calculateRow = (fz: Polynomial, roots: Roots, transform: Transform, iterMethod: IterRootMethod, renderScale: number, row: number): PixelDataBuffer => {
-    const method = getIterMethod(iterMethod);
-    return getNewton().__calculateRow(fz, roots, transform, method, renderScale, row);
+export const calculateRow = (fz: Polynomial, roots: Roots, viewport: Viewport, iterMethod: number, renderScale: number, row: number): PixelDataBuffer => {
+    // An empty parameter list selects the method's defaults
+    return getNewton().__calculateRow(fz, roots, viewport, iterMethod, new Float32Array(), renderScale, row);
+}
+
+export const calculateRows = (
+    fz: Polynomial, roots: Roots, viewport: Viewport, iterMethod: number, renderScale: number, row: number, rowCount: number,
+): PixelDataBuffer => {
+    return getNewton().__calculateRows(fz, roots, viewport, iterMethod, new Float32Array(), renderScale, row, rowCount);
+}
+
+// Full records per pixel, for shading by anything but iterations. Render them into a buffer from newImageRecordBuffer.
+export const calculateRecordRows = (
+    fz: Polynomial, roots: Roots, viewport: Viewport, iterMethod: number, renderScale: number, row: number, rowCount: number,
+): PixelDataBuffer => {
+    return getNewton().__calculateRecordRows(fz, roots, viewport, iterMethod, new Float32Array(), renderScale, row, rowCount);
 }
 
 export const renderRow = (
     context: CanvasRenderingContext2D, roots: Roots, pdb: PixelDataBuffer, pdbRow: PixelDataBuffer, renderScale: number, row: number,
-    dropoff: number, lightnessMode: LightnessMode, nonConvergence: NonConvergence,
+    dropoff: number, lightnessMode: LightnessMode, nonConvergence: NonConvergence, shading = Shading.Iterations,
 ) => {
     const _dropoff = calcDropoff(dropoff);
     const _lightnessMode = getLightnessMode(lightnessMode);
+    const _shading = getShading(shading);
     const _nonConvergence = getNonConvergence(nonConvergence);
-    getNewton().__renderRow(context, roots, pdb, pdbRow, renderScale, row, _dropoff, _lightnessMode, _nonConvergence);
+    getNewton().__renderRow(context, roots, pdb, pdbRow, renderScale, row, _dropoff, _lightnessMode, _shading, _nonConvergence);
 }
 
 export const recolorRow = (
     context: CanvasRenderingContext2D, roots: Roots, pdb: PixelDataBuffer, row: number,
-    dropoff: number, lightnessMode: LightnessMode, nonConvergence: NonConvergence,
+    dropoff: number, lightnessMode: LightnessMode, nonConvergence: NonConvergence, shading = Shading.Iterations,
 ) => {
     const _dropoff = calcDropoff(dropoff);
     const _lightnessMode = getLightnessMode(lightnessMode);
+    const _shading = getShading(shading);
     const _nonConvergence = getNonConvergence(nonConvergence);
-    getNewton().__recolorRow(context, roots, pdb, row, _dropoff, _lightnessMode, _nonConvergence);
+    getNewton().__recolorRow(context, roots, pdb, row, _dropoff, _lightnessMode, _shading, _nonConvergence);
+}
+
+export const newImagePixelDataBuffer = (width: number, height: number): PixelDataBuffer => {
+    return getNewton().__newImagePixelDataBuffer(width, height);
+}
+
+export const newImageRecordBuffer = (width: number, height: number): PixelDataBuffer => {
+    return getNewton().__newImageRecordBuffer(width, height);
+}
+
+export const pixelToComplex = (viewport: Viewport, x: number, y: number): Complex => {
+    return getNewton().__pixelToComplex(viewport, x, y);
+}
+
+export const complexToPixel = (viewport: Viewport, z: Complex): Point => {
+    return getNewton().__complexToPixel(viewport, z);
+}
+
+// Root colors from one of newton_core's color schemes, by id
+export const schemeColors = (
+    roots: Complex[], scheme: number, hueOffset: number, chromaticity: number, staticHues: boolean,
+): OklchColor[] => {
+    return getNewton().__schemeColors(roots, scheme, hueOffset, chromaticity, staticHues);
+}
+
+// Lightness at evenly spaced convergence speeds from 0 to 1, for drawing a curve. Throws if it's invalid.
+export const sampleLightnessCurve = (curve: string, count: number): Float32Array => {
+    return getNewton().__sampleLightnessCurve(curve, count);
+}
+
+export const isValidLightnessCurve = (curve: string): boolean => {
+    try {
+        sampleLightnessCurve(curve, 0);
+        return true;
+    } catch {
+        return false;
+    }
+}
+
+export const orbit = (fz: Polynomial, iterMethod: number, z0: Complex): OrbitStep[] => {
+    return getNewton().__orbit(fz, iterMethod, new Float32Array(), z0);
+}
+
+// Periods of the attracting cycles the free critical points fall into, if any
+export const attractingCyclePeriods = (formula: string, iterMethod: number): number[] => {
+    const fz = newPolynomial(formula) ?? undefined;
+    const roots = newRoots(fz);
+    try {
+        if (!fz || !roots) return [];
+        const orbits = getNewton().__criticalOrbits(fz, roots, iterMethod, new Float32Array());
+        const periods = orbits.flatMap(orbit => orbit.fate == "cycle" ? [orbit.period] : []);
+        return [...new Set(periods)].sort((a, b) => a - b);
+    } catch {
+        // Not every method supports critical point analysis
+        return [];
+    } finally {
+        fz?.free();
+        roots?.free();
+    }
+}
+
+export const polynomialRoots = (formula: string): Complex[] => {
+    const fz = newPolynomial(formula) ?? undefined;
+    const roots = newRoots(fz);
+    try {
+        return roots?.roots() ?? [];
+    } finally {
+        fz?.free();
+        roots?.free();
+    }
+}
+
+// Both throw with a message fit for the user if the scene can't be rendered
+export const parseScene = (json: string): Scene => {
+    return getNewton().__parseScene(json);
+}
+
+export const sceneToJson = (scene: Scene): string => {
+    return getNewton().__sceneToJson(scene);
+}
+
+export const encodeScene = (scene: Scene): string => {
+    return getNewton().__encodeScene(scene);
+}
+
+export const decodeScene = (code: string): Scene => {
+    return getNewton().__decodeScene(code);
 }
 
-export const newImagePixelDataBuffer = (): PixelDataBuffer => {
-    return getNewton().__newImagePixelDataBuffer();
+export const pngScene = (bytes: Uint8Array): Scene => {
+    return getNewton().__pngScene(bytes);
 }